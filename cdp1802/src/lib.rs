@@ -0,0 +1,316 @@
+// RCA CDP1802, the COSMAC VIP's CPU. Only the processor: memory, I/O and
+// the EF flag lines belong to whatever it is plugged into, through Bus.
+#![no_std]
+
+pub trait Bus {
+    fn read(&mut self, addr: u16) -> u8;
+    fn write(&mut self, addr: u16, val: u8);
+    // OUT 1-7.
+    fn output(&mut self, _port: u8, _val: u8) {}
+    // INP 1-7.
+    fn input(&mut self, _port: u8) -> u8 {
+        0
+    }
+    // EF1-EF4, numbered from 1 like the branches that test them.
+    fn flag(&mut self, _ef: u8) -> bool {
+        false
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Cpu {
+    // Scratchpad registers R0-RF.
+    pub r: [u16; 16],
+    // Accumulator and its carry/no-borrow flag.
+    pub d: u8,
+    pub df: bool,
+    // Which R is the program counter, and which the data pointer.
+    pub p: u8,
+    pub x: u8,
+    // X and P saved by an interrupt or MARK.
+    pub t: u8,
+    // Interrupts enabled.
+    pub ie: bool,
+    // The Q output line.
+    pub q: bool,
+    // Set by IDL; step() does nothing until an interrupt clears it.
+    pub idle: bool,
+}
+
+impl Default for Cpu {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Cpu {
+    // State after a hardware reset: P, X, R0 and Q cleared, interrupts on.
+    pub fn new() -> Self {
+        Cpu {
+            r: [0; 16],
+            d: 0,
+            df: false,
+            p: 0,
+            x: 0,
+            t: 0,
+            ie: true,
+            q: false,
+            idle: false,
+        }
+    }
+
+    // Takes an interrupt if they are enabled: X and P go to T, R1 becomes
+    // the program counter and R2 the data pointer.
+    pub fn interrupt(&mut self) {
+        if self.ie {
+            self.t = self.x << 4 | self.p;
+            self.p = 1;
+            self.x = 2;
+            self.ie = false;
+            self.idle = false;
+        }
+    }
+
+    // Runs one instruction and returns how many machine cycles (8 clocks
+    // each) it took.
+    pub fn step<B: Bus>(&mut self, bus: &mut B) -> u32 {
+        if self.idle {
+            return 1;
+        }
+        let op = self.fetch(bus);
+        let n = op & 0xF;
+        let rn = n as usize;
+        match op >> 4 {
+            0x0 if n == 0 => self.idle = true,
+            // LDN
+            0x0 => self.d = bus.read(self.r[rn]),
+            // INC, DEC
+            0x1 => self.r[rn] = self.r[rn].wrapping_add(1),
+            0x2 => self.r[rn] = self.r[rn].wrapping_sub(1),
+            // Short branches within the current page.
+            0x3 => {
+                let taken = match n & 7 {
+                    0 => true,
+                    1 => self.q,
+                    2 => self.d == 0,
+                    3 => self.df,
+                    ef => bus.flag(ef - 3),
+                };
+                // 38 (SKP) is the negation of BR.
+                if taken != (n >= 8) {
+                    let target = bus.read(self.pc());
+                    self.set_pc(self.pc() & 0xFF00 | target as u16);
+                } else {
+                    self.set_pc(self.pc().wrapping_add(1));
+                }
+            }
+            // LDA
+            0x4 => {
+                self.d = bus.read(self.r[rn]);
+                self.r[rn] = self.r[rn].wrapping_add(1);
+            }
+            // STR
+            0x5 => bus.write(self.r[rn], self.d),
+            0x6 => self.io(bus, n),
+            0x7 => self.misc(bus, n),
+            // GLO, GHI, PLO, PHI
+            0x8 => self.d = self.r[rn] as u8,
+            0x9 => self.d = (self.r[rn] >> 8) as u8,
+            0xA => self.r[rn] = self.r[rn] & 0xFF00 | self.d as u16,
+            0xB => self.r[rn] = self.r[rn] & 0x00FF | (self.d as u16) << 8,
+            0xC => {
+                self.long_branch(bus, n);
+                return 3;
+            }
+            // SEP, SEX
+            0xD => self.p = n,
+            0xE => self.x = n,
+            _ => self.alu(bus, n),
+        }
+        2
+    }
+
+    fn pc(&self) -> u16 {
+        self.r[self.p as usize]
+    }
+
+    fn set_pc(&mut self, addr: u16) {
+        self.r[self.p as usize] = addr;
+    }
+
+    fn fetch<B: Bus>(&mut self, bus: &mut B) -> u8 {
+        let byte = bus.read(self.pc());
+        self.set_pc(self.pc().wrapping_add(1));
+        byte
+    }
+
+    fn rx(&self) -> u16 {
+        self.r[self.x as usize]
+    }
+
+    fn inc_x(&mut self) {
+        self.r[self.x as usize] = self.rx().wrapping_add(1);
+    }
+
+    // 60 IRX, 61-67 OUT, 69-6F INP. 68 is unused on the 1802.
+    fn io<B: Bus>(&mut self, bus: &mut B, n: u8) {
+        match n {
+            0 => self.inc_x(),
+            1..=7 => {
+                let val = bus.read(self.rx());
+                bus.output(n, val);
+                self.inc_x();
+            }
+            8 => {}
+            _ => {
+                let val = bus.input(n - 8);
+                bus.write(self.rx(), val);
+                self.d = val;
+            }
+        }
+    }
+
+    fn misc<B: Bus>(&mut self, bus: &mut B, n: u8) {
+        match n {
+            // RET, DIS
+            0 | 1 => {
+                let xp = bus.read(self.rx());
+                self.inc_x();
+                self.x = xp >> 4;
+                self.p = xp & 0xF;
+                self.ie = n == 0;
+            }
+            // LDXA
+            2 => {
+                self.d = bus.read(self.rx());
+                self.inc_x();
+            }
+            // STXD
+            3 => {
+                bus.write(self.rx(), self.d);
+                self.r[self.x as usize] = self.rx().wrapping_sub(1);
+            }
+            // ADC, SDB, SMB
+            4 => self.add(bus.read(self.rx()), self.df),
+            5 => self.sub(bus.read(self.rx()), self.d, self.df),
+            7 => self.sub(self.d, bus.read(self.rx()), self.df),
+            // SHRC
+            6 => {
+                let carry = self.df;
+                self.df = self.d & 1 != 0;
+                self.d = self.d >> 1 | (carry as u8) << 7;
+            }
+            // SAV
+            8 => bus.write(self.rx(), self.t),
+            // MARK
+            9 => {
+                self.t = self.x << 4 | self.p;
+                bus.write(self.r[2], self.t);
+                self.x = self.p;
+                self.r[2] = self.r[2].wrapping_sub(1);
+            }
+            // REQ, SEQ
+            0xA | 0xB => self.q = n == 0xB,
+            // ADCI, SDBI, SMBI
+            0xC => {
+                let imm = self.fetch(bus);
+                self.add(imm, self.df);
+            }
+            0xD => {
+                let imm = self.fetch(bus);
+                self.sub(imm, self.d, self.df);
+            }
+            0xF => {
+                let imm = self.fetch(bus);
+                self.sub(self.d, imm, self.df);
+            }
+            // SHLC
+            _ => {
+                let carry = self.df;
+                self.df = self.d & 0x80 != 0;
+                self.d = self.d << 1 | carry as u8;
+            }
+        }
+    }
+
+    // C0-CF: long branches to the next two bytes, or long skips over them.
+    fn long_branch<B: Bus>(&mut self, bus: &mut B, n: u8) {
+        let condition = match n & 3 {
+            0 => true,
+            1 => self.q,
+            2 => self.d == 0,
+            _ => self.df,
+        };
+        // C4 is NOP, and CC tests IE instead of branching unconditionally.
+        let (skip, taken) = match n {
+            0x4 => (true, false),
+            0xC => (true, self.ie),
+            0x5..=0x7 => (true, !condition),
+            0x8..=0xB => (n == 0x8, !condition || n == 0x8),
+            0xD..=0xF => (true, condition),
+            _ => (false, condition),
+        };
+        if skip {
+            if taken {
+                self.set_pc(self.pc().wrapping_add(2));
+            }
+        } else if taken {
+            let hi = bus.read(self.pc());
+            let lo = bus.read(self.pc().wrapping_add(1));
+            self.set_pc(u16::from_be_bytes([hi, lo]));
+        } else {
+            self.set_pc(self.pc().wrapping_add(2));
+        }
+    }
+
+    // F0-FF: logic and arithmetic on D with M(R(X)), or with the next byte
+    // from F8 up.
+    fn alu<B: Bus>(&mut self, bus: &mut B, n: u8) {
+        if n == 0 {
+            self.d = bus.read(self.rx());
+            return;
+        }
+        if n == 8 {
+            self.d = self.fetch(bus);
+            return;
+        }
+        if n & 7 == 6 {
+            // SHR, SHL
+            if n == 6 {
+                self.df = self.d & 1 != 0;
+                self.d >>= 1;
+            } else {
+                self.df = self.d & 0x80 != 0;
+                self.d <<= 1;
+            }
+            return;
+        }
+        let m = if n < 8 {
+            bus.read(self.rx())
+        } else {
+            self.fetch(bus)
+        };
+        match n & 7 {
+            1 => self.d |= m,
+            2 => self.d &= m,
+            3 => self.d ^= m,
+            4 => self.add(m, false),
+            5 => self.sub(m, self.d, true),
+            _ => self.sub(self.d, m, true),
+        }
+    }
+
+    fn add(&mut self, m: u8, carry: bool) {
+        let sum = self.d as u16 + m as u16 + carry as u16;
+        self.d = sum as u8;
+        self.df = sum > 0xFF;
+    }
+
+    // D = a - b, less one unless `no_borrow`. DF is set when nothing was
+    // borrowed.
+    fn sub(&mut self, a: u8, b: u8, no_borrow: bool) {
+        let diff = a as i16 - b as i16 - !no_borrow as i16;
+        self.d = diff as u8;
+        self.df = diff >= 0;
+    }
+}
@@ -0,0 +1,110 @@
+use cdp1802::{Bus, Cpu};
+
+struct Ram([u8; 256]);
+
+impl Bus for Ram {
+    fn read(&mut self, addr: u16) -> u8 {
+        self.0[addr as usize % 256]
+    }
+    fn write(&mut self, addr: u16, val: u8) {
+        self.0[addr as usize % 256] = val;
+    }
+}
+
+// Loads `program` at 0 and steps until it reaches IDL.
+fn run(program: &[u8]) -> (Cpu, Ram) {
+    let mut ram = Ram([0; 256]);
+    ram.0[..program.len()].copy_from_slice(program);
+    let mut cpu = Cpu::new();
+    for _ in 0..1000 {
+        cpu.step(&mut ram);
+        if cpu.idle {
+            return (cpu, ram);
+        }
+    }
+    panic!("program did not finish");
+}
+
+#[test]
+fn loops_until_zero() {
+    let (cpu, _) = run(&[
+        0xF8, 0x05, // LDI 5
+        0xA2, // PLO R2
+        0xF8, 0x00, // LDI 0
+        0xA3, // PLO R3: total
+        0x83, // loop: GLO R3
+        0xFC, 0x03, // ADI 3
+        0xA3, // PLO R3
+        0x22, // DEC R2
+        0x82, // GLO R2
+        0x3A, 0x06, // BNZ loop
+        0x00, // IDL
+    ]);
+    assert_eq!(cpu.r[3], 15);
+    assert_eq!(cpu.r[2], 0);
+}
+
+#[test]
+fn subtraction_flags_mean_no_borrow() {
+    let (cpu, _) = run(&[0xF8, 0x05, 0xFF, 0x07, 0x00]); // LDI 5, SMI 7, IDL
+    assert_eq!((cpu.d, cpu.df), (0xFE, false));
+    let (cpu, _) = run(&[0xF8, 0x05, 0xFD, 0x07, 0x00]); // LDI 5, SDI 7, IDL
+    assert_eq!((cpu.d, cpu.df), (2, true));
+    let (cpu, _) = run(&[0xF8, 0xFF, 0xFC, 0x02, 0x7C, 0x00, 0x00]); // ADI, ADCI
+    assert_eq!((cpu.d, cpu.df), (2, false));
+}
+
+#[test]
+fn subroutine_by_sep() {
+    let mut program = vec![
+        0xF8, 0x40, // LDI 40
+        0xA3, // PLO R3: subroutine address
+        0xF8, 0xF0, // LDI F0
+        0xA2, // PLO R2: stack
+        0xD3, // SEP R3
+        0x00, // IDL, after the subroutine
+    ];
+    program.resize(0x40, 0);
+    program.extend([
+        0xF8, 0x2A, // LDI 2A
+        0xE2, // SEX 2
+        0x73, // STXD
+        0xD0, // SEP R0 back to the caller
+    ]);
+    let (cpu, ram) = run(&program);
+    assert_eq!(cpu.p, 0);
+    assert_eq!(cpu.r[0], 8);
+    assert_eq!(ram.0[0xF0], 0x2A);
+    assert_eq!(cpu.r[2], 0xEF);
+}
+
+#[test]
+fn mark_and_ret() {
+    let (cpu, _) = run(&[
+        0xF8, 0xF0, // LDI F0
+        0xA2, // PLO R2
+        0xE5, // SEX 5
+        0x79, // MARK: saves X=5, P=0 at F0, X = P
+        0x12, // INC R2
+        0xE2, // SEX 2
+        0x71, // DIS: X=5, P=0 again, interrupts off
+        0x00,
+    ]);
+    assert_eq!((cpu.x, cpu.p, cpu.t), (5, 0, 0x50));
+    assert!(!cpu.ie);
+    assert_eq!(cpu.r[2], 0xF1);
+}
+
+#[test]
+fn long_branch_and_skip() {
+    let (cpu, _) = run(&[
+        0xC0, 0x00, 0x06, // LBR 0006
+        0xF8, 0x11, // skipped
+        0x00, // not reached
+        0xC8, // LSKP
+        0xF8, 0x22, // skipped
+        0xF8, 0x33, // LDI 33
+        0x00,
+    ]);
+    assert_eq!(cpu.d, 0x33);
+}
@@ -0,0 +1,53 @@
+// Instructions per second of Emu::tick on a few workloads. To compare
+// against another revision, save a baseline there and diff against it:
+//
+// $ cargo bench -p chip8_core --bench throughput -- --save-baseline before
+// $ cargo bench -p chip8_core --bench throughput -- --baseline before
+//
+// With `--features jit` each workload is also run through the JIT.
+use chip8_core::*;
+use criterion::{Criterion, Throughput, criterion_group, criterion_main};
+use std::hint::black_box;
+
+const TICKS: u64 = 10_000;
+
+// Tight ALU loop: 7XNN, 8XY4, 8XY3, ANNN, FX1E, 3XNN, 1NNN.
+const ALU_LOOP: [u8; 18] = [
+    0x60, 0x00, 0x70, 0x01, 0x81, 0x04, 0x82, 0x13, 0xA3, 0x00, 0xF1, 0x1E, 0x30, 0x00, 0x12, 0x02,
+    0x12, 0x00,
+];
+
+fn bench_rom(c: &mut Criterion, name: &str, rom: &[u8]) {
+    let mut group = c.benchmark_group(name);
+    group.throughput(Throughput::Elements(TICKS));
+    group.bench_function("tick", |b| {
+        let mut emu = Emu::new(|| 0);
+        emu.load(rom).unwrap();
+        b.iter(|| {
+            for _ in 0..TICKS {
+                black_box(emu.tick()).unwrap();
+            }
+        })
+    });
+    #[cfg(all(feature = "jit", target_arch = "x86_64", target_os = "linux"))]
+    group.bench_function("jit", |b| {
+        let mut emu = Emu::new(|| 0);
+        emu.load(rom).unwrap();
+        let mut jit = Jit::new().unwrap();
+        b.iter(|| jit.run(black_box(&mut emu), TICKS as usize).unwrap())
+    });
+    group.finish();
+}
+
+fn throughput(c: &mut Criterion) {
+    bench_rom(c, "alu_loop", &ALU_LOOP);
+    bench_rom(
+        c,
+        "font_grid",
+        include_bytes!("../../test_roms/font_grid.ch8"),
+    );
+    bench_rom(c, "arith", include_bytes!("../../test_roms/arith.ch8"));
+}
+
+criterion_group!(benches, throughput);
+criterion_main!(benches);
@@ -0,0 +1,153 @@
+// Octo's cartridges: GIF pictures of a program's label with the program
+// hidden in them. The low two bits of every pixel's palette index, four
+// pixels to a byte and the first frame first, make a big-endian length and
+// then that much JSON: the program's Octo source and the options Octo runs
+// it with.
+use crate::{OctoError, Platform, Quirks, RomInfo, assemble, romdb::parse_color};
+use serde::Deserialize;
+use std::fmt;
+
+#[derive(Debug)]
+pub enum CartridgeError {
+    // Not a GIF, or a broken one.
+    Gif(String),
+    // The length at the start runs past the picture.
+    Truncated,
+    Json(String),
+}
+
+impl fmt::Display for CartridgeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CartridgeError::Gif(e) => write!(f, "not a cartridge: {}", e),
+            CartridgeError::Truncated => write!(f, "cartridge is truncated"),
+            CartridgeError::Json(e) => write!(f, "cartridge is damaged: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for CartridgeError {}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Cartridge {
+    // Octo source; `rom` assembles it.
+    pub program: String,
+    // Octo runs everything as XO-CHIP, but the assembler only takes what
+    // Emu runs, so the platform is ModernChip8 with the cartridge's quirks.
+    // The palette is the background, the two planes' colours and the
+    // colour where they overlap. Cartridges have no title, so it is left
+    // for the loader to fill in.
+    pub info: RomInfo,
+}
+
+impl Cartridge {
+    pub fn detect(data: &[u8]) -> bool {
+        data.starts_with(b"GIF87a") || data.starts_with(b"GIF89a")
+    }
+
+    pub fn decode(gif: &[u8]) -> Result<Cartridge, CartridgeError> {
+        let bad_gif = |e: gif::DecodingError| CartridgeError::Gif(e.to_string());
+        let mut options = gif::DecodeOptions::new();
+        options.set_color_output(gif::ColorOutput::Indexed);
+        let mut decoder = options.read_info(gif).map_err(bad_gif)?;
+        let mut pixels = Vec::new();
+        while let Some(frame) = decoder.read_next_frame().map_err(bad_gif)? {
+            pixels.extend_from_slice(&frame.buffer);
+        }
+        let bytes: Vec<u8> = pixels
+            .chunks_exact(4)
+            .map(|p| p.iter().fold(0, |acc, index| acc << 2 | index & 3))
+            .collect();
+        let len = bytes.get(..4).ok_or(CartridgeError::Truncated)?;
+        let len = u32::from_be_bytes(len.try_into().unwrap()) as usize;
+        let payload = bytes
+            .get(4..4usize.saturating_add(len))
+            .ok_or(CartridgeError::Truncated)?;
+        // Octo writes the JSON a byte per character.
+        let json = match std::str::from_utf8(payload) {
+            Ok(json) => json.to_string(),
+            Err(_) => payload.iter().map(|&b| b as char).collect(),
+        };
+        let payload: Payload =
+            serde_json::from_str(&json).map_err(|e| CartridgeError::Json(e.to_string()))?;
+        Ok(Cartridge {
+            program: payload.program,
+            info: payload.options.info(),
+        })
+    }
+
+    pub fn rom(&self) -> Result<Vec<u8>, OctoError> {
+        assemble(&self.program)
+    }
+}
+
+#[derive(Deserialize)]
+struct Payload {
+    program: String,
+    #[serde(default)]
+    options: Options,
+}
+
+// Octo's options, with its defaults. vfOrderQuirks and vBlankQuirks aren't
+// emulated, and the rest (the buzzer's colours, rotation, touch input and
+// font) are Octo's own business.
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+struct Options {
+    tickrate: u32,
+    shift_quirks: bool,
+    load_store_quirks: bool,
+    jump_quirks: bool,
+    clip_quirks: bool,
+    logic_quirks: bool,
+    background_color: String,
+    fill_color: String,
+    fill_color2: String,
+    blend_color: String,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Options {
+            tickrate: 20,
+            shift_quirks: false,
+            load_store_quirks: false,
+            jump_quirks: false,
+            clip_quirks: false,
+            logic_quirks: false,
+            background_color: "#996600".to_string(),
+            fill_color: "#FFCC00".to_string(),
+            fill_color2: "#FF6600".to_string(),
+            blend_color: "#662200".to_string(),
+        }
+    }
+}
+
+impl Options {
+    fn info(self) -> RomInfo {
+        let colors = [
+            &self.background_color,
+            &self.fill_color,
+            &self.fill_color2,
+            &self.blend_color,
+        ];
+        RomInfo {
+            title: String::new(),
+            platform: Platform::ModernChip8,
+            quirks: Quirks {
+                vf_reset: self.logic_quirks,
+                memory_increment: !self.load_store_quirks,
+                shift_vx: self.shift_quirks,
+                jump_vx: self.jump_quirks,
+                clip_sprites: self.clip_quirks,
+            },
+            tickrate: self.tickrate,
+            palette: colors
+                .iter()
+                .map(|c| parse_color(c))
+                .collect::<Option<_>>()
+                .unwrap_or_default(),
+            keys: Vec::new(),
+        }
+    }
+}
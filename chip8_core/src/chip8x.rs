@@ -0,0 +1,92 @@
+// CHIP-8X colour, from the COSMAC VIP's VP-590 colour board. Colours are
+// attributes laid over the monochrome screen: every 8x1 block of pixels has
+// one foreground colour, and the whole screen shares a background colour.
+use crate::{SCREEN_H, SCREEN_W};
+
+// Colour cells across and down the screen.
+const CELLS_W: usize = SCREEN_W / 8;
+const CELLS_H: usize = SCREEN_H;
+// BXY0 zones are 8x4 pixels, so four cells high.
+const ZONE_H: usize = 4;
+const ZONES_H: usize = CELLS_H / ZONE_H;
+
+// RGB of the VP-590's foreground colours 0-7.
+pub const VP590_FOREGROUND: [[u8; 3]; 8] = [
+    [0x00, 0x00, 0x00], // black
+    [0xFF, 0x00, 0x00], // red
+    [0x00, 0x00, 0xFF], // blue
+    [0xFF, 0x00, 0xFF], // violet
+    [0x00, 0xFF, 0x00], // green
+    [0xFF, 0xFF, 0x00], // yellow
+    [0x00, 0xFF, 0xFF], // aqua
+    [0xFF, 0xFF, 0xFF], // white
+];
+// RGB of the background colours, in the order 02A0 steps through them.
+pub const VP590_BACKGROUND: [[u8; 3]; 4] = [
+    [0x00, 0x00, 0x80], // blue
+    [0x00, 0x00, 0x00], // black
+    [0x00, 0x80, 0x00], // green
+    [0x80, 0x00, 0x00], // red
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ColorLayer {
+    // Foreground colour index per 8x1 cell, row-major.
+    cells: [u8; CELLS_W * CELLS_H],
+    // Index into VP590_BACKGROUND.
+    background: u8,
+}
+
+impl Default for ColorLayer {
+    // Blue background with red pixels, as the interpreter starts up.
+    fn default() -> Self {
+        ColorLayer {
+            cells: [1; CELLS_W * CELLS_H],
+            background: 0,
+        }
+    }
+}
+
+impl ColorLayer {
+    // Colour of the lit pixel at (x, y).
+    pub fn foreground(&self, x: usize, y: usize) -> [u8; 3] {
+        let cell = self.cells[(y % CELLS_H) * CELLS_W + (x / 8) % CELLS_W];
+        VP590_FOREGROUND[cell as usize]
+    }
+
+    pub fn background(&self) -> [u8; 3] {
+        VP590_BACKGROUND[self.background as usize]
+    }
+
+    // 02A0
+    pub(crate) fn cycle_background(&mut self) {
+        self.background = (self.background + 1) % VP590_BACKGROUND.len() as u8;
+    }
+
+    // BXY0: `horizontal` and `vertical` hold the first zone in their low
+    // nibble and how many more to colour in their high nibble.
+    pub(crate) fn color_zones(&mut self, horizontal: u8, vertical: u8, color: u8) {
+        for zone_y in span(vertical) {
+            for zone_x in span(horizontal) {
+                let zone_y = zone_y % ZONES_H;
+                for row in zone_y * ZONE_H..(zone_y + 1) * ZONE_H {
+                    self.cells[row * CELLS_W + zone_x % CELLS_W] = color & 7;
+                }
+            }
+        }
+    }
+
+    // BXYN: `rows` pixel rows from (x, y) down, in the 8 pixel column
+    // holding x.
+    pub(crate) fn color_rows(&mut self, x: u8, y: u8, rows: u8, color: u8) {
+        let column = (x as usize / 8) % CELLS_W;
+        for row in y as usize..y as usize + rows as usize {
+            self.cells[(row % CELLS_H) * CELLS_W + column] = color & 7;
+        }
+    }
+}
+
+fn span(nibbles: u8) -> core::ops::RangeInclusive<usize> {
+    let first = (nibbles & 0xF) as usize;
+    first..=first + (nibbles >> 4) as usize
+}
@@ -0,0 +1,228 @@
+// Guessing a ROM's platform from its contents, for when the ROM database
+// doesn't know it. For each platform the code is followed from where that
+// platform starts it, through jumps, calls and skips, and each opcode only
+// some platforms have counts against the others, as does a CHIP-8 HIRES
+// header or its absence, or a ROM too big for 4K of RAM. Words that ANNN
+// points at are data and are left out, and code pointing below where the
+// platform loads it counts against that platform. Data can still look like
+// anything, so opcodes that are common in sprites count for less.
+use crate::{MAX_ROM_SIZE, Platform, RAM_SIZE};
+
+// How much a platform loses, in halvings of its score, for each opcode it
+// doesn't have.
+const STRONG: u32 = 5;
+const WEAK: u32 = 1;
+// For a size or header that rules a platform out.
+const DECISIVE: u32 = 10;
+
+use Platform::*;
+const SCHIP: &[Platform] = &[SuperChip, SuperChip1, XoChip, MegaChip8];
+const XO: &[Platform] = &[XoChip];
+const MEGA: &[Platform] = &[MegaChip8];
+// Interpreters with a CPU underneath to run 0NNN machine code.
+const NATIVE: &[Platform] = &[
+    OriginalChip8,
+    HybridVip,
+    Chip8X,
+    Chip8Hires,
+    Eti660,
+    Dream6800,
+];
+const NOT_HIRES: &[Platform] = &[
+    OriginalChip8,
+    HybridVip,
+    ModernChip8,
+    Chip8X,
+    Chip48,
+    SuperChip1,
+    SuperChip,
+    MegaChip8,
+    XoChip,
+    Eti660,
+    Dream6800,
+];
+const MEGA_OR_NATIVE: &[Platform] = &[
+    MegaChip8,
+    OriginalChip8,
+    HybridVip,
+    Chip8X,
+    Chip8Hires,
+    Eti660,
+    Dream6800,
+];
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Guess {
+    pub platform: Platform,
+    // Between 0 and 1; the confidences of one classification add up to 1.
+    pub confidence: f32,
+}
+
+impl Platform {
+    // Every platform, most likely first. With nothing to go on, that is
+    // ModernChip8, then the platforms in order of how common their ROMs are.
+    pub fn classify(rom: &[u8]) -> [Guess; Platform::ALL.len()] {
+        let mut penalty = [0u32; Platform::ALL.len()];
+        let mut evidence = |platforms: &[Platform], weight: u32| {
+            for (idx, platform) in Platform::ALL.iter().enumerate() {
+                if !platforms.contains(platform) {
+                    penalty[idx] = penalty[idx].saturating_add(weight);
+                }
+            }
+        };
+
+        // CHIP-8 HIRES programs start after its setup, behind this jump.
+        if rom.starts_with(&[0x12, 0x60]) {
+            evidence(&[Chip8Hires], DECISIVE);
+        } else {
+            evidence(NOT_HIRES, DECISIVE);
+        }
+        if rom.len() > MAX_ROM_SIZE {
+            evidence(&[XoChip, MegaChip8], DECISIVE);
+        }
+
+        for (idx, &platform) in Platform::ALL.iter().enumerate() {
+            let base = platform.load_addr();
+            let mut data = Words::EMPTY;
+            walk(rom, platform, &mut data, |_| ());
+            walk(rom, platform, &mut data, |op| {
+                // Jumps and pointers below the program were written for a
+                // different load address.
+                let below = op & 0xFFF < base;
+                let weight = match opcode_evidence(op) {
+                    _ if below && matches!(op >> 12, 0x1 | 0x2) => STRONG,
+                    _ if below && op >> 12 == 0xA => WEAK,
+                    Some((platforms, weight)) if !platforms.contains(&platform) => weight,
+                    _ => 0,
+                };
+                penalty[idx] = penalty[idx].saturating_add(weight);
+            });
+        }
+
+        let mut guesses = Platform::ALL.map(|platform| Guess {
+            platform,
+            confidence: 0.0,
+        });
+        let least = penalty.iter().copied().min().unwrap_or(0);
+        let mut total = 0.0;
+        for (guess, penalty) in guesses.iter_mut().zip(penalty) {
+            let halvings = (penalty - least).min(31);
+            guess.confidence = guess.platform.prior() / (1u32 << halvings) as f32;
+            total += guess.confidence;
+        }
+        for guess in &mut guesses {
+            guess.confidence /= total;
+        }
+        // Ties keep the order of ALL.
+        guesses.sort_unstable_by(|a, b| {
+            b.confidence.total_cmp(&a.confidence).then_with(|| {
+                let pos = |p: Platform| Platform::ALL.iter().position(|q| *q == p);
+                pos(a.platform).cmp(&pos(b.platform))
+            })
+        });
+        guesses
+    }
+
+    // How likely a ROM is to be for this platform before looking at it.
+    fn prior(self) -> f32 {
+        match self {
+            ModernChip8 => 1.0,
+            SuperChip => 0.5,
+            OriginalChip8 | XoChip => 0.2,
+            SuperChip1 | HybridVip | Chip48 => 0.1,
+            MegaChip8 | Chip8X | Chip8Hires => 0.05,
+            Eti660 | Dream6800 => 0.02,
+        }
+    }
+}
+
+// The platforms that have `op`, and how much it counts against the rest.
+fn opcode_evidence(op: u16) -> Option<(&'static [Platform], u32)> {
+    let (n, nn) = (op & 0xF, op & 0xFF);
+    let evidence = match op >> 12 {
+        // Padding, and what every platform has.
+        0 if op == 0x0000 || op == 0x00E0 || op == 0x00EE => return None,
+        0 if op == 0x0230 => (&[Chip8Hires][..], STRONG),
+        0 if op == 0x02A0 => (&[Chip8X][..], STRONG),
+        0 if op == 0x0010 || op == 0x0011 => (MEGA, STRONG),
+        0 if (0x00FB..=0x00FF).contains(&op) || op & 0xFFF0 == 0x00C0 => (SCHIP, STRONG),
+        0 if op & 0xFFF0 == 0x00D0 => (XO, STRONG),
+        // MegaChip's palette, sprite and sound opcodes share their form
+        // with machine code calls.
+        0 if (0x01..=0x09).contains(&(op >> 8)) => (MEGA_OR_NATIVE, WEAK),
+        0 => (NATIVE, WEAK),
+        5 if n == 1 => (&[Chip8X][..], STRONG),
+        5 if n == 2 || n == 3 => (XO, STRONG),
+        0xD if n == 0 => (SCHIP, WEAK),
+        0xE if nn == 0xF2 || nn == 0xF5 => (&[Chip8X][..], STRONG),
+        0xF if op == 0xF000 || nn == 0x01 || op == 0xF002 || nn == 0x3A => (XO, STRONG),
+        0xF if nn == 0x30 || nn == 0x75 || nn == 0x85 => (SCHIP, STRONG),
+        0xF if nn == 0xF8 || nn == 0xFB => (&[Chip8X][..], STRONG),
+        _ => return None,
+    };
+    Some(evidence)
+}
+
+// One bit for each address in 4K, which is all a 12-bit jump can reach.
+// Past that, code only runs on from what comes before it.
+struct Words([u64; RAM_SIZE / 64]);
+
+impl Words {
+    const EMPTY: Words = Words([0; RAM_SIZE / 64]);
+
+    fn get(&self, addr: u32) -> bool {
+        (addr as usize) < RAM_SIZE && self.0[addr as usize / 64] >> (addr % 64) & 1 != 0
+    }
+
+    fn set(&mut self, addr: u32, on: bool) {
+        if (addr as usize) < RAM_SIZE {
+            let bit = 1 << (addr % 64);
+            let word = &mut self.0[addr as usize / 64];
+            *word = if on { *word | bit } else { *word & !bit };
+        }
+    }
+
+    fn first(&self) -> Option<u32> {
+        let (idx, word) = self.0.iter().enumerate().find(|(_, word)| **word != 0)?;
+        Some(idx as u32 * 64 + word.trailing_zeros())
+    }
+}
+
+// Calls `visit` with each opcode that can run on `platform`, following
+// jumps, calls and both sides of skips from its entry point. The words ANNN
+// points at go in `data`, and code stops short of any already there.
+fn walk(rom: &[u8], platform: Platform, data: &mut Words, mut visit: impl FnMut(u16)) {
+    let base = platform.load_addr() as u32;
+    let end = base + rom.len() as u32;
+    let mut pending = Words::EMPTY;
+    let mut done = Words::EMPTY;
+    pending.set(platform.entry() as u32, true);
+    while let Some(start) = pending.first() {
+        pending.set(start, false);
+        let mut pc = start;
+        while pc >= base && pc + 2 <= end && !done.get(pc) && !data.get(pc) {
+            done.set(pc, true);
+            let at = (pc - base) as usize;
+            let op = u16::from_be_bytes([rom[at], rom[at + 1]]);
+            visit(op);
+            let nnn = (op & 0xFFF) as u32;
+            pc += 2;
+            match op >> 12 {
+                0 if op == 0x00EE || op == 0x00FD => break,
+                0x1 => {
+                    pending.set(nnn, true);
+                    break;
+                }
+                0x2 => pending.set(nnn, true),
+                0x3 | 0x4 | 0x5 | 0x9 | 0xE => pending.set(pc + 2, true),
+                0xA if nnn >= base => data.set(nnn, true),
+                // BNNN goes somewhere only known when it runs; on CHIP-8X
+                // BXYN colours the screen instead.
+                0xB if platform != Chip8X => break,
+                // F000 NNNN takes the next word as an address.
+                0xF if op == 0xF000 => pc += 2,
+                _ => (),
+            }
+        }
+    }
+}
@@ -0,0 +1,87 @@
+// Instructions as text, in the usual CHIP-8 mnemonics (Cowgod's reference)
+// and MegaChip's own for its opcodes. Addresses and immediates are in hex.
+use crate::Instruction;
+use core::fmt;
+
+impl fmt::Display for Instruction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use Instruction::*;
+        match *self {
+            Cls => write!(f, "CLS"),
+            Ret => write!(f, "RET"),
+            HiresCls => write!(f, "CLS HIRES"),
+            CycleBackground => write!(f, "BGCOL"),
+            AddColors(x, y) => write!(f, "ADDC V{:X}, V{:X}", x, y),
+            ColorZones(x, y) => write!(f, "COLZ V{:X}, V{:X}", x, y),
+            ColorRows(x, y, n) => write!(f, "COLR V{:X}, V{:X}, {}", x, y, n),
+            SkipKey2(x) => write!(f, "SKP2 V{:X}", x),
+            SkipNotKey2(x) => write!(f, "SKNP2 V{:X}", x),
+            PortOut(x) => write!(f, "OUT V{:X}", x),
+            PortIn(x) => write!(f, "IN V{:X}", x),
+            MegaOff => write!(f, "MEGAOFF"),
+            MegaOn => write!(f, "MEGAON"),
+            // The low 16 bits are in the next word.
+            LoadLongI(nn) => write!(f, "LDHI I, 0x{:02X}....", nn),
+            LoadPalette(nn) => write!(f, "LDPAL {}", nn),
+            SpriteWidth(nn) => write!(f, "SPRW {}", nn),
+            SpriteHeight(nn) => write!(f, "SPRH {}", nn),
+            ScreenAlpha(nn) => write!(f, "ALPHA 0x{:02X}", nn),
+            PlaySample(n) => write!(f, "DIGISND {}", n),
+            StopSample => write!(f, "STOPSND"),
+            SetBlend(n) => write!(f, "BMODE {}", n),
+            CollisionColor(nn) => write!(f, "CCOL {}", nn),
+            MachineCall(nnn) => write!(f, "SYS 0x{:03X}", nnn),
+            Jump(nnn) => write!(f, "JP 0x{:03X}", nnn),
+            Call(nnn) => write!(f, "CALL 0x{:03X}", nnn),
+            SkipEqImm(x, nn) => write!(f, "SE V{:X}, 0x{:02X}", x, nn),
+            SkipNeImm(x, nn) => write!(f, "SNE V{:X}, 0x{:02X}", x, nn),
+            SkipEqReg(x, y) => write!(f, "SE V{:X}, V{:X}", x, y),
+            LoadImm(x, nn) => write!(f, "LD V{:X}, 0x{:02X}", x, nn),
+            AddImm(x, nn) => write!(f, "ADD V{:X}, 0x{:02X}", x, nn),
+            Move(x, y) => write!(f, "LD V{:X}, V{:X}", x, y),
+            Or(x, y) => write!(f, "OR V{:X}, V{:X}", x, y),
+            And(x, y) => write!(f, "AND V{:X}, V{:X}", x, y),
+            Xor(x, y) => write!(f, "XOR V{:X}, V{:X}", x, y),
+            Add(x, y) => write!(f, "ADD V{:X}, V{:X}", x, y),
+            Sub(x, y) => write!(f, "SUB V{:X}, V{:X}", x, y),
+            ShiftRight(x, y) => write!(f, "SHR V{:X}, V{:X}", x, y),
+            SubN(x, y) => write!(f, "SUBN V{:X}, V{:X}", x, y),
+            ShiftLeft(x, y) => write!(f, "SHL V{:X}, V{:X}", x, y),
+            SkipNeReg(x, y) => write!(f, "SNE V{:X}, V{:X}", x, y),
+            LoadI(nnn) => write!(f, "LD I, 0x{:03X}", nnn),
+            // Which register is added depends on the jump quirk.
+            JumpOffset(_, nnn) => write!(f, "JP V0, 0x{:03X}", nnn),
+            Random(x, nn) => write!(f, "RND V{:X}, 0x{:02X}", x, nn),
+            Draw(x, y, n) => write!(f, "DRW V{:X}, V{:X}, {}", x, y, n),
+            SkipKey(x) => write!(f, "SKP V{:X}", x),
+            SkipNotKey(x) => write!(f, "SKNP V{:X}", x),
+            GetDelay(x) => write!(f, "LD V{:X}, DT", x),
+            WaitKey(x) => write!(f, "LD V{:X}, K", x),
+            SetDelay(x) => write!(f, "LD DT, V{:X}", x),
+            SetSound(x) => write!(f, "LD ST, V{:X}", x),
+            AddI(x) => write!(f, "ADD I, V{:X}", x),
+            Font(x) => write!(f, "LD F, V{:X}", x),
+            Bcd(x) => write!(f, "LD B, V{:X}", x),
+            Store(x) => write!(f, "LD [I], V{:X}", x),
+            Load(x) => write!(f, "LD V{:X}, [I]", x),
+            Invalid(op) => write!(f, "DW 0x{:04X}", op),
+        }
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl crate::Emu {
+    // The instruction at `addr` as the platform decodes it. Opcodes that an
+    // extension claims are shown under the extension's name.
+    pub fn disassemble(&self, addr: u16) -> alloc::string::String {
+        use alloc::string::ToString;
+        let op = u16::from_be_bytes([self.read(addr), self.read(addr.wrapping_add(1))]);
+        match Instruction::decode_for(self.platform, op) {
+            Instruction::Invalid(op) => match self.extension_claiming(op) {
+                Some(ext) => alloc::format!("{} 0x{:04X}", ext.name(), op),
+                None => Instruction::Invalid(op).to_string(),
+            },
+            ins => ins.to_string(),
+        }
+    }
+}
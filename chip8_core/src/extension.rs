@@ -0,0 +1,49 @@
+// Custom opcodes without forking Emu. Extensions are asked, in the order
+// they were added, about every opcode the platform doesn't know, before it
+// is reported as EmuError::InvalidOpcode.
+use crate::Emu;
+use alloc::{sync::Arc, vec::Vec};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Handling {
+    Handled,
+    // Not this extension's; the next one gets a go.
+    Unhandled,
+}
+
+pub trait OpcodeExtension: Send + Sync {
+    // Shown by the disassembler and debuggers.
+    fn name(&self) -> &str;
+    // Whether `op` is one of this extension's opcodes, for the disassembler,
+    // which doesn't run anything.
+    fn claims(&self, op: u16) -> bool;
+    // Runs `op`, with pc already past it. State the extension keeps between
+    // opcodes lives in the machine or behind interior mutability.
+    fn execute(&self, op: u16, emu: &mut Emu) -> Handling;
+}
+
+impl Emu {
+    // Like quirks, extensions are configuration and survive reset().
+    pub fn add_extension(&mut self, extension: Arc<dyn OpcodeExtension>) {
+        self.extensions.push(extension);
+    }
+
+    pub fn extension_names(&self) -> Vec<&str> {
+        self.extensions.iter().map(|ext| ext.name()).collect()
+    }
+
+    pub(crate) fn extension_claiming(&self, op: u16) -> Option<&dyn OpcodeExtension> {
+        let ext = self.extensions.iter().find(|ext| ext.claims(op))?;
+        Some(ext.as_ref())
+    }
+
+    pub(crate) fn run_extensions(&mut self, op: u16) -> Handling {
+        for idx in 0..self.extensions.len() {
+            let ext = Arc::clone(&self.extensions[idx]);
+            if ext.execute(op, self) == Handling::Handled {
+                return Handling::Handled;
+            }
+        }
+        Handling::Unhandled
+    }
+}
@@ -0,0 +1,211 @@
+use crate::Platform;
+
+// Decoded form of a CHIP-8 opcode. Register operands are indices into V0-VF.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Instruction {
+    // 00E0
+    Cls,
+    // 00EE
+    Ret,
+    // 0230, CHIP-8 HIRES: clear the 64x64 screen.
+    HiresCls,
+    // 02A0, CHIP-8X: step to the next background colour.
+    CycleBackground,
+    // 5XY1, CHIP-8X: add VY to VX one octal digit at a time (nibbles & 7).
+    AddColors(u8, u8),
+    // BXY0, CHIP-8X: colour zones of 8x4 pixels. VX and VX+1 hold the first
+    // column and row in their low nibble and the extra columns and rows in
+    // their high nibble.
+    ColorZones(u8, u8),
+    // BXYN, CHIP-8X: colour N rows of the 8 pixel wide column at VX, VX+1.
+    ColorRows(u8, u8, u8),
+    // EXF2, CHIP-8X
+    SkipKey2(u8),
+    // EXF5, CHIP-8X
+    SkipNotKey2(u8),
+    // FXF8, CHIP-8X: write VX to the expansion port.
+    PortOut(u8),
+    // FXFB, CHIP-8X: read the expansion port into VX.
+    PortIn(u8),
+    // 0010, MegaChip8: back to the monochrome screen.
+    MegaOff,
+    // 0011, MegaChip8: switch to the 256x192 colour screen.
+    MegaOn,
+    // 01NN NNNN, MegaChip8: I = NNNNNN, taking the next word as well.
+    LoadLongI(u8),
+    // 02NN, MegaChip8: load NN ARGB palette entries from I, from entry 1 up.
+    LoadPalette(u8),
+    // 03NN and 04NN, MegaChip8: sprite size in pixels, 0 meaning 256.
+    SpriteWidth(u8),
+    SpriteHeight(u8),
+    // 05NN, MegaChip8
+    ScreenAlpha(u8),
+    // 060N, MegaChip8: play the sample at I, looping if N is 0.
+    PlaySample(u8),
+    // 0700, MegaChip8
+    StopSample,
+    // 080N, MegaChip8: sprite blend mode.
+    SetBlend(u8),
+    // 09NN, MegaChip8: palette index whose pixels count as collisions.
+    CollisionColor(u8),
+    // 0NNN, COSMAC VIP: call the 1802 machine code subroutine at NNN.
+    MachineCall(u16),
+    // 1NNN
+    Jump(u16),
+    // 2NNN
+    Call(u16),
+    // 3XNN
+    SkipEqImm(u8, u8),
+    // 4XNN
+    SkipNeImm(u8, u8),
+    // 5XY0
+    SkipEqReg(u8, u8),
+    // 6XNN
+    LoadImm(u8, u8),
+    // 7XNN
+    AddImm(u8, u8),
+    // 8XY0
+    Move(u8, u8),
+    // 8XY1
+    Or(u8, u8),
+    // 8XY2
+    And(u8, u8),
+    // 8XY3
+    Xor(u8, u8),
+    // 8XY4
+    Add(u8, u8),
+    // 8XY5
+    Sub(u8, u8),
+    // 8XY6
+    ShiftRight(u8, u8),
+    // 8XY7
+    SubN(u8, u8),
+    // 8XYE
+    ShiftLeft(u8, u8),
+    // 9XY0
+    SkipNeReg(u8, u8),
+    // ANNN
+    LoadI(u16),
+    // BNNN, X is kept for the jump quirk (BXNN).
+    JumpOffset(u8, u16),
+    // CXNN
+    Random(u8, u8),
+    // DXYN
+    Draw(u8, u8, u8),
+    // EX9E
+    SkipKey(u8),
+    // EXA1
+    SkipNotKey(u8),
+    // FX07
+    GetDelay(u8),
+    // FX0A
+    WaitKey(u8),
+    // FX15
+    SetDelay(u8),
+    // FX18
+    SetSound(u8),
+    // FX1E
+    AddI(u8),
+    // FX29
+    Font(u8),
+    // FX33
+    Bcd(u8),
+    // FX55
+    Store(u8),
+    // FX65
+    Load(u8),
+    // Anything else, with the raw opcode for error reporting.
+    Invalid(u16),
+}
+
+impl Instruction {
+    pub fn decode(op: u16) -> Instruction {
+        let [digit1, digit2, digit3, digit4] = [
+            (op >> 12) as u8,
+            ((op >> 8) & 0xF) as u8,
+            ((op >> 4) & 0xF) as u8,
+            (op & 0xF) as u8,
+        ];
+        let (x, y) = (digit2, digit3);
+        let nn = (op & 0xFF) as u8;
+        let nnn = op & 0xFFF;
+        match (digit1, digit2, digit3, digit4) {
+            (0, 0, 0xE, 0) => Instruction::Cls,
+            (0, 0, 0xE, 0xE) => Instruction::Ret,
+            (1, _, _, _) => Instruction::Jump(nnn),
+            (2, _, _, _) => Instruction::Call(nnn),
+            (3, _, _, _) => Instruction::SkipEqImm(x, nn),
+            (4, _, _, _) => Instruction::SkipNeImm(x, nn),
+            (5, _, _, 0) => Instruction::SkipEqReg(x, y),
+            (6, _, _, _) => Instruction::LoadImm(x, nn),
+            (7, _, _, _) => Instruction::AddImm(x, nn),
+            (8, _, _, 0) => Instruction::Move(x, y),
+            (8, _, _, 1) => Instruction::Or(x, y),
+            (8, _, _, 2) => Instruction::And(x, y),
+            (8, _, _, 3) => Instruction::Xor(x, y),
+            (8, _, _, 4) => Instruction::Add(x, y),
+            (8, _, _, 5) => Instruction::Sub(x, y),
+            (8, _, _, 6) => Instruction::ShiftRight(x, y),
+            (8, _, _, 7) => Instruction::SubN(x, y),
+            (8, _, _, 0xE) => Instruction::ShiftLeft(x, y),
+            (9, _, _, 0) => Instruction::SkipNeReg(x, y),
+            (0xA, _, _, _) => Instruction::LoadI(nnn),
+            (0xB, _, _, _) => Instruction::JumpOffset(x, nnn),
+            (0xC, _, _, _) => Instruction::Random(x, nn),
+            (0xD, _, _, _) => Instruction::Draw(x, y, digit4),
+            (0xE, _, 9, 0xE) => Instruction::SkipKey(x),
+            (0xE, _, 0xA, 1) => Instruction::SkipNotKey(x),
+            (0xF, _, 0, 7) => Instruction::GetDelay(x),
+            (0xF, _, 0, 0xA) => Instruction::WaitKey(x),
+            (0xF, _, 1, 5) => Instruction::SetDelay(x),
+            (0xF, _, 1, 8) => Instruction::SetSound(x),
+            (0xF, _, 1, 0xE) => Instruction::AddI(x),
+            (0xF, _, 2, 9) => Instruction::Font(x),
+            (0xF, _, 3, 3) => Instruction::Bcd(x),
+            (0xF, _, 5, 5) => Instruction::Store(x),
+            (0xF, _, 6, 5) => Instruction::Load(x),
+            (_, _, _, _) => Instruction::Invalid(op),
+        }
+    }
+
+    // Decodes `op` with the extra or changed opcodes of `platform`.
+    pub fn decode_for(platform: Platform, op: u16) -> Instruction {
+        let x = ((op >> 8) & 0xF) as u8;
+        let y = ((op >> 4) & 0xF) as u8;
+        let n = (op & 0xF) as u8;
+        match (platform, op >> 12, op & 0xFF) {
+            (Platform::Chip8Hires, _, _) if op == 0x0230 => Instruction::HiresCls,
+            (Platform::Chip8X, _, _) if op == 0x02A0 => Instruction::CycleBackground,
+            (Platform::Chip8X, 5, _) if n == 1 => Instruction::AddColors(x, y),
+            (Platform::Chip8X, 0xB, _) if n == 0 => Instruction::ColorZones(x, y),
+            (Platform::Chip8X, 0xB, _) => Instruction::ColorRows(x, y, n),
+            (Platform::Chip8X, 0xE, 0xF2) => Instruction::SkipKey2(x),
+            (Platform::Chip8X, 0xE, 0xF5) => Instruction::SkipNotKey2(x),
+            (Platform::Chip8X, 0xF, 0xF8) => Instruction::PortOut(x),
+            (Platform::Chip8X, 0xF, 0xFB) => Instruction::PortIn(x),
+            (Platform::MegaChip8, 0, nn) if cfg!(feature = "megachip") => {
+                let nn = nn as u8;
+                match op >> 8 {
+                    0x00 if nn == 0x10 => Instruction::MegaOff,
+                    0x00 if nn == 0x11 => Instruction::MegaOn,
+                    0x01 => Instruction::LoadLongI(nn),
+                    0x02 => Instruction::LoadPalette(nn),
+                    0x03 => Instruction::SpriteWidth(nn),
+                    0x04 => Instruction::SpriteHeight(nn),
+                    0x05 => Instruction::ScreenAlpha(nn),
+                    0x06 if y == 0 => Instruction::PlaySample(n),
+                    0x07 if nn == 0 => Instruction::StopSample,
+                    0x08 if y == 0 => Instruction::SetBlend(n),
+                    0x09 => Instruction::CollisionColor(nn),
+                    _ => Instruction::decode(op),
+                }
+            }
+            (Platform::OriginalChip8 | Platform::HybridVip, 0, _)
+                if cfg!(feature = "cdp1802") && op != 0x00E0 && op != 0x00EE =>
+            {
+                Instruction::MachineCall(op & 0xFFF)
+            }
+            _ => Instruction::decode(op),
+        }
+    }
+}
@@ -0,0 +1,570 @@
+// Dynamic recompiler: translates CHIP-8 code to native code with Cranelift
+// and runs it in place of the interpreter.
+//
+// A region is everything reachable from its entry pc through register-only
+// instructions (6XNN, 7XNN, 8XYN, ANNN, FX1E), jumps and skips, so loops run
+// without leaving native code, with V0-VF and I kept in host registers.
+// Everything else, like DXYN, CALL/RET, keys, timers and memory access, ends
+// the region and is left to Emu::tick. Compiled code never writes RAM, so
+// regions only go stale when an interpreted instruction or poke writes over
+// their bytes; those are dropped and recompiled on next use.
+use crate::{Emu, EmuError, Instruction, Platform, Quirks, RAM_SIZE};
+use cranelift_codegen::ir::{
+    AbiParam, Block, InstBuilder, MemFlags, Value, condcodes::IntCC, types,
+};
+use cranelift_codegen::settings::{self, Configurable};
+use cranelift_frontend::{FunctionBuilder, FunctionBuilderContext, Variable};
+use cranelift_jit::{JITBuilder, JITModule};
+use cranelift_module::{Module, default_libcall_names};
+use std::collections::HashMap;
+
+// Most instructions translated into one region.
+const MAX_REGION_LEN: usize = 256;
+// Compiled code is only freed with the whole module, so start over when a
+// self-modifying ROM has produced this many functions.
+const MAX_COMPILED: usize = 4096;
+
+// Takes pointers to V0-VF, I and a tick budget, returns the next pc. Each
+// instruction spends one tick; the function returns before running out.
+type RegionFn = unsafe extern "C" fn(*mut u8, *mut u32, *mut u32) -> u32;
+
+#[derive(Debug)]
+pub struct JitError(String);
+
+impl std::fmt::Display for JitError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "JIT unavailable: {}", self.0)
+    }
+}
+
+impl std::error::Error for JitError {}
+
+struct Region {
+    // None when the entry instruction can't be compiled, so we don't retry.
+    code: Option<RegionFn>,
+    // Address and bytes of every instruction translated, to notice when they
+    // have been overwritten.
+    source: Vec<(u16, [u8; 2])>,
+}
+
+impl Region {
+    fn is_current(&self, emu: &Emu) -> bool {
+        self.source
+            .iter()
+            .all(|&(addr, bytes)| [emu.read(addr), emu.read(addr + 1)] == bytes)
+    }
+}
+
+pub struct Jit {
+    module: JITModule,
+    // Indexed by entry pc.
+    regions: Vec<Option<Region>>,
+    // Entry pcs that have a region, so invalidation doesn't scan all of RAM.
+    live: Vec<u16>,
+    compiled: usize,
+    // Emu::ram_version when the regions were last checked against RAM.
+    ram_version: u64,
+    quirks: Quirks,
+    platform: Platform,
+    differential: bool,
+}
+
+impl Jit {
+    pub fn new() -> Result<Jit, JitError> {
+        Ok(Jit {
+            module: new_module()?,
+            regions: (0..RAM_SIZE).map(|_| None).collect(),
+            live: Vec::new(),
+            compiled: 0,
+            ram_version: 0,
+            quirks: Quirks::default(),
+            platform: Platform::ModernChip8,
+            differential: false,
+        })
+    }
+
+    // In differential mode every native run is replayed on a copy of the
+    // machine with Emu::tick, and any difference panics with the details.
+    pub fn set_differential(&mut self, on: bool) {
+        self.differential = on;
+    }
+
+    pub fn compiled_regions(&self) -> usize {
+        self.live
+            .iter()
+            .filter(|&&pc| matches!(&self.regions[pc as usize], Some(r) if r.code.is_some()))
+            .count()
+    }
+
+    // Equivalent to calling emu.tick() `ticks` times, stopping at the first error.
+    pub fn run(&mut self, emu: &mut Emu, ticks: usize) -> Result<(), EmuError> {
+        // Compiled code runs past the hooks, so observers get the interpreter.
+        #[cfg(feature = "observe")]
+        if !emu.observers.is_empty() {
+            for _ in 0..ticks {
+                emu.tick()?;
+            }
+            return Ok(());
+        }
+        let mut done = 0;
+        while done < ticks {
+            self.sync(emu);
+            let pc = emu.pc;
+            if self.regions[pc as usize].is_none() {
+                let region = self.compile(emu, pc);
+                self.regions[pc as usize] = Some(region);
+                self.live.push(pc);
+            }
+            match self.regions[pc as usize].as_ref().and_then(|r| r.code) {
+                Some(code) => {
+                    let shadow = self.differential.then(|| emu.clone());
+                    let budget = (ticks - done).min(u32::MAX as usize) as u32;
+                    let mut left = budget;
+                    // SAFETY: the function was compiled for exactly these
+                    // pointers and only touches V0-VF, I and the budget.
+                    let next = unsafe { code(emu.v_reg.as_mut_ptr(), &mut emu.i_reg, &mut left) };
+                    emu.pc = next as u16;
+                    let spent = (budget - left) as usize;
+                    if let Some(mut shadow) = shadow {
+                        for _ in 0..spent {
+                            shadow.tick()?;
+                        }
+                        check_same(pc, spent, emu, &shadow);
+                    }
+                    done += spent;
+                }
+                None => {
+                    emu.tick()?;
+                    done += 1;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    // Drop regions whose bytes changed, or everything if the quirks or
+    // platform changed.
+    fn sync(&mut self, emu: &Emu) {
+        if emu.quirks != self.quirks
+            || emu.platform != self.platform
+            || self.compiled >= MAX_COMPILED
+        {
+            self.flush();
+            self.quirks = emu.quirks;
+            self.platform = emu.platform;
+        } else if emu.ram_version != self.ram_version {
+            let regions = &mut self.regions;
+            self.live.retain(|&pc| {
+                let slot = &mut regions[pc as usize];
+                let keep = slot.as_ref().is_some_and(|r| r.is_current(emu));
+                if !keep {
+                    *slot = None;
+                }
+                keep
+            });
+        }
+        self.ram_version = emu.ram_version;
+    }
+
+    fn flush(&mut self) {
+        for pc in self.live.drain(..) {
+            self.regions[pc as usize] = None;
+        }
+        self.compiled = 0;
+        if let Ok(module) = new_module() {
+            let old = std::mem::replace(&mut self.module, module);
+            // SAFETY: the region table is empty, nothing points into the old module.
+            unsafe { old.free_memory() };
+        }
+    }
+
+    fn compile(&mut self, emu: &Emu, entry: u16) -> Region {
+        let fetch = |addr: u16| {
+            let bytes = [emu.read(addr), emu.read(addr + 1)];
+            (
+                bytes,
+                Instruction::decode_for(emu.platform, u16::from_be_bytes(bytes)),
+            )
+        };
+        let mut ops = Vec::new();
+        let mut source = Vec::new();
+        let mut seen = vec![false; RAM_SIZE];
+        let mut work = vec![entry];
+        while let Some(addr) = work.pop() {
+            if seen[addr as usize] || ops.len() >= MAX_REGION_LEN {
+                continue;
+            }
+            seen[addr as usize] = true;
+            let (bytes, ins) = fetch(addr);
+            if !compilable(ins) {
+                continue;
+            }
+            ops.push((addr, ins));
+            source.push((addr, bytes));
+            work.extend(successors(addr, ins));
+        }
+        if ops.is_empty() {
+            return Region {
+                code: None,
+                source: vec![(entry, fetch(entry).0)],
+            };
+        }
+        let code = self.translate(&ops, emu.quirks, emu.i_mask());
+        if code.is_some() {
+            self.compiled += 1;
+        }
+        Region { code, source }
+    }
+
+    // `ops[0]` is the entry. Returns None if Cranelift rejects the function;
+    // the interpreter then handles this address.
+    fn translate(
+        &mut self,
+        ops: &[(u16, Instruction)],
+        quirks: Quirks,
+        i_mask: u32,
+    ) -> Option<RegionFn> {
+        let mut ctx = self.module.make_context();
+        let ptr = self.module.target_config().pointer_type();
+        for _ in 0..3 {
+            ctx.func.signature.params.push(AbiParam::new(ptr));
+        }
+        ctx.func.signature.returns.push(AbiParam::new(types::I32));
+
+        let mut fctx = FunctionBuilderContext::new();
+        let b = FunctionBuilder::new(&mut ctx.func, &mut fctx);
+        RegionGen::new(b, ops, quirks, i_mask).emit_all(ops);
+
+        let id = self
+            .module
+            .declare_anonymous_function(&ctx.func.signature)
+            .ok()?;
+        self.module.define_function(id, &mut ctx).ok()?;
+        self.module.clear_context(&mut ctx);
+        self.module.finalize_definitions().ok()?;
+        let code = self.module.get_finalized_function(id);
+        // SAFETY: the signature declared above is (ptr, ptr, ptr) -> i32.
+        Some(unsafe { std::mem::transmute::<*const u8, RegionFn>(code) })
+    }
+}
+
+fn new_module() -> Result<JITModule, JitError> {
+    let err = |e: &dyn std::fmt::Display| JitError(e.to_string());
+    let mut flags = settings::builder();
+    flags.set("opt_level", "speed").map_err(|e| err(&e))?;
+    let isa = cranelift_native::builder()
+        .map_err(|e| err(&e))?
+        .finish(settings::Flags::new(flags))
+        .map_err(|e| err(&e))?;
+    Ok(JITModule::new(JITBuilder::with_isa(
+        isa,
+        default_libcall_names(),
+    )))
+}
+
+fn compilable(ins: Instruction) -> bool {
+    use Instruction::*;
+    matches!(
+        ins,
+        LoadImm(..)
+            | AddImm(..)
+            | Move(..)
+            | Or(..)
+            | And(..)
+            | Xor(..)
+            | Add(..)
+            | Sub(..)
+            | ShiftRight(..)
+            | SubN(..)
+            | ShiftLeft(..)
+            | LoadI(_)
+            | AddI(_)
+            | Jump(_)
+            | SkipEqImm(..)
+            | SkipNeImm(..)
+            | SkipEqReg(..)
+            | SkipNeReg(..)
+    )
+}
+
+// `addr + step`, wrapped like Emu's pc.
+fn after(addr: u16, step: u16) -> u16 {
+    (addr + step) % RAM_SIZE as u16
+}
+
+fn successors(addr: u16, ins: Instruction) -> Vec<u16> {
+    use Instruction::*;
+    match ins {
+        Jump(nnn) => vec![nnn],
+        SkipEqImm(..) | SkipNeImm(..) | SkipEqReg(..) | SkipNeReg(..) => {
+            vec![after(addr, 2), after(addr, 4)]
+        }
+        _ => vec![after(addr, 2)],
+    }
+}
+
+// Panics with the first difference between the native and interpreted run.
+fn check_same(entry: u16, ticks: usize, native: &Emu, interp: &Emu) {
+    let differs = |reg: String| {
+        format!(
+            "JIT region {:03X}, {} ticks: {} differs from the interpreter",
+            entry, ticks, reg
+        )
+    };
+    assert_eq!(native.pc, interp.pc, "{}", differs("pc".into()));
+    assert_eq!(native.i_reg, interp.i_reg, "{}", differs("I".into()));
+    for r in 0..native.v_reg.len() {
+        assert_eq!(
+            native.v_reg[r],
+            interp.v_reg[r],
+            "{}",
+            differs(format!("V{:X}", r))
+        );
+    }
+}
+
+struct RegionGen<'a> {
+    b: FunctionBuilder<'a>,
+    // Native block for each translated instruction address.
+    blocks: HashMap<u16, Block>,
+    // Takes the next pc, writes the registers and budget back and returns.
+    exit: Block,
+    v: [Variable; 16],
+    i: Variable,
+    budget: Variable,
+    quirks: Quirks,
+    // Emu::i_mask for the platform.
+    i_mask: u32,
+}
+
+impl<'a> RegionGen<'a> {
+    fn new(
+        mut b: FunctionBuilder<'a>,
+        ops: &[(u16, Instruction)],
+        quirks: Quirks,
+        i_mask: u32,
+    ) -> Self {
+        let v = std::array::from_fn(|r| Variable::from_u32(r as u32));
+        let i = Variable::from_u32(16);
+        let budget = Variable::from_u32(17);
+        for var in v {
+            b.declare_var(var, types::I8);
+        }
+        b.declare_var(i, types::I32);
+        b.declare_var(budget, types::I32);
+        let blocks = ops
+            .iter()
+            .map(|&(addr, _)| (addr, b.create_block()))
+            .collect();
+        let exit = b.create_block();
+        b.append_block_param(exit, types::I32);
+        RegionGen {
+            b,
+            blocks,
+            exit,
+            v,
+            i,
+            budget,
+            quirks,
+            i_mask,
+        }
+    }
+
+    fn emit_all(mut self, ops: &[(u16, Instruction)]) {
+        let entry = self.b.create_block();
+        self.b.append_block_params_for_function_params(entry);
+        self.b.switch_to_block(entry);
+        let params = self.b.block_params(entry).to_vec();
+        let (v_ptr, i_ptr, budget_ptr) = (params[0], params[1], params[2]);
+        let flags = MemFlags::trusted();
+        for r in 0..16 {
+            let val = self.b.ins().load(types::I8, flags, v_ptr, r as i32);
+            self.b.def_var(self.v[r], val);
+        }
+        let val = self.b.ins().load(types::I32, flags, i_ptr, 0);
+        self.b.def_var(self.i, val);
+        let val = self.b.ins().load(types::I32, flags, budget_ptr, 0);
+        self.b.def_var(self.budget, val);
+        self.goto(ops[0].0);
+
+        for &(addr, ins) in ops {
+            self.emit(addr, ins);
+        }
+
+        self.b.switch_to_block(self.exit);
+        let pc = self.b.block_params(self.exit)[0];
+        for r in 0..16 {
+            let val = self.b.use_var(self.v[r]);
+            self.b.ins().store(flags, val, v_ptr, r as i32);
+        }
+        let val = self.b.use_var(self.i);
+        self.b.ins().store(flags, val, i_ptr, 0);
+        let val = self.b.use_var(self.budget);
+        self.b.ins().store(flags, val, budget_ptr, 0);
+        self.b.ins().return_(&[pc]);
+
+        self.b.seal_all_blocks();
+        self.b.finalize();
+    }
+
+    // Continue at `addr`: its native block, or the exit if it wasn't translated.
+    fn goto(&mut self, addr: u16) {
+        match self.blocks.get(&addr) {
+            Some(&block) => {
+                self.b.ins().jump(block, &[]);
+            }
+            None => self.leave(addr),
+        }
+    }
+
+    fn leave(&mut self, addr: u16) {
+        let pc = self.b.ins().iconst(types::I32, addr as i64);
+        self.b.ins().jump(self.exit, &[pc]);
+    }
+
+    fn branch(&mut self, cond: Value, then_addr: u16, else_addr: u16) {
+        let then_block = self.b.create_block();
+        let else_block = self.b.create_block();
+        self.b.ins().brif(cond, then_block, &[], else_block, &[]);
+        self.b.switch_to_block(then_block);
+        self.goto(then_addr);
+        self.b.switch_to_block(else_block);
+        self.goto(else_addr);
+    }
+
+    fn var(&mut self, x: u8) -> Value {
+        self.b.use_var(self.v[x as usize])
+    }
+
+    fn set(&mut self, x: u8, val: Value) {
+        self.b.def_var(self.v[x as usize], val);
+    }
+
+    fn set_imm(&mut self, x: u8, imm: u8) {
+        let val = self.b.ins().iconst(types::I8, imm as i64);
+        self.set(x, val);
+    }
+
+    // Result goes to VX before the flag goes to VF, as in Emu::execute.
+    fn set_with_flag(&mut self, x: u8, val: Value, flag: Value) {
+        self.set(x, val);
+        self.set(0xF, flag);
+    }
+
+    fn shift_source(&mut self, x: u8, y: u8) -> Value {
+        if self.quirks.shift_vx {
+            self.var(x)
+        } else {
+            self.var(y)
+        }
+    }
+
+    // Spends a tick, or leaves with pc = addr when there are none left.
+    fn emit(&mut self, addr: u16, ins: Instruction) {
+        use Instruction::*;
+        let block = self.blocks[&addr];
+        self.b.switch_to_block(block);
+        let budget = self.b.use_var(self.budget);
+        let body = self.b.create_block();
+        let out_of_ticks = self.b.create_block();
+        self.b.ins().brif(budget, body, &[], out_of_ticks, &[]);
+        self.b.switch_to_block(out_of_ticks);
+        self.leave(addr);
+        self.b.switch_to_block(body);
+        let budget = self.b.ins().iadd_imm(budget, -1);
+        self.b.def_var(self.budget, budget);
+
+        match ins {
+            LoadImm(x, nn) => self.set_imm(x, nn),
+            AddImm(x, nn) => {
+                let vx = self.var(x);
+                let sum = self.b.ins().iadd_imm(vx, nn as i64);
+                self.set(x, sum);
+            }
+            Move(x, y) => {
+                let vy = self.var(y);
+                self.set(x, vy);
+            }
+            Or(x, y) | And(x, y) | Xor(x, y) => {
+                let (vx, vy) = (self.var(x), self.var(y));
+                let res = match ins {
+                    Or(..) => self.b.ins().bor(vx, vy),
+                    And(..) => self.b.ins().band(vx, vy),
+                    _ => self.b.ins().bxor(vx, vy),
+                };
+                self.set(x, res);
+                if self.quirks.vf_reset {
+                    self.set_imm(0xF, 0);
+                }
+            }
+            Add(x, y) => {
+                let (vx, vy) = (self.var(x), self.var(y));
+                let wide_x = self.b.ins().uextend(types::I16, vx);
+                let wide_y = self.b.ins().uextend(types::I16, vy);
+                let wide = self.b.ins().iadd(wide_x, wide_y);
+                let sum = self.b.ins().ireduce(types::I8, wide);
+                let carry = self.b.ins().ushr_imm(wide, 8);
+                let carry = self.b.ins().ireduce(types::I8, carry);
+                self.set_with_flag(x, sum, carry);
+            }
+            Sub(x, y) | SubN(x, y) => {
+                let (vx, vy) = (self.var(x), self.var(y));
+                let (a, b) = if let Sub(..) = ins {
+                    (vx, vy)
+                } else {
+                    (vy, vx)
+                };
+                let diff = self.b.ins().isub(a, b);
+                let no_borrow = self.b.ins().icmp(IntCC::UnsignedGreaterThanOrEqual, a, b);
+                self.set_with_flag(x, diff, no_borrow);
+            }
+            ShiftRight(x, y) => {
+                let src = self.shift_source(x, y);
+                let res = self.b.ins().ushr_imm(src, 1);
+                let lsb = self.b.ins().band_imm(src, 1);
+                self.set_with_flag(x, res, lsb);
+            }
+            ShiftLeft(x, y) => {
+                let src = self.shift_source(x, y);
+                let res = self.b.ins().ishl_imm(src, 1);
+                let msb = self.b.ins().ushr_imm(src, 7);
+                self.set_with_flag(x, res, msb);
+            }
+            LoadI(nnn) => {
+                let val = self.b.ins().iconst(types::I32, nnn as i64);
+                self.b.def_var(self.i, val);
+            }
+            AddI(x) => {
+                let vx = self.var(x);
+                let vx = self.b.ins().uextend(types::I32, vx);
+                let i = self.b.use_var(self.i);
+                let sum = self.b.ins().iadd(i, vx);
+                let sum = self.b.ins().band_imm(sum, self.i_mask as i64);
+                self.b.def_var(self.i, sum);
+            }
+            Jump(nnn) => return self.goto(nnn),
+            SkipEqImm(x, nn) | SkipNeImm(x, nn) => {
+                let vx = self.var(x);
+                let cc = if let SkipEqImm(..) = ins {
+                    IntCC::Equal
+                } else {
+                    IntCC::NotEqual
+                };
+                let cond = self.b.ins().icmp_imm(cc, vx, nn as i64);
+                return self.branch(cond, after(addr, 4), after(addr, 2));
+            }
+            SkipEqReg(x, y) | SkipNeReg(x, y) => {
+                let (vx, vy) = (self.var(x), self.var(y));
+                let cc = if let SkipEqReg(..) = ins {
+                    IntCC::Equal
+                } else {
+                    IntCC::NotEqual
+                };
+                let cond = self.b.ins().icmp(cc, vx, vy);
+                return self.branch(cond, after(addr, 4), after(addr, 2));
+            }
+            _ => unreachable!("{:?} is not compilable", ins),
+        }
+        self.goto(after(addr, 2));
+    }
+}
@@ -1,4 +1,66 @@
 // Chip-8
+//
+// no_std unless the `std` feature is on. CXNN's random bytes come from the
+// closure passed to Emu::new, a plain function without `alloc`; with
+// `os-rng`, Emu::default uses getrandom.
+#![cfg_attr(not(feature = "std"), no_std)]
+
+#[cfg(feature = "alloc")]
+extern crate alloc;
+
+#[cfg(feature = "octo")]
+mod cartridge;
+mod chip8x;
+mod classify;
+mod disasm;
+#[cfg(feature = "alloc")]
+mod extension;
+mod instruction;
+#[cfg(all(feature = "jit", target_arch = "x86_64", target_os = "linux"))]
+mod jit;
+mod machine;
+#[cfg(feature = "megachip")]
+mod megachip;
+#[cfg(feature = "observe")]
+mod observer;
+#[cfg(feature = "octo")]
+mod octo;
+#[cfg(feature = "pack")]
+mod pack;
+#[cfg(feature = "alloc")]
+mod patch;
+mod platform;
+mod quirks;
+#[cfg(feature = "romdb")]
+mod romdb;
+#[cfg(feature = "cdp1802")]
+mod vip;
+
+#[cfg(feature = "octo")]
+pub use cartridge::{Cartridge, CartridgeError};
+pub use chip8x::{ColorLayer, VP590_BACKGROUND, VP590_FOREGROUND};
+pub use classify::Guess;
+#[cfg(feature = "alloc")]
+pub use extension::{Handling, OpcodeExtension};
+pub use instruction::Instruction;
+#[cfg(all(feature = "jit", target_arch = "x86_64", target_os = "linux"))]
+pub use jit::{Jit, JitError};
+pub use machine::{Frame, Machine, MachineState};
+#[cfg(feature = "megachip")]
+pub use megachip::{BlendMode, Framebuffer, MEGA_RAM_SIZE, MEGA_SCREEN_H, MEGA_SCREEN_W, Sample};
+#[cfg(feature = "observe")]
+pub use observer::{Observer, Register};
+#[cfg(feature = "octo")]
+pub use octo::{OctoError, assemble};
+#[cfg(feature = "pack")]
+pub use pack::{PackError, PackedRom, RomPack};
+#[cfg(feature = "alloc")]
+pub use patch::{PatchError, PatchFormat, apply_patch};
+pub use platform::Platform;
+pub use quirks::Quirks;
+#[cfg(feature = "romdb")]
+pub use romdb::{RomDb, RomDbError, RomInfo, sha1_hex};
+
 const FONTSET_SIZE: usize = 80;
 const FONTSET: [u8; FONTSET_SIZE] = [
     0xF0, 0x90, 0x90, 0x90, 0xF0, // 0
@@ -20,180 +82,451 @@ const FONTSET: [u8; FONTSET_SIZE] = [
 ];
 
 // system setup
+// Everything below START_ADDR belongs to the interpreter.
 const START_ADDR: u16 = 0x200;
-const RAM_SIZE: usize = 4096;
-const NUM_REGISTERS: usize = 16;
-const STACK_SIZE: usize = 16;
-const NUM_KEYS: usize = 16;
+pub const RAM_SIZE: usize = 4096;
+pub const NUM_REGISTERS: usize = 16;
+pub const STACK_SIZE: usize = 16;
+pub const NUM_KEYS: usize = 16;
+// Largest ROM that fits between START_ADDR and the end of RAM.
+pub const MAX_ROM_SIZE: usize = RAM_SIZE - START_ADDR as usize;
 
 // display setup
+// Size of the standard screen; see Platform::screen_size for the others.
 pub const SCREEN_W: usize = 64;
 pub const SCREEN_H: usize = 32;
+// Room for the largest screen of any platform.
+const SCREEN_PIXELS: usize = 64 * 64;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EmuError {
+    // The opcode at `pc` is not part of the instruction set.
+    InvalidOpcode { op: u16, pc: u16 },
+    // CALL with all STACK_SIZE slots in use.
+    StackOverflow { pc: u16 },
+    // RET with an empty stack.
+    StackUnderflow { pc: u16 },
+    // ROM does not fit between its load address and the end of memory.
+    RomTooLarge { size: usize, max: usize },
+    // Load address past the end of the platform's memory.
+    BadLoadAddress { addr: u16 },
+    // Entry point outside the program area. pc only reaches the first 4K,
+    // even on MegaChip8.
+    BadEntryPoint { entry: u16 },
+    // Entry point at or past the end of the ROM, so none of it would run.
+    EntryPastRom { entry: u16 },
+    // The machine code subroutine at `addr`, called by 0NNN at `pc`, did not
+    // return within the step limit.
+    MachineCodeTimeout { addr: u16, pc: u16 },
+}
+
+impl core::fmt::Display for EmuError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            EmuError::InvalidOpcode { op, pc } => {
+                write!(f, "invalid opcode {:04X} at {:03X}", op, pc)
+            }
+            EmuError::StackOverflow { pc } => write!(f, "stack overflow at {:03X}", pc),
+            EmuError::StackUnderflow { pc } => write!(f, "stack underflow at {:03X}", pc),
+            EmuError::RomTooLarge { size, max } => {
+                write!(
+                    f,
+                    "ROM is {} bytes, at most {} bytes fit in memory",
+                    size, max
+                )
+            }
+            EmuError::BadLoadAddress { addr } => {
+                write!(f, "load address {:03X} is past the end of memory", addr)
+            }
+            EmuError::BadEntryPoint { entry } => {
+                write!(f, "entry point {:03X} is outside program memory", entry)
+            }
+            EmuError::EntryPastRom { entry } => {
+                write!(f, "entry point {:03X} is past the end of the ROM", entry)
+            }
+            EmuError::MachineCodeTimeout { addr, pc } => write!(
+                f,
+                "machine code at {:03X}, called from {:03X}, did not return",
+                addr, pc
+            ),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for EmuError {}
+
+#[derive(Clone)]
 pub struct Emu {
     // program counter
     pc: u16,
     ram: [u8; RAM_SIZE],
-    screen: [bool; SCREEN_H * SCREEN_W],
+    // Row-major, width and height from the platform; pixels past them stay
+    // off.
+    screen: [bool; SCREEN_PIXELS],
 
     v_reg: [u8; NUM_REGISTERS],
-    i_reg: u16,
+    // 16 bits wide, 24 on MegaChip8.
+    i_reg: u32,
 
     // stack pointer
     sp: u16,
     stack: [u16; STACK_SIZE],
     keys: [bool; NUM_KEYS],
+    // CHIP-8X's second keypad.
+    keys2: [bool; NUM_KEYS],
+    // CHIP-8X's colours.
+    colors: ColorLayer,
+    // Last byte written to, and next byte read from, the CHIP-8X expansion
+    // port.
+    port_out: u8,
+    port_in: u8,
 
     // Delay Timer
     dt: u8,
     // Sound Timer
     st: u8,
+    // tick_timers() calls, for platforms whose timers run slower than 60 Hz.
+    timer_calls: u8,
+
+    quirks: Quirks,
+    platform: Platform,
+    // MegaChip8's extra state, present while that is the platform.
+    #[cfg(feature = "megachip")]
+    mega: Option<alloc::boxed::Box<megachip::Mega>>,
+    // Source of CXNN's random bytes.
+    random: Random,
+    #[cfg(feature = "alloc")]
+    extensions: alloc::vec::Vec<alloc::sync::Arc<dyn OpcodeExtension>>,
+    #[cfg(feature = "observe")]
+    observers: alloc::vec::Vec<alloc::sync::Arc<dyn Observer>>,
+    // Instruction decoded at each address, filled in on first fetch and
+    // cleared whenever one of its two bytes is written.
+    decoded: [Option<Instruction>; RAM_SIZE],
+    // Changed on every RAM write, so compiled blocks can tell they may be
+    // stale. Versions come from RAM_VERSIONS, so no two machines share one.
+    #[cfg(feature = "jit")]
+    ram_version: u64,
 }
 
+// Where CXNN's random bytes come from. Boxed so it can carry a seed or other
+// state, which is cloned along with the Emu.
+#[cfg(feature = "alloc")]
+type Random = alloc::boxed::Box<dyn RandomSource>;
+#[cfg(not(feature = "alloc"))]
+type Random = fn() -> u8;
+
+#[cfg(feature = "alloc")]
+trait RandomSource: Send + Sync {
+    fn byte(&mut self) -> u8;
+    fn boxed(&self) -> Random;
+}
+
+#[cfg(feature = "alloc")]
+impl<F: FnMut() -> u8 + Clone + Send + Sync + 'static> RandomSource for F {
+    fn byte(&mut self) -> u8 {
+        self()
+    }
+
+    fn boxed(&self) -> Random {
+        alloc::boxed::Box::new(self.clone())
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl Clone for Random {
+    fn clone(&self) -> Self {
+        self.boxed()
+    }
+}
+
+// The next RAM version for any Emu. A Jit moved to another machine then sees
+// a version it hasn't checked its regions against.
+#[cfg(feature = "jit")]
+static RAM_VERSIONS: core::sync::atomic::AtomicU64 = core::sync::atomic::AtomicU64::new(1);
+
+#[cfg(feature = "os-rng")]
 impl Default for Emu {
     fn default() -> Self {
+        Self::new(os_random)
+    }
+}
+
+impl Emu {
+    // `random` supplies CXNN's random bytes, e.g. from a hardware RNG or a
+    // seeded generator for reproducible runs.
+    #[cfg(feature = "alloc")]
+    pub fn new(random: impl FnMut() -> u8 + Clone + Send + Sync + 'static) -> Self {
+        Self::with_random(alloc::boxed::Box::new(random))
+    }
+
+    #[cfg(not(feature = "alloc"))]
+    pub fn new(random: fn() -> u8) -> Self {
+        Self::with_random(random)
+    }
+
+    fn with_random(random: Random) -> Self {
         let mut ram = [0; RAM_SIZE];
         ram[..FONTSET_SIZE].copy_from_slice(&FONTSET);
         Self {
             pc: START_ADDR,
             ram, // Rust allows omitting field names only when they are the same
-            screen: [false; SCREEN_H * SCREEN_W],
+            screen: [false; SCREEN_PIXELS],
             v_reg: [0; NUM_REGISTERS],
             i_reg: 0,
             sp: 0,
             stack: [0; STACK_SIZE],
             keys: [false; NUM_KEYS],
+            keys2: [false; NUM_KEYS],
+            colors: ColorLayer::default(),
+            port_out: 0,
+            port_in: 0,
             dt: 0,
             st: 0,
+            timer_calls: 0,
+            quirks: Quirks::default(),
+            platform: Platform::ModernChip8,
+            #[cfg(feature = "megachip")]
+            mega: None,
+            random,
+            #[cfg(feature = "alloc")]
+            extensions: alloc::vec::Vec::new(),
+            #[cfg(feature = "observe")]
+            observers: alloc::vec::Vec::new(),
+            decoded: [None; RAM_SIZE],
+            #[cfg(feature = "jit")]
+            ram_version: next_ram_version(),
         }
     }
-}
 
-impl Emu {
-    fn push(&mut self, val: u16) {
+    fn push(&mut self, val: u16) -> Result<(), EmuError> {
+        if self.sp as usize >= STACK_SIZE {
+            return Err(EmuError::StackOverflow { pc: self.op_addr() });
+        }
         self.stack[self.sp as usize] = val;
         self.sp += 1;
+        Ok(())
     }
 
-    fn pop(&mut self) -> u16 {
+    fn pop(&mut self) -> Result<u16, EmuError> {
+        if self.sp == 0 {
+            return Err(EmuError::StackUnderflow { pc: self.op_addr() });
+        }
         self.sp -= 1;
-        self.stack[self.sp as usize]
+        Ok(self.stack[self.sp as usize])
+    }
+
+    // Addresses wrap around RAM instead of running off the end of it.
+    fn read(&self, addr: u16) -> u8 {
+        self.ram[addr as usize % RAM_SIZE]
+    }
+
+    fn write(&mut self, addr: u16, val: u8) {
+        let addr = addr as usize % RAM_SIZE;
+        self.ram[addr] = val;
+        // The byte belongs to the instructions starting at addr and addr - 1.
+        self.decoded[addr] = None;
+        self.decoded[(addr + RAM_SIZE - 1) % RAM_SIZE] = None;
+        #[cfg(feature = "jit")]
+        {
+            self.ram_version = next_ram_version();
+        }
+        #[cfg(feature = "observe")]
+        self.notify(|o| o.write(addr as u32, val));
+    }
+
+    // Data access through I, which can reach past `ram` on MegaChip8.
+    fn read_mem(&self, addr: u32) -> u8 {
+        #[cfg(feature = "megachip")]
+        let val = match &self.mega {
+            Some(mega) => mega.read(&self.ram, addr),
+            None => self.read(addr as u16),
+        };
+        #[cfg(not(feature = "megachip"))]
+        let val = self.read(addr as u16);
+        #[cfg(feature = "observe")]
+        self.notify(|o| o.read(addr, val));
+        val
+    }
+
+    fn write_mem(&mut self, addr: u32, val: u8) {
+        #[cfg(feature = "megachip")]
+        if let Some(mega) = &mut self.mega
+            && addr as usize % MEGA_RAM_SIZE >= RAM_SIZE
+        {
+            mega.write_high(addr, val);
+            #[cfg(feature = "observe")]
+            self.notify(|o| o.write(addr, val));
+            return;
+        }
+        self.write(addr as u16, val);
+    }
+
+    // I's width as a mask.
+    fn i_mask(&self) -> u32 {
+        if self.platform == Platform::MegaChip8 {
+            0xFF_FFFF
+        } else {
+            0xFFFF
+        }
+    }
+
+    // I + offset, wrapped to the width of I.
+    fn i_plus(&self, offset: u32) -> u32 {
+        self.i_reg.wrapping_add(offset) & self.i_mask()
+    }
+
+    fn set_pc_wrapped(&mut self, addr: u16) {
+        self.pc = addr % RAM_SIZE as u16;
+    }
+
+    // Address of the instruction being executed; fetch has already moved pc past it.
+    fn op_addr(&self) -> u16 {
+        self.pc.wrapping_sub(2) % RAM_SIZE as u16
     }
 
     pub fn reset(&mut self) {
         let mut ram = [0; RAM_SIZE];
-        ram[..FONTSET_SIZE].copy_from_slice(&FONTSET);
+        ram[..FONTSET_SIZE].copy_from_slice(self.platform.font());
 
-        self.pc = START_ADDR;
+        self.pc = self.platform.entry();
         self.ram = ram;
-        self.screen = [false; SCREEN_W * SCREEN_H];
+        self.screen = [false; SCREEN_PIXELS];
         self.v_reg = [0; NUM_REGISTERS];
         self.i_reg = 0;
         self.sp = 0;
         self.stack = [0; STACK_SIZE];
         self.keys = [false; NUM_KEYS];
+        self.keys2 = [false; NUM_KEYS];
+        self.colors = ColorLayer::default();
+        self.port_out = 0;
+        self.port_in = 0;
         self.dt = 0;
         self.st = 0;
-        self.ram[..FONTSET_SIZE].copy_from_slice(&FONTSET);
+        self.timer_calls = 0;
+        self.decoded = [None; RAM_SIZE];
+        #[cfg(feature = "megachip")]
+        if let Some(mega) = &mut self.mega {
+            **mega = megachip::Mega::new();
+        }
+        #[cfg(feature = "jit")]
+        {
+            self.ram_version = next_ram_version();
+        }
     }
-    pub fn tick(&mut self) {
-        let op = self.fetch();
+    // tick, fetch and execute are inlined so a caller's tick loop compiles
+    // into one loop over the decoded instruction cache.
+    #[inline]
+    pub fn tick(&mut self) -> Result<(), EmuError> {
+        let pc = self.pc;
+        let ins = self.fetch();
+        #[cfg(feature = "observe")]
+        let before = self.observe_fetch(pc, ins);
 
         // Decode and Execute can happen simultaneously in the Chip-8 systems.
-        self.execute(op);
-    }
-    fn execute(&mut self, op: u16) {
-        let [digit1, digit2, digit3, digit4] = [
-            (op >> 12) as u8,
-            ((op >> 8) & 0xF) as u8,
-            ((op >> 4) & 0xF) as u8,
-            (op & 0xF) as u8,
-        ];
-        match (digit1, digit2, digit3, digit4) {
+        let res = self.execute(ins);
+        if res.is_err() {
+            // Leave pc on the faulting instruction.
+            self.pc = pc;
+        }
+        #[cfg(feature = "observe")]
+        if let Some(before) = before {
+            self.observe_execute(ins, before);
+        }
+        res
+    }
+    #[inline]
+    fn execute(&mut self, ins: Instruction) -> Result<(), EmuError> {
+        match ins {
             // CLS
-            (0, 0, 0xE, 0) => {
-                self.screen = [false; SCREEN_H * SCREEN_W];
+            Instruction::Cls => {
+                self.screen = [false; SCREEN_PIXELS];
+                #[cfg(feature = "megachip")]
+                if let Some(mega) = self.mega.as_deref_mut().filter(|m| m.on) {
+                    mega.present();
+                }
+            }
+            Instruction::HiresCls => {
+                self.screen = [false; SCREEN_PIXELS];
             }
             // RET
-            (0, 0, 0xE, 0xE) => {
-                let re_addr = self.pop();
-                self.pc = re_addr;
+            Instruction::Ret => {
+                let re_addr = self.pop()?;
+                self.set_pc_wrapped(re_addr);
             }
             // JMP NNN
-            (1, _, _, _) => {
-                let nnn = op & 0xFFF;
+            Instruction::Jump(nnn) => {
                 self.pc = nnn;
             }
             // CALL NNN
-            (2, _, _, _) => {
-                let nnn = op & 0xFFF;
-                self.push(self.pc);
+            Instruction::Call(nnn) => {
+                self.push(self.pc)?;
                 self.pc = nnn;
             }
             // SKIP VX == NN
-            (3, _, _, _) => {
-                let x = digit2 as usize;
-                let nn = (op & 0xFF) as u8;
+            Instruction::SkipEqImm(x, nn) => {
+                let x = x as usize;
                 if self.v_reg[x] == nn {
-                    self.pc += 2;
+                    self.skip();
                     // Skip next if v[x] == nn
                 }
             }
             // SKIP VX != NN
-            (4, _, _, _) => {
-                let x = digit2 as usize;
-                let nn = (op & 0xFF) as u8;
+            Instruction::SkipNeImm(x, nn) => {
+                let x = x as usize;
                 if self.v_reg[x] != nn {
-                    self.pc += 2;
+                    self.skip();
                 }
             }
             //  SKIP VX == VY COMMAND: 5XY0
-            (5, _, _, _) => {
-                let x = digit2 as usize;
-                let y = digit3 as usize;
+            Instruction::SkipEqReg(x, y) => {
+                let x = x as usize;
+                let y = y as usize;
                 if self.v_reg[x] == self.v_reg[y] {
-                    self.pc += 2;
+                    self.skip();
                 }
             }
             // VX == NN  COMMAND: 6XNN
-            (6, _, _, _) => {
-                let x = digit2 as usize;
-                let nn = (op & 0xFF) as u8;
+            Instruction::LoadImm(x, nn) => {
+                let x = x as usize;
                 self.v_reg[x] = nn;
             }
             // VX += NN
-            (7, _, _, _) => {
-                let x = digit2 as usize;
-                let nn = (op & 0xFF) as u8;
+            Instruction::AddImm(x, nn) => {
+                let x = x as usize;
                 // Wrapping prevents stack overflow from happening.
                 self.v_reg[x] = self.v_reg[x].wrapping_add(nn);
             }
             // VX == VY
-            (8, _, _, 0) => {
-                let x = digit2 as usize;
-                let y = digit3 as usize;
+            Instruction::Move(x, y) => {
+                let x = x as usize;
+                let y = y as usize;
                 self.v_reg[x] = self.v_reg[y];
             }
             // VX |= VY 8XY1 OR,8XY2 AND,8XY3 XOR
-            (8, _, _, 1) => {
-                let x = digit2 as usize;
-                let y = digit3 as usize;
+            Instruction::Or(x, y) => {
+                let x = x as usize;
+                let y = y as usize;
                 self.v_reg[x] |= self.v_reg[y];
+                self.logic_vf_reset();
             }
 
-            (8, _, _, 2) => {
-                let x = digit2 as usize;
-                let y = digit3 as usize;
+            Instruction::And(x, y) => {
+                let x = x as usize;
+                let y = y as usize;
                 self.v_reg[x] &= self.v_reg[y];
+                self.logic_vf_reset();
             }
-            (8, _, _, 3) => {
-                let x = digit2 as usize;
-                let y = digit3 as usize;
+            Instruction::Xor(x, y) => {
+                let x = x as usize;
+                let y = y as usize;
                 self.v_reg[x] ^= self.v_reg[y];
+                self.logic_vf_reset();
             }
             // VX += VY
-            (8, _, _, 4) => {
-                let x = digit2 as usize;
-                let y = digit3 as usize;
+            Instruction::Add(x, y) => {
+                let x = x as usize;
+                let y = y as usize;
                 // In addition,overflow is treated as carry 1,otherwise 0.
                 let (new_vx, carry) = self.v_reg[x].overflowing_add(self.v_reg[y]);
                 let new_vf = if carry { 1 } else { 0 };
@@ -202,9 +535,9 @@ impl Emu {
                 self.v_reg[0xF] = new_vf;
             }
             // VX -= VY
-            (8, _, _, 5) => {
-                let x = digit2 as usize;
-                let y = digit3 as usize;
+            Instruction::Sub(x, y) => {
+                let x = x as usize;
+                let y = y as usize;
                 // In subtraction, underflow is 0, otherwise 1.
                 let (new_vx, borrow) = self.v_reg[x].overflowing_sub(self.v_reg[y]);
                 let new_vf = if borrow { 0 } else { 1 };
@@ -214,17 +547,18 @@ impl Emu {
             }
             // VX >= 1
             // A single right shift on the value in VX, and stores the dropped-off bit into the VF register.
-            (8, _, _, 6) => {
-                let x = digit2 as usize;
+            Instruction::ShiftRight(x, y) => {
+                let x = x as usize;
+                self.shift_source(x, y as usize);
                 // Least Significant Bit
                 let lsb = self.v_reg[x] & 1;
                 self.v_reg[x] >>= 1;
                 self.v_reg[0xF] = lsb;
             }
             // VX = VY - VX
-            (8, _, _, 7) => {
-                let x = digit2 as usize;
-                let y = digit3 as usize;
+            Instruction::SubN(x, y) => {
+                let x = x as usize;
+                let y = y as usize;
 
                 let (new_vx, borrow) = self.v_reg[y].overflowing_sub(self.v_reg[x]);
                 let new_vf = if borrow { 0 } else { 1 };
@@ -233,64 +567,87 @@ impl Emu {
                 self.v_reg[0xF] = new_vf;
             }
             // VX <<= 1
-            (8, _, _, 0xE) => {
-                let x = digit2 as usize;
+            Instruction::ShiftLeft(x, y) => {
+                let x = x as usize;
+                self.shift_source(x, y as usize);
                 let msb = (self.v_reg[x] >> 7) & 1;
                 // Most Significant Bit
                 self.v_reg[x] <<= 1;
                 self.v_reg[0xF] = msb;
             }
             // SKIP VX != VY
-            (9, _, _, 0) => {
-                let x = digit2 as usize;
-                let y = digit3 as usize;
+            Instruction::SkipNeReg(x, y) => {
+                let x = x as usize;
+                let y = y as usize;
                 if self.v_reg[x] != self.v_reg[y] {
-                    self.pc += 2;
+                    self.skip();
                 }
             }
             // ANNN I = NNN
-            (0xA, _, _, _) => {
-                let nnn = op & 0xFFF;
-                self.i_reg = nnn;
+            Instruction::LoadI(nnn) => {
+                self.i_reg = nnn as u32;
             }
             // BNNN JMP V0 + NNN
-            (0xB, _, _, _) => {
-                let nnn = op & 0xFFF;
-                self.pc = (self.v_reg[0] as u16) + nnn;
+            Instruction::JumpOffset(x, nnn) => {
+                let offset = if self.quirks.jump_vx {
+                    self.v_reg[x as usize]
+                } else {
+                    self.v_reg[0]
+                };
+                self.set_pc_wrapped((offset as u16) + nnn);
             }
             //CXNN VX = rand() & NN
-            (0xC, _, _, _) => {
-                let x = digit2 as usize;
-                let nn = (op & 0xFF) as u8;
-                let rng: u8 = get_random_u8().unwrap();
+            Instruction::Random(x, nn) => {
+                let x = x as usize;
+                #[cfg(feature = "alloc")]
+                let rng: u8 = self.random.byte();
+                #[cfg(not(feature = "alloc"))]
+                let rng: u8 = (self.random)();
                 self.v_reg[x] = rng & nn;
             }
             // DRAW
-            (0xD, _, _, _) => {
+            Instruction::Draw(x, y, n) => {
+                #[cfg(feature = "megachip")]
+                if self.mega.as_ref().is_some_and(|m| m.on) {
+                    self.draw_mega(x, y);
+                    return Ok(());
+                }
                 // Get the (x,y) coords for our sprite.
-                let x_coord = self.v_reg[digit2 as usize] as u16;
-                let y_coord = self.v_reg[digit3 as usize] as u16;
+                let mut x_coord = self.v_reg[x as usize] as u16;
+                let mut y_coord = self.v_reg[y as usize] as u16;
+                let (width, height) = self.platform.screen_size();
+                if self.quirks.clip_sprites {
+                    // Only the start position wraps, the sprite itself is cut at the edges.
+                    x_coord %= width as u16;
+                    y_coord %= height as u16;
+                }
                 // The last digit determines how many rows high our sprite is
-                let num_row = digit4 as u16;
+                let num_row = n as u16;
                 // Keep track if any pixels were flipped
                 let mut flipped = false;
 
                 for y_line in 0..num_row {
                     // Determine which memory address out row's data is stored
-                    let addr = self.i_reg + y_line;
+                    let addr = self.i_plus(y_line as u32);
                     // This is the data for each Y'line.
-                    let pixels = self.ram[addr as usize];
+                    let pixels = self.read_mem(addr);
 
                     // Number 8 is sprite's width
                     // This line of code uses a moving mask to determine the state of each bit.
                     for x_line in 0..8 {
                         // Use mask to fetch current pixel's bit. Only flip is a 1.
                         if (pixels & (0b1000_0000 >> x_line)) != 0 {
+                            if self.quirks.clip_sprites
+                                && (x_coord + x_line >= width as u16
+                                    || y_coord + y_line >= height as u16)
+                            {
+                                continue;
+                            }
                             // Sprite should wrap around screen ,so apply modulo.
-                            let x = (x_coord + x_line) as usize % SCREEN_W;
-                            let y = (y_coord + y_line) as usize % SCREEN_H;
+                            let x = (x_coord + x_line) as usize % width;
+                            let y = (y_coord + y_line) as usize % height;
 
-                            let idx = x + SCREEN_W * y;
+                            let idx = x + width * y;
                             flipped |= self.screen[idx];
                             self.screen[idx] ^= true;
                         }
@@ -305,32 +662,34 @@ impl Emu {
                 }
             }
             // SKIP KEY PRESS
-            (0xE, _, 9, 0xE) => {
-                let x = digit2 as usize;
-                let vx = self.v_reg[x] as usize;
+            Instruction::SkipKey(x) => {
+                let x = x as usize;
+                // Only the low nibble selects a key.
+                let vx = (self.v_reg[x] & 0xF) as usize;
                 let key = self.keys[vx];
                 if key {
-                    self.pc += 2;
+                    self.skip();
                 }
             }
             // SKIP KEY RELEASE
-            (0xE, _, 0xA, 1) => {
-                let x = digit2 as usize;
-                let vx = self.v_reg[x] as usize;
+            Instruction::SkipNotKey(x) => {
+                let x = x as usize;
+                // Only the low nibble selects a key.
+                let vx = (self.v_reg[x] & 0xF) as usize;
                 let key = self.keys[vx];
 
                 if !key {
-                    self.pc += 2;
+                    self.skip();
                 }
             }
             // FX07 VX = DT
-            (0xF, _, 0, 7) => {
-                let x = digit2 as usize;
+            Instruction::GetDelay(x) => {
+                let x = x as usize;
                 self.v_reg[x] = self.dt;
             }
             // FX0A WAIT KEY
-            (0xF, _, 0, 0xA) => {
-                let x = digit2 as usize;
+            Instruction::WaitKey(x) => {
+                let x = x as usize;
                 let mut pressed = false;
                 for i in 0..self.keys.len() {
                     if self.keys[i] {
@@ -341,65 +700,161 @@ impl Emu {
                 }
                 if !pressed {
                     // Redo opcode
-                    self.pc -= 2;
+                    self.set_pc_wrapped(self.pc.wrapping_sub(2));
                 }
             }
             // FX15 DT = VX
-            (0xF, _, 1, 5) => {
-                let x = digit2 as usize;
+            Instruction::SetDelay(x) => {
+                let x = x as usize;
                 self.dt = self.v_reg[x];
             }
             // FX18 ST = VX
-            (0xF, _, 1, 8) => {
-                let x = digit2 as usize;
+            Instruction::SetSound(x) => {
+                let x = x as usize;
                 self.st = self.v_reg[x];
             }
             // FX1E I += VX
-            (0xF, _, 1, 0xE) => {
-                let x = digit2 as usize;
-                let vx = self.v_reg[x] as u16;
-                self.i_reg = self.i_reg.wrapping_add(vx);
+            Instruction::AddI(x) => {
+                let x = x as usize;
+                self.i_reg = self.i_plus(self.v_reg[x] as u32);
             }
             // FX29 I = FONT
-            (0xF, _, 2, 9) => {
-                let x = digit2 as usize;
-                let c = self.v_reg[x] as u16;
+            Instruction::Font(x) => {
+                let x = x as usize;
+                let c = (self.v_reg[x] & 0xF) as u32;
                 self.i_reg = c * 5;
             }
             // BCD Binary-Coded Decimal https://en.wikipedia.org/wiki/Binary-coded_decimal
-            (0xF, _, 3, 3) => {
-                let x = digit2 as usize;
-                let vx = self.v_reg[x] as f32;
+            Instruction::Bcd(x) => {
+                let x = x as usize;
+                let vx = self.v_reg[x];
 
-                let hundreds = (vx / 100.0).floor() as u8;
-                let tens = ((vx / 10.0) % 10.0).floor() as u8;
-                let ones = (vx % 10.0) as u8;
+                let hundreds = vx / 100;
+                let tens = (vx / 10) % 10;
+                let ones = vx % 10;
 
-                self.ram[self.i_reg as usize] = hundreds;
-                self.ram[(self.i_reg + 1) as usize] = tens;
-                self.ram[(self.i_reg + 2) as usize] = ones;
+                self.write_mem(self.i_reg, hundreds);
+                self.write_mem(self.i_plus(1), tens);
+                self.write_mem(self.i_plus(2), ones);
             }
             // FX55 STORE V0 - VX
-            (0xF, _, 5, 5) => {
-                let x = digit2 as usize;
-                let i = self.i_reg as usize;
+            Instruction::Store(x) => {
+                let x = x as usize;
                 for idx in 0..=x {
-                    self.ram[i + idx] = self.v_reg[idx];
+                    self.write_mem(self.i_plus(idx as u32), self.v_reg[idx]);
                 }
+                self.memory_increment(x);
             }
             // FX65 LOAD V0-VX
-            (0xF, _, 6, 5) => {
-                let x = digit2 as usize;
-                let i = self.i_reg as usize;
+            Instruction::Load(x) => {
+                let x = x as usize;
                 for idx in 0..=x {
-                    self.v_reg[idx] = self.ram[i + idx];
+                    self.v_reg[idx] = self.read_mem(self.i_plus(idx as u32));
+                }
+                self.memory_increment(x);
+            }
+            // CHIP-8X
+            Instruction::CycleBackground => {
+                self.colors.cycle_background();
+            }
+            Instruction::AddColors(x, y) => {
+                let (vx, vy) = (self.v_reg[x as usize], self.v_reg[y as usize]);
+                self.v_reg[x as usize] = ((vx & 0x77) + (vy & 0x77)) & 0x77;
+            }
+            Instruction::ColorZones(x, y) => {
+                let horizontal = self.v_reg[x as usize];
+                let vertical = self.v_reg[(x as usize + 1) % NUM_REGISTERS];
+                self.colors
+                    .color_zones(horizontal, vertical, self.v_reg[y as usize]);
+            }
+            Instruction::ColorRows(x, y, n) => {
+                let px = self.v_reg[x as usize];
+                let py = self.v_reg[(x as usize + 1) % NUM_REGISTERS];
+                self.colors.color_rows(px, py, n, self.v_reg[y as usize]);
+            }
+            Instruction::SkipKey2(x) => {
+                if self.keys2[(self.v_reg[x as usize] & 0xF) as usize] {
+                    self.skip();
+                }
+            }
+            Instruction::SkipNotKey2(x) => {
+                if !self.keys2[(self.v_reg[x as usize] & 0xF) as usize] {
+                    self.skip();
                 }
             }
-            (_, _, _, _) => unimplemented!("Unimplemented opcode: {}", op),
+            Instruction::PortOut(x) => {
+                self.port_out = self.v_reg[x as usize];
+            }
+            Instruction::PortIn(x) => {
+                self.v_reg[x as usize] = self.port_in;
+            }
+            // MegaChip8
+            Instruction::MegaOff
+            | Instruction::MegaOn
+            | Instruction::LoadLongI(_)
+            | Instruction::LoadPalette(_)
+            | Instruction::SpriteWidth(_)
+            | Instruction::SpriteHeight(_)
+            | Instruction::ScreenAlpha(_)
+            | Instruction::PlaySample(_)
+            | Instruction::StopSample
+            | Instruction::SetBlend(_)
+            | Instruction::CollisionColor(_) => {
+                #[cfg(feature = "megachip")]
+                return self.execute_mega(ins);
+                #[cfg(not(feature = "megachip"))]
+                unreachable!("MegaChip opcodes need the megachip feature");
+            }
+            Instruction::MachineCall(addr) => {
+                #[cfg(feature = "cdp1802")]
+                return self.call_machine_code(addr);
+                #[cfg(not(feature = "cdp1802"))]
+                unreachable!("{:03X}: machine code calls need the cdp1802 feature", addr);
+            }
+            Instruction::Invalid(op) => {
+                #[cfg(feature = "alloc")]
+                if self.run_extensions(op) == Handling::Handled {
+                    return Ok(());
+                }
+                return Err(EmuError::InvalidOpcode {
+                    op,
+                    pc: self.op_addr(),
+                });
+            }
         }
+        Ok(())
+    }
+
+    fn skip(&mut self) {
+        self.set_pc_wrapped(self.pc + 2);
     }
 
+    fn logic_vf_reset(&mut self) {
+        if self.quirks.vf_reset {
+            self.v_reg[0xF] = 0;
+        }
+    }
+
+    fn shift_source(&mut self, x: usize, y: usize) {
+        if !self.quirks.shift_vx {
+            self.v_reg[x] = self.v_reg[y];
+        }
+    }
+
+    fn memory_increment(&mut self, x: usize) {
+        if self.quirks.memory_increment {
+            self.i_reg = self.i_plus(x as u32 + 1);
+        }
+    }
+
+    // Call at 60 Hz. On 50 Hz platforms every sixth call does nothing.
     pub fn tick_timers(&mut self) {
+        if self.platform.timer_hz() == 50 {
+            self.timer_calls = (self.timer_calls + 1) % 6;
+            if self.timer_calls == 0 {
+                return;
+            }
+        }
         if self.dt > 0 {
             self.dt -= 1;
         }
@@ -409,33 +864,217 @@ impl Emu {
                 // BEEP
                 // Because this book does not implement this function, so it will finish later.
             }
+            self.st -= 1;
         }
+        #[cfg(feature = "observe")]
+        self.notify(|o| o.timers(self.dt, self.st));
     }
 
-    fn fetch(&mut self) -> u16 {
-        // Use Big-Endian format for composing data.
-        let higher_byte = self.ram[self.pc as usize] as u16;
-        let lower_byte = self.ram[(self.pc + 1) as usize] as u16;
-        let op = (higher_byte << 8) | lower_byte;
-        self.pc += 2;
-        op
+    #[inline]
+    fn fetch(&mut self) -> Instruction {
+        let ins = match self.decoded[self.pc as usize] {
+            Some(ins) => ins,
+            None => {
+                // Use Big-Endian format for composing data.
+                let higher_byte = self.read(self.pc) as u16;
+                let lower_byte = self.read(self.pc + 1) as u16;
+                let ins = Instruction::decode_for(self.platform, (higher_byte << 8) | lower_byte);
+                self.decoded[self.pc as usize] = Some(ins);
+                ins
+            }
+        };
+        self.set_pc_wrapped(self.pc + 2);
+        ins
     }
 
     // Display-related function
+    // Row-major, screen_size() pixels.
     pub fn get_display(&self) -> &[bool] {
-        &self.screen
+        let (width, height) = self.screen_size();
+        &self.screen[..width * height]
+    }
+    pub fn screen_size(&self) -> (usize, usize) {
+        self.platform.screen_size()
     }
     pub fn keypress(&mut self, idx: usize, pressed: bool) {
         self.keys[idx] = pressed;
     }
-    pub fn load(&mut self, data: &[u8]) {
-        let start = START_ADDR as usize;
-        let end = (START_ADDR as usize) + data.len();
-        self.ram[start..end].copy_from_slice(data);
+    // The second keypad, read by CHIP-8X's EXF2 and EXF5.
+    pub fn keypress2(&mut self, idx: usize, pressed: bool) {
+        self.keys2[idx] = pressed;
+    }
+    // Colours to draw the screen in, on platforms that have them.
+    pub fn color_layer(&self) -> Option<&ColorLayer> {
+        (self.platform == Platform::Chip8X).then_some(&self.colors)
+    }
+    // MegaChip8's colour picture, once the program has switched it on with
+    // 0011; until then get_display() has the picture.
+    #[cfg(feature = "megachip")]
+    pub fn framebuffer(&self) -> Option<&Framebuffer> {
+        let mega = self.mega.as_deref().filter(|m| m.on)?;
+        Some(mega.framebuffer())
+    }
+    // The sample MegaChip8 is playing, for the frontend to output.
+    #[cfg(feature = "megachip")]
+    pub fn sample(&self) -> Option<Sample> {
+        self.mega.as_deref()?.sample()
+    }
+    // Its bytes, read from memory as it is now; nothing when it isn't
+    // playing.
+    #[cfg(feature = "megachip")]
+    pub fn sample_data(&self) -> impl Iterator<Item = u8> + '_ {
+        self.mega
+            .as_deref()
+            .into_iter()
+            .flat_map(|mega| mega.sample_data(&self.ram))
+    }
+    // CHIP-8X expansion port: FXF8 writes port_output(), FXFB reads what was
+    // last given to set_port_input(). On a VIP the VP-595 sound board sits
+    // there and takes the byte as its tone.
+    pub fn port_output(&self) -> u8 {
+        self.port_out
+    }
+    pub fn set_port_input(&mut self, val: u8) {
+        self.port_in = val;
+    }
+    // Loads a ROM at the platform's usual address and entry point.
+    pub fn load(&mut self, data: &[u8]) -> Result<(), EmuError> {
+        self.load_at(data, self.platform.load_addr(), self.platform.entry())
+    }
+
+    // Copies a ROM image to `load_addr` and starts running at `entry`. Bytes
+    // that would land below START_ADDR are taken to be a copy of the
+    // interpreter, as in memory dumps loaded at 0, and skipped. The machine is
+    // reset first, so nothing of a previous ROM is left behind; on error it is
+    // left untouched.
+    pub fn load_at(&mut self, data: &[u8], load_addr: u16, entry: u16) -> Result<(), EmuError> {
+        let memory = self.platform.memory_size();
+        if load_addr as usize >= memory {
+            return Err(EmuError::BadLoadAddress { addr: load_addr });
+        }
+        if entry < START_ADDR || entry as usize >= RAM_SIZE {
+            return Err(EmuError::BadEntryPoint { entry });
+        }
+        if entry as usize >= load_addr as usize + data.len() {
+            return Err(EmuError::EntryPastRom { entry });
+        }
+        let skip = START_ADDR.saturating_sub(load_addr) as usize;
+        let program = data.get(skip..).unwrap_or_default();
+        let start = load_addr.max(START_ADDR) as usize;
+        if program.len() > memory - start {
+            return Err(EmuError::RomTooLarge {
+                size: program.len(),
+                max: memory - start,
+            });
+        }
+        self.reset();
+        let (low, high) = program.split_at(program.len().min(RAM_SIZE - start));
+        self.ram[start..start + low.len()].copy_from_slice(low);
+        #[cfg(feature = "megachip")]
+        if let Some(mega) = &mut self.mega {
+            mega.load_high(high);
+        }
+        // Only MegaChip8 has memory past `ram`, and the size check above
+        // only lets programs run into it there.
+        debug_assert!(high.is_empty() || self.platform.memory_size() > RAM_SIZE);
+        self.pc = entry;
+        Ok(())
+    }
+
+    // Reads a whole ROM, e.g. from a file, and loads it. A ROM that doesn't
+    // fit is reported as InvalidData wrapping EmuError::RomTooLarge.
+    #[cfg(feature = "std")]
+    pub fn load_from(&mut self, mut reader: impl std::io::Read) -> std::io::Result<()> {
+        let mut rom = Vec::new();
+        reader.read_to_end(&mut rom)?;
+        self.load(&rom)
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))
+    }
+
+    // State access, for tools that inspect or seed the machine.
+    pub fn pc(&self) -> u16 {
+        self.pc
+    }
+    pub fn sp(&self) -> u16 {
+        self.sp
+    }
+    pub fn i_reg(&self) -> u32 {
+        self.i_reg
+    }
+    pub fn v_reg(&self) -> &[u8; NUM_REGISTERS] {
+        &self.v_reg
+    }
+    pub fn ram(&self) -> &[u8; RAM_SIZE] {
+        &self.ram
+    }
+    pub fn delay_timer(&self) -> u8 {
+        self.dt
+    }
+    pub fn sound_timer(&self) -> u8 {
+        self.st
+    }
+    // Out-of-range addresses wrap around RAM, like the interpreter does.
+    pub fn set_pc(&mut self, pc: u16) {
+        self.set_pc_wrapped(pc);
+    }
+    pub fn set_i_reg(&mut self, val: u32) {
+        self.i_reg = val & self.i_mask();
+    }
+    pub fn set_v_reg(&mut self, x: usize, val: u8) {
+        self.v_reg[x] = val;
+    }
+    pub fn v_reg_mut(&mut self) -> &mut [u8; NUM_REGISTERS] {
+        &mut self.v_reg
+    }
+    pub fn set_timers(&mut self, dt: u8, st: u8) {
+        self.dt = dt;
+        self.st = st;
+    }
+    // Memory access for cheats and debuggers. Writes go through the same path
+    // as FX33/FX55, so the decoded instruction cache stays in sync.
+    pub fn peek(&self, addr: u16) -> u8 {
+        self.read(addr)
+    }
+    pub fn poke(&mut self, addr: u16, val: u8) {
+        self.write(addr, val);
+    }
+    pub fn quirks(&self) -> Quirks {
+        self.quirks
+    }
+    // Quirks are configuration, so reset() keeps them.
+    pub fn set_quirks(&mut self, quirks: Quirks) {
+        self.quirks = quirks;
+    }
+    pub fn platform(&self) -> Platform {
+        self.platform
+    }
+    // Picks the memory layout and font load() and reset() use and the screen
+    // size, so set it before loading. Like quirks it survives reset(); the
+    // platform's quirks are not applied, see Platform::quirks.
+    pub fn set_platform(&mut self, platform: Platform) {
+        self.platform = platform;
+        #[cfg(feature = "megachip")]
+        {
+            self.mega = (platform == Platform::MegaChip8)
+                .then(|| alloc::boxed::Box::new(megachip::Mega::new()));
+        }
+        // Platforms decode some opcodes differently.
+        self.decoded = [None; RAM_SIZE];
+        #[cfg(feature = "jit")]
+        {
+            self.ram_version = next_ram_version();
+        }
     }
 }
-fn get_random_u8() -> Result<u8, getrandom::Error> {
+#[cfg(feature = "jit")]
+fn next_ram_version() -> u64 {
+    RAM_VERSIONS.fetch_add(1, core::sync::atomic::Ordering::Relaxed)
+}
+
+// A random byte from the OS, for Emu::new or Platform::machine.
+#[cfg(feature = "os-rng")]
+pub fn os_random() -> u8 {
     let mut buf = [0u8; 1];
-    getrandom::fill(&mut buf)?;
-    Ok(u8::from_ne_bytes(buf))
+    getrandom::fill(&mut buf).expect("OS random number generator failed");
+    buf[0]
 }
@@ -0,0 +1,128 @@
+// The CHIP-8 family behind one interface, so frontends and tools can drive
+// any variant without knowing what implements it. Emu covers every Platform
+// today; Platform::machine picks the implementation for a platform.
+#[cfg(feature = "megachip")]
+use crate::Framebuffer;
+use crate::{ColorLayer, Emu, EmuError, NUM_REGISTERS, Platform, Quirks, RAM_SIZE, STACK_SIZE};
+
+pub trait Machine {
+    fn platform(&self) -> Platform;
+    fn quirks(&self) -> Quirks;
+    fn set_quirks(&mut self, quirks: Quirks);
+    // Resets, then loads a ROM where the platform expects it.
+    fn load(&mut self, rom: &[u8]) -> Result<(), EmuError>;
+    fn reset(&mut self);
+    // Runs one instruction.
+    fn step(&mut self) -> Result<(), EmuError>;
+    // Call at 60 Hz.
+    fn tick_timers(&mut self);
+    // What to show right now.
+    fn frame(&self) -> Frame<'_>;
+    // Keys 0-F of the keypad.
+    fn keypress(&mut self, key: usize, pressed: bool);
+    // Keys of a second keypad, on platforms that have one.
+    fn keypress2(&mut self, _key: usize, _pressed: bool) {}
+    fn state(&self) -> MachineState;
+    fn set_state(&mut self, state: &MachineState);
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum Frame<'a> {
+    // Pixels on or off, row-major. CHIP-8X colours them through `colors`.
+    Mono {
+        pixels: &'a [bool],
+        width: usize,
+        height: usize,
+        colors: Option<&'a ColorLayer>,
+    },
+    #[cfg(feature = "megachip")]
+    Color(&'a Framebuffer),
+}
+
+impl Frame<'_> {
+    // Width and height in pixels.
+    pub fn size(&self) -> (usize, usize) {
+        match self {
+            Frame::Mono { width, height, .. } => (*width, *height),
+            #[cfg(feature = "megachip")]
+            Frame::Color(picture) => (picture.width(), picture.height()),
+        }
+    }
+}
+
+// The CPU's registers and timers: enough to seed a machine or compare two.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MachineState {
+    pub pc: u16,
+    pub i_reg: u32,
+    pub v_reg: [u8; NUM_REGISTERS],
+    pub stack: [u16; STACK_SIZE],
+    pub sp: u16,
+    pub dt: u8,
+    pub st: u8,
+}
+
+impl Machine for Emu {
+    fn platform(&self) -> Platform {
+        self.platform
+    }
+    fn quirks(&self) -> Quirks {
+        self.quirks
+    }
+    fn set_quirks(&mut self, quirks: Quirks) {
+        self.quirks = quirks;
+    }
+    fn load(&mut self, rom: &[u8]) -> Result<(), EmuError> {
+        Emu::load(self, rom)
+    }
+    fn reset(&mut self) {
+        Emu::reset(self);
+    }
+    fn step(&mut self) -> Result<(), EmuError> {
+        self.tick()
+    }
+    fn tick_timers(&mut self) {
+        Emu::tick_timers(self);
+    }
+    fn frame(&self) -> Frame<'_> {
+        #[cfg(feature = "megachip")]
+        if let Some(picture) = self.framebuffer() {
+            return Frame::Color(picture);
+        }
+        let (width, height) = self.screen_size();
+        Frame::Mono {
+            pixels: self.get_display(),
+            width,
+            height,
+            colors: self.color_layer(),
+        }
+    }
+    fn keypress(&mut self, key: usize, pressed: bool) {
+        Emu::keypress(self, key, pressed);
+    }
+    fn keypress2(&mut self, key: usize, pressed: bool) {
+        Emu::keypress2(self, key, pressed);
+    }
+    fn state(&self) -> MachineState {
+        MachineState {
+            pc: self.pc,
+            i_reg: self.i_reg,
+            v_reg: self.v_reg,
+            stack: self.stack,
+            sp: self.sp,
+            dt: self.dt,
+            st: self.st,
+        }
+    }
+    // Addresses are wrapped the way the machine wraps them itself, so a
+    // made-up state can't point anywhere it couldn't.
+    fn set_state(&mut self, state: &MachineState) {
+        self.set_pc(state.pc);
+        self.set_i_reg(state.i_reg);
+        self.v_reg = state.v_reg;
+        self.stack = state.stack.map(|addr| addr % RAM_SIZE as u16);
+        self.sp = state.sp.min(STACK_SIZE as u16);
+        self.dt = state.dt;
+        self.st = state.st;
+    }
+}
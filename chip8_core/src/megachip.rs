@@ -0,0 +1,264 @@
+// MegaChip8 (MEGA-CHIP by Martijn Wanting). After 0011 the program draws
+// palette-indexed sprites of any size into a 256x192 colour buffer that 00E0
+// puts on screen, addresses 16 MiB through a 24-bit I and can play samples.
+use crate::{Emu, EmuError, Instruction, NUM_REGISTERS, RAM_SIZE};
+use alloc::{vec, vec::Vec};
+
+pub const MEGA_SCREEN_W: usize = 256;
+pub const MEGA_SCREEN_H: usize = 192;
+// Everything a 24-bit I can reach.
+pub const MEGA_RAM_SIZE: usize = 1 << 24;
+
+// How 080N mixes sprite pixels with what is already drawn.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BlendMode {
+    Normal,
+    // The sprite at 25% or 50% of its own alpha.
+    Quarter,
+    Half,
+    Add,
+    Multiply,
+}
+
+// A sound started by 060N: unsigned 8-bit mono. It stays in memory, len
+// bytes from start, and Emu::sample_data reads it from there.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Sample {
+    // Samples per second.
+    pub rate: u16,
+    pub start: u32,
+    pub len: u32,
+    pub looping: bool,
+}
+
+// A colour picture, row-major RGB.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Framebuffer {
+    width: usize,
+    height: usize,
+    pixels: Vec<[u8; 3]>,
+}
+
+impl Framebuffer {
+    fn new(width: usize, height: usize) -> Self {
+        Framebuffer {
+            width,
+            height,
+            pixels: vec![[0; 3]; width * height],
+        }
+    }
+    pub fn width(&self) -> usize {
+        self.width
+    }
+    pub fn height(&self) -> usize {
+        self.height
+    }
+    pub fn pixels(&self) -> &[[u8; 3]] {
+        &self.pixels
+    }
+    pub fn pixel(&self, x: usize, y: usize) -> [u8; 3] {
+        self.pixels[y * self.width + x]
+    }
+}
+
+#[derive(Clone)]
+pub(crate) struct Mega {
+    // Memory from RAM_SIZE up; below that it is Emu::ram.
+    high_mem: Vec<u8>,
+    // Set by 0011: the colour screen is in use.
+    pub(crate) on: bool,
+    // ARGB, loaded by 02NN into entries 1 and up. Index 0 is transparent.
+    palette: [[u8; 4]; 256],
+    sprite_w: usize,
+    sprite_h: usize,
+    // 05NN, applied to the whole picture when it is shown.
+    alpha: u8,
+    blend: BlendMode,
+    // Sprite pixels drawn over this palette index set VF; 0, the empty
+    // background, never collides.
+    collision: u8,
+    // Drawn into by DXYN, shown by 00E0.
+    back: Framebuffer,
+    // Palette index of each back buffer pixel, for collisions.
+    indices: Vec<u8>,
+    front: Framebuffer,
+    sample: Option<Sample>,
+}
+
+impl Mega {
+    pub(crate) fn new() -> Self {
+        Mega {
+            high_mem: vec![0; MEGA_RAM_SIZE - RAM_SIZE],
+            on: false,
+            palette: [[0xFF; 4]; 256],
+            sprite_w: 0,
+            sprite_h: 0,
+            alpha: 0xFF,
+            blend: BlendMode::Normal,
+            collision: 0,
+            back: Framebuffer::new(MEGA_SCREEN_W, MEGA_SCREEN_H),
+            indices: vec![0; MEGA_SCREEN_W * MEGA_SCREEN_H],
+            front: Framebuffer::new(MEGA_SCREEN_W, MEGA_SCREEN_H),
+            sample: None,
+        }
+    }
+
+    pub(crate) fn read(&self, ram: &[u8; RAM_SIZE], addr: u32) -> u8 {
+        let addr = addr as usize % MEGA_RAM_SIZE;
+        match addr.checked_sub(RAM_SIZE) {
+            Some(high) => self.high_mem[high],
+            None => ram[addr],
+        }
+    }
+
+    // Only for addresses past the end of Emu::ram.
+    pub(crate) fn write_high(&mut self, addr: u32, val: u8) {
+        self.high_mem[addr as usize % MEGA_RAM_SIZE - RAM_SIZE] = val;
+    }
+
+    // Loads the part of a ROM that doesn't fit in Emu::ram.
+    pub(crate) fn load_high(&mut self, data: &[u8]) {
+        self.high_mem[..data.len()].copy_from_slice(data);
+    }
+
+    pub(crate) fn framebuffer(&self) -> &Framebuffer {
+        &self.front
+    }
+
+    pub(crate) fn sample(&self) -> Option<Sample> {
+        self.sample
+    }
+
+    pub(crate) fn sample_data<'a>(
+        &'a self,
+        ram: &'a [u8; RAM_SIZE],
+    ) -> impl Iterator<Item = u8> + 'a {
+        let (start, len) = self.sample.map_or((0, 0), |s| (s.start, s.len));
+        (start..start + len).map(move |addr| self.read(ram, addr))
+    }
+
+    // 00E0 in MegaChip mode: show what was drawn and start a new picture.
+    pub(crate) fn present(&mut self) {
+        let alpha = self.alpha as u32;
+        for (front, back) in self.front.pixels.iter_mut().zip(&self.back.pixels) {
+            *front = back.map(|c| (c as u32 * alpha / 255) as u8);
+        }
+        self.back.pixels.fill([0; 3]);
+        self.indices.fill(0);
+    }
+
+    // Sprites are sprite_w x sprite_h palette indices at `i`, clipped at the
+    // screen edges. Returns whether a pixel landed on the collision colour.
+    fn draw(&mut self, ram: &[u8; RAM_SIZE], i: u32, x: u8, y: u8) -> bool {
+        let mut hit = false;
+        for row in 0..self.sprite_h {
+            let py = y as usize + row;
+            if py >= MEGA_SCREEN_H {
+                break;
+            }
+            for col in 0..self.sprite_w {
+                let px = x as usize + col;
+                if px >= MEGA_SCREEN_W {
+                    break;
+                }
+                let idx = self.read(ram, i.wrapping_add((row * self.sprite_w + col) as u32));
+                if idx == 0 {
+                    continue;
+                }
+                let p = py * MEGA_SCREEN_W + px;
+                hit |= self.indices[p] != 0 && self.indices[p] == self.collision;
+                self.indices[p] = idx;
+                let dst = self.back.pixels[p];
+                self.back.pixels[p] = blend(self.blend, self.palette[idx as usize], dst);
+            }
+        }
+        hit
+    }
+}
+
+fn blend(mode: BlendMode, [a, r, g, b]: [u8; 4], dst: [u8; 3]) -> [u8; 3] {
+    let src = [r, g, b];
+    let mix = |alpha: u32| {
+        core::array::from_fn(|c| {
+            ((src[c] as u32 * alpha + dst[c] as u32 * (255 - alpha)) / 255) as u8
+        })
+    };
+    match mode {
+        BlendMode::Normal => mix(a as u32),
+        BlendMode::Quarter => mix(a as u32 / 4),
+        BlendMode::Half => mix(a as u32 / 2),
+        BlendMode::Add => core::array::from_fn(|c| src[c].saturating_add(dst[c])),
+        BlendMode::Multiply => {
+            core::array::from_fn(|c| (src[c] as u32 * dst[c] as u32 / 255) as u8)
+        }
+    }
+}
+
+impl Emu {
+    pub(crate) fn execute_mega(&mut self, ins: Instruction) -> Result<(), EmuError> {
+        // 01NN NNNN: the low 16 bits are the next word.
+        if let Instruction::LoadLongI(nn) = ins {
+            let lo = u16::from_be_bytes([self.read(self.pc), self.read(self.pc + 1)]);
+            self.i_reg = (nn as u32) << 16 | lo as u32;
+            self.skip();
+            return Ok(());
+        }
+        let i = self.i_reg;
+        let Some(mega) = self.mega.as_deref_mut() else {
+            unreachable!("MegaChip opcodes are only decoded on MegaChip8");
+        };
+        match ins {
+            Instruction::MegaOff => mega.on = false,
+            Instruction::MegaOn => mega.on = true,
+            Instruction::LoadPalette(n) => {
+                for k in 0..n as u32 {
+                    let argb = core::array::from_fn(|c| mega.read(&self.ram, i + 4 * k + c as u32));
+                    mega.palette[k as usize + 1] = argb;
+                }
+            }
+            Instruction::SpriteWidth(nn) => mega.sprite_w = if nn == 0 { 256 } else { nn as usize },
+            Instruction::SpriteHeight(nn) => {
+                mega.sprite_h = if nn == 0 { 256 } else { nn as usize }
+            }
+            Instruction::ScreenAlpha(nn) => mega.alpha = nn,
+            // The sound at I starts with its rate (2 bytes) and length (3
+            // bytes) and a reserved byte. A length running past the end of
+            // memory stops there.
+            Instruction::PlaySample(n) => {
+                let byte = |k: u32| mega.read(&self.ram, i + k) as u32;
+                let rate = (byte(0) << 8 | byte(1)) as u16;
+                let len = byte(2) << 16 | byte(3) << 8 | byte(4);
+                let start = (i + 6) % MEGA_RAM_SIZE as u32;
+                mega.sample = Some(Sample {
+                    rate,
+                    start,
+                    len: len.min(MEGA_RAM_SIZE as u32 - start),
+                    looping: n == 0,
+                });
+            }
+            Instruction::StopSample => mega.sample = None,
+            Instruction::SetBlend(n) => {
+                mega.blend = match n {
+                    1 => BlendMode::Quarter,
+                    2 => BlendMode::Half,
+                    3 => BlendMode::Add,
+                    4 => BlendMode::Multiply,
+                    _ => BlendMode::Normal,
+                }
+            }
+            Instruction::CollisionColor(nn) => mega.collision = nn,
+            _ => unreachable!("{:?} is not a MegaChip opcode", ins),
+        }
+        Ok(())
+    }
+
+    // DXYN while the colour screen is on; N is unused.
+    pub(crate) fn draw_mega(&mut self, x: u8, y: u8) {
+        let (vx, vy) = (self.v_reg[x as usize], self.v_reg[y as usize]);
+        let Some(mega) = self.mega.as_deref_mut() else {
+            return;
+        };
+        let hit = mega.draw(&self.ram, self.i_reg, vx, vy);
+        self.v_reg[NUM_REGISTERS - 1] = hit as u8;
+    }
+}
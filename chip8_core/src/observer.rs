@@ -0,0 +1,91 @@
+// Watching the interpreter, for profilers, coverage, cheat search and the
+// like. Only built with the `observe` feature, and even then Emu skips all
+// of it while no observer is attached. Every attached observer sees every
+// event, in the order they were added.
+use crate::{Emu, Instruction, NUM_REGISTERS};
+use alloc::sync::Arc;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Register {
+    V(u8),
+    I,
+    Delay,
+    Sound,
+}
+
+// All methods default to doing nothing, so an observer only implements what
+// it needs. Observers are shared with Emu, so they keep what they collect
+// behind atomics or a lock.
+pub trait Observer: Send + Sync {
+    // An instruction at `addr` is about to run.
+    fn fetch(&self, _addr: u16, _ins: Instruction) {}
+    // An instruction read a byte of memory through I.
+    fn read(&self, _addr: u32, _val: u8) {}
+    // A byte of memory was written, by an instruction or Emu::poke.
+    fn write(&self, _addr: u32, _val: u8) {}
+    // An instruction changed a register.
+    fn register(&self, _reg: Register, _val: u32) {}
+    // DXYN drew at (x, y), the values of VX and VY; `collision` is VF after.
+    fn draw(&self, _x: u8, _y: u8, _height: u8, _collision: bool) {}
+    // tick_timers() ran.
+    fn timers(&self, _delay: u8, _sound: u8) {}
+}
+
+// Registers before an instruction, to report what it changed.
+#[derive(Clone, Copy)]
+pub(crate) struct Registers {
+    v_reg: [u8; NUM_REGISTERS],
+    i_reg: u32,
+    dt: u8,
+    st: u8,
+}
+
+impl Emu {
+    pub fn add_observer(&mut self, observer: Arc<dyn Observer>) {
+        self.observers.push(observer);
+    }
+
+    pub(crate) fn notify(&self, event: impl Fn(&dyn Observer)) {
+        for observer in &self.observers {
+            event(observer.as_ref());
+        }
+    }
+
+    // Called by tick() after fetching; None when nobody is watching.
+    pub(crate) fn observe_fetch(&self, addr: u16, ins: Instruction) -> Option<Registers> {
+        if self.observers.is_empty() {
+            return None;
+        }
+        self.notify(|o| o.fetch(addr, ins));
+        Some(Registers {
+            v_reg: self.v_reg,
+            i_reg: self.i_reg,
+            dt: self.dt,
+            st: self.st,
+        })
+    }
+
+    // Called by tick() after executing `ins`, with the registers from before.
+    pub(crate) fn observe_execute(&self, ins: Instruction, before: Registers) {
+        for (x, (old, new)) in before.v_reg.iter().zip(self.v_reg).enumerate() {
+            if *old != new {
+                self.notify(|o| o.register(Register::V(x as u8), new as u32));
+            }
+        }
+        let others = [
+            (Register::I, before.i_reg, self.i_reg),
+            (Register::Delay, before.dt as u32, self.dt as u32),
+            (Register::Sound, before.st as u32, self.st as u32),
+        ];
+        for (reg, old, new) in others {
+            if old != new {
+                self.notify(|o| o.register(reg, new));
+            }
+        }
+        if let Instruction::Draw(x, y, n) = ins {
+            let (vx, vy) = (before.v_reg[x as usize], before.v_reg[y as usize]);
+            let collision = self.v_reg[NUM_REGISTERS - 1] != 0;
+            self.notify(|o| o.draw(vx, vy, n, collision));
+        }
+    }
+}
@@ -0,0 +1,968 @@
+// An assembler for Octo, the language CHIP-8 programs are shared as source
+// in (https://github.com/JohnEarnest/Octo/blob/gh-pages/docs/Manual.md).
+// Like Octo, programs start at 0x200 with a jump to main, which is dropped
+// when main comes first anyway. Only what Emu runs is assembled: the
+// SUPER-CHIP and XO-CHIP statements are errors, and programs have 4K of
+// memory. Breakpoints and monitors only mean something to Octo's debugger
+// and are skipped.
+use crate::RAM_SIZE;
+use std::collections::{HashMap, VecDeque};
+use std::fmt;
+
+const START: u32 = 0x200;
+const END: u32 = RAM_SIZE as u32;
+// Macros calling themselves would otherwise expand forever.
+const MAX_EXPANSIONS: usize = 1 << 16;
+// Operators of :calc expressions. They all bind equally, right to left.
+const BINARY: &[&str] = &[
+    "-", "+", "*", "/", "%", "&", "|", "^", "<<", ">>", "pow", "min", "max", "<", "<=", "==", "!=",
+    ">=", ">",
+];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OctoError {
+    pub line: usize,
+    pub msg: String,
+}
+
+impl fmt::Display for OctoError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.msg)
+    }
+}
+
+impl std::error::Error for OctoError {}
+
+// The ROM image of an Octo program, to load at 0x200.
+pub fn assemble(source: &str) -> Result<Vec<u8>, OctoError> {
+    let mut asm = Assembler {
+        tokens: tokenize(source)?.into(),
+        line: 1,
+        rom: Vec::new(),
+        here: START,
+        labels: HashMap::new(),
+        consts: HashMap::new(),
+        aliases: HashMap::new(),
+        fixups: Vec::new(),
+        macros: HashMap::new(),
+        stringmodes: HashMap::new(),
+        expansions: 0,
+        branches: Vec::new(),
+        loops: Vec::new(),
+    };
+    asm.run()?;
+    Ok(asm.rom)
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Tok {
+    Word(String),
+    Num(f64),
+    Str(String),
+}
+
+#[derive(Debug, Clone)]
+struct Token {
+    tok: Tok,
+    line: usize,
+}
+
+impl Token {
+    fn is(&self, word: &str) -> bool {
+        matches!(&self.tok, Tok::Word(w) if w == word)
+    }
+}
+
+fn tokenize(source: &str) -> Result<Vec<Token>, OctoError> {
+    let mut tokens = Vec::new();
+    for (idx, text) in source.lines().enumerate() {
+        let line = idx + 1;
+        let error = |msg: &str| OctoError {
+            line,
+            msg: msg.to_string(),
+        };
+        let mut chars = text.chars().peekable();
+        loop {
+            while chars.next_if(|c| c.is_whitespace()).is_some() {}
+            match chars.peek() {
+                None | Some('#') => break,
+                Some('"') => {
+                    chars.next();
+                    let mut text = String::new();
+                    loop {
+                        match chars.next() {
+                            Some('"') => break,
+                            Some('\\') => text.push(match chars.next() {
+                                Some('n') => '\n',
+                                Some('r') => '\r',
+                                Some('t') => '\t',
+                                Some('0') => '\0',
+                                Some(c) => c,
+                                None => return Err(error("unterminated string")),
+                            }),
+                            Some(c) => text.push(c),
+                            None => return Err(error("unterminated string")),
+                        }
+                    }
+                    tokens.push(Token {
+                        tok: Tok::Str(text),
+                        line,
+                    });
+                }
+                Some(_) => {
+                    let mut word = String::new();
+                    while let Some(c) = chars.next_if(|c| !c.is_whitespace()) {
+                        word.push(c);
+                    }
+                    let tok = match parse_number(&word) {
+                        Some(n) => Tok::Num(n),
+                        None => Tok::Word(word),
+                    };
+                    tokens.push(Token { tok, line });
+                }
+            }
+        }
+    }
+    Ok(tokens)
+}
+
+// 0x1F, 0b101, 31 or -31.
+fn parse_number(word: &str) -> Option<f64> {
+    let (negative, digits) = match word.strip_prefix('-') {
+        Some(digits) => (true, digits),
+        None => (false, word),
+    };
+    let value = if let Some(hex) = digits.strip_prefix("0x") {
+        i64::from_str_radix(hex, 16).ok()? as f64
+    } else if let Some(bin) = digits.strip_prefix("0b") {
+        i64::from_str_radix(bin, 2).ok()? as f64
+    } else if digits.starts_with(|c: char| c.is_ascii_digit()) {
+        digits.parse().ok()?
+    } else {
+        return None;
+    };
+    Some(if negative { -value } else { value })
+}
+
+// How a label used before it is defined gets filled in.
+#[derive(Debug, Clone, Copy)]
+enum Use {
+    // The low 12 bits of an instruction.
+    Addr12,
+    Addr16,
+    // The immediates of `v0 := NN v1 := NN`, with the nibble above a 12
+    // bit address or the whole of a 16 bit one.
+    Unpack(Option<u8>),
+}
+
+struct Fixup {
+    name: String,
+    at: u32,
+    kind: Use,
+    line: usize,
+}
+
+struct Macro {
+    args: Vec<String>,
+    body: Vec<Token>,
+    calls: u32,
+}
+
+struct Assembler {
+    tokens: VecDeque<Token>,
+    // Of the token being assembled, for errors.
+    line: usize,
+    // Memory from START up to the last byte written.
+    rom: Vec<u8>,
+    here: u32,
+    labels: HashMap<String, u32>,
+    consts: HashMap<String, f64>,
+    aliases: HashMap<String, u8>,
+    fixups: Vec<Fixup>,
+    macros: HashMap<String, Macro>,
+    // Per character, its value in the alphabet and the code to expand.
+    stringmodes: HashMap<String, HashMap<char, (u32, Vec<Token>)>>,
+    expansions: usize,
+    // Jumps of open if and else blocks, to point past them at their end.
+    branches: Vec<u32>,
+    // Where each open loop starts, and the jumps out of it of its whiles.
+    loops: Vec<(u32, Vec<u32>)>,
+}
+
+impl Assembler {
+    fn run(&mut self) -> Result<(), OctoError> {
+        self.fixups.push(Fixup {
+            name: "main".to_string(),
+            at: START,
+            kind: Use::Addr12,
+            line: 1,
+        });
+        self.op(0x1000)?;
+        while let Some(token) = self.tokens.pop_front() {
+            self.line = token.line;
+            self.statement(token)?;
+        }
+        if !self.branches.is_empty() {
+            return Err(self.error("if without end"));
+        }
+        if !self.loops.is_empty() {
+            return Err(self.error("loop without again"));
+        }
+        for fixup in std::mem::take(&mut self.fixups) {
+            self.line = fixup.line;
+            let Some(&addr) = self.labels.get(&fixup.name) else {
+                return Err(if fixup.name == "main" {
+                    self.error("program has no main label")
+                } else {
+                    self.error(format!("undefined name {}", fixup.name))
+                });
+            };
+            self.fill(fixup.at, addr, fixup.kind)?;
+        }
+        Ok(())
+    }
+
+    fn statement(&mut self, token: Token) -> Result<(), OctoError> {
+        let word = match token.tok {
+            Tok::Num(n) => {
+                let byte = self.check_byte(n)?;
+                return self.byte(byte);
+            }
+            Tok::Str(_) => return Err(self.error("unexpected string")),
+            Tok::Word(word) => word,
+        };
+        match word.as_str() {
+            ":" => {
+                let name = self.name()?;
+                self.define(name)?;
+            }
+            ":alias" => {
+                let name = self.name()?;
+                let reg = self.register()?;
+                self.aliases.insert(name, reg);
+            }
+            ":const" => {
+                let name = self.name()?;
+                let value = self.value()?;
+                self.consts.insert(name, value);
+            }
+            ":calc" => {
+                let name = self.name()?;
+                self.expect("{")?;
+                let value = self.calc()?;
+                self.consts.insert(name, value);
+            }
+            ":unpack" => {
+                let nibble = if self.peek_is("long") {
+                    self.next()?;
+                    None
+                } else {
+                    Some(self.nibble()?)
+                };
+                let addr = self.target(Use::Unpack(nibble), 0)?;
+                self.op(0x6000)?;
+                self.op(0x6100)?;
+                self.fill(self.here - 4, addr, Use::Unpack(nibble))?;
+            }
+            ":next" => {
+                let name = self.name()?;
+                self.here += 1;
+                let defined = self.define(name);
+                self.here -= 1;
+                defined?;
+            }
+            ":org" => {
+                let addr = self.value()?;
+                if !(START as f64..END as f64).contains(&addr) {
+                    return Err(self.error(format!("cannot assemble at {}", addr)));
+                }
+                self.here = addr as u32;
+            }
+            ":byte" => {
+                let value = self.value()?;
+                let byte = self.check_byte(value)?;
+                self.byte(byte)?;
+            }
+            ":pointer" => {
+                let addr = self.target(Use::Addr16, 0)?;
+                self.op(addr as u16)?;
+            }
+            ":call" => {
+                let addr = self.addr12(0)?;
+                self.op(0x2000 | addr)?;
+            }
+            ":macro" => {
+                let name = self.name()?;
+                let mut args = Vec::new();
+                while !self.peek_is("{") {
+                    args.push(self.name()?);
+                }
+                self.next()?;
+                let body = self.block()?;
+                self.macros.insert(
+                    name,
+                    Macro {
+                        args,
+                        body,
+                        calls: 0,
+                    },
+                );
+            }
+            ":stringmode" => {
+                let name = self.name()?;
+                let alphabet = self.string()?;
+                self.expect("{")?;
+                let body = self.block()?;
+                let mode = self.stringmodes.entry(name).or_default();
+                for (value, c) in alphabet.chars().enumerate() {
+                    mode.insert(c, (value as u32, body.clone()));
+                }
+            }
+            ":assert" => {
+                let msg = match self.tokens.front().map(|t| &t.tok) {
+                    Some(Tok::Str(_)) => format!("assertion failed: {}", self.string()?),
+                    _ => "assertion failed".to_string(),
+                };
+                if self.value()? == 0.0 {
+                    return Err(self.error(msg));
+                }
+            }
+            ":breakpoint" => {
+                self.name()?;
+            }
+            ":monitor" => {
+                self.next()?;
+                self.next()?;
+            }
+            ";" | "return" => self.op(0x00EE)?,
+            "clear" => self.op(0x00E0)?,
+            "exit" | "lores" | "hires" | "scroll-left" | "scroll-right" | "scroll-down"
+            | "saveflags" | "loadflags" => return Err(self.not_emulated(&word, "SUPER-CHIP")),
+            "scroll-up" | "audio" | "plane" | "pitch" => {
+                return Err(self.not_emulated(&word, "XO-CHIP"));
+            }
+            "bcd" => self.reg_op(0xF033)?,
+            "save" | "load" => {
+                let x = self.register()? as u16;
+                if self.peek_is("-") {
+                    return Err(self.not_emulated(&format!("{} with a range", word), "XO-CHIP"));
+                }
+                let op = if word == "save" { 0xF055 } else { 0xF065 };
+                self.op(op | x << 8)?;
+            }
+            "sprite" => {
+                let x = self.register()? as u16;
+                let y = self.register()? as u16;
+                let n = self.nibble()? as u16;
+                self.op(0xD000 | x << 8 | y << 4 | n)?;
+            }
+            "jump" => {
+                let addr = self.addr12(0)?;
+                self.op(0x1000 | addr)?;
+            }
+            "jump0" => {
+                let addr = self.addr12(0)?;
+                self.op(0xB000 | addr)?;
+            }
+            "native" => return Err(self.error("native machine code isn't emulated")),
+            "delay" | "buzzer" => {
+                self.expect(":=")?;
+                self.reg_op(if word == "delay" { 0xF015 } else { 0xF018 })?;
+            }
+            "i" => self.i_statement()?,
+            "if" => {
+                let skip = self.condition()?;
+                let then = self.word()?;
+                match then.as_str() {
+                    "then" => self.op(skip)?,
+                    "begin" => {
+                        self.op(negate(skip))?;
+                        self.branches.push(self.here);
+                        self.op(0x1000)?;
+                    }
+                    _ => return Err(self.error(format!("expected then or begin, found {}", then))),
+                }
+            }
+            "else" => {
+                let branch = self
+                    .branches
+                    .pop()
+                    .ok_or_else(|| self.error("else without if"))?;
+                let jump = self.here;
+                self.op(0x1000)?;
+                self.fill(branch, self.here, Use::Addr12)?;
+                self.branches.push(jump);
+            }
+            "end" => {
+                let branch = self
+                    .branches
+                    .pop()
+                    .ok_or_else(|| self.error("end without if"))?;
+                self.fill(branch, self.here, Use::Addr12)?;
+            }
+            "loop" => self.loops.push((self.here, Vec::new())),
+            "while" => {
+                let skip = self.condition()?;
+                self.op(negate(skip))?;
+                let here = self.here;
+                let (_, exits) = self.loops.last_mut().ok_or_else(|| OctoError {
+                    line: self.line,
+                    msg: "while outside a loop".to_string(),
+                })?;
+                exits.push(here);
+                self.op(0x1000)?;
+            }
+            "again" => {
+                let (start, exits) = self
+                    .loops
+                    .pop()
+                    .ok_or_else(|| self.error("again without loop"))?;
+                self.op(0x1000 | start as u16)?;
+                for exit in exits {
+                    self.fill(exit, self.here, Use::Addr12)?;
+                }
+            }
+            _ if self.macros.contains_key(&word) => self.expand_macro(&word)?,
+            _ if self.stringmodes.contains_key(&word) => self.expand_string(&word)?,
+            _ if self.as_register(&word).is_some() => {
+                let x = self.as_register(&word).unwrap();
+                self.register_statement(x as u16)?;
+            }
+            _ if self.consts.contains_key(&word) => {
+                let byte = self.check_byte(self.consts[&word])?;
+                self.byte(byte)?;
+            }
+            // Anything else is a subroutine to call.
+            _ => {
+                self.tokens.push_front(Token {
+                    tok: Tok::Word(word),
+                    line: self.line,
+                });
+                let addr = self.addr12(0)?;
+                self.op(0x2000 | addr)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn i_statement(&mut self) -> Result<(), OctoError> {
+        let op = self.word()?;
+        match op.as_str() {
+            ":=" if self.peek_is("long") => Err(self.not_emulated("i := long", "XO-CHIP")),
+            ":=" if self.peek_is("hex") => {
+                self.next()?;
+                self.reg_op(0xF029)
+            }
+            ":=" if self.peek_is("bighex") => Err(self.not_emulated("i := bighex", "SUPER-CHIP")),
+            ":=" => {
+                let addr = self.addr12(0)?;
+                self.op(0xA000 | addr)
+            }
+            "+=" => self.reg_op(0xF01E),
+            _ => Err(self.error(format!("unknown operator i {}", op))),
+        }
+    }
+
+    fn register_statement(&mut self, x: u16) -> Result<(), OctoError> {
+        let op = self.word()?;
+        let y = self.peek_register();
+        if let Some(y) = y {
+            let n = match op.as_str() {
+                ":=" => 0x0,
+                "|=" => 0x1,
+                "&=" => 0x2,
+                "^=" => 0x3,
+                "+=" => 0x4,
+                "-=" => 0x5,
+                ">>=" => 0x6,
+                "=-" => 0x7,
+                "<<=" => 0xE,
+                _ => return Err(self.error(format!("unknown operator {}", op))),
+            };
+            self.next()?;
+            return self.op(0x8000 | x << 8 | (y as u16) << 4 | n);
+        }
+        match op.as_str() {
+            ":=" if self.peek_is("key") => {
+                self.next()?;
+                self.op(0xF00A | x << 8)
+            }
+            ":=" if self.peek_is("delay") => {
+                self.next()?;
+                self.op(0xF007 | x << 8)
+            }
+            ":=" if self.peek_is("random") => {
+                self.next()?;
+                let value = self.value()?;
+                let mask = self.check_byte(value)?;
+                self.op(0xC000 | x << 8 | mask as u16)
+            }
+            ":=" | "+=" | "-=" => {
+                let value = self.value()?;
+                let value = if op == "-=" { -value } else { value };
+                let nn = self.check_byte(value)? as u16;
+                let base = if op == ":=" { 0x6000 } else { 0x7000 };
+                self.op(base | x << 8 | nn)
+            }
+            _ => Err(self.error(format!("unknown operator {}", op))),
+        }
+    }
+
+    // The skip after which the next instruction only runs if the condition
+    // holds, once any instructions the comparison needs are emitted.
+    fn condition(&mut self) -> Result<u16, OctoError> {
+        let x = self.register()? as u16;
+        let op = self.word()?;
+        match op.as_str() {
+            "key" => return Ok(0xE0A1 | x << 8),
+            "-key" => return Ok(0xE09E | x << 8),
+            _ => {}
+        }
+        let y = self.peek_register().map(|y| y as u16);
+        let operand = match y {
+            Some(y) => {
+                self.next()?;
+                y << 4
+            }
+            None => {
+                let value = self.value()?;
+                self.check_byte(value)? as u16
+            }
+        };
+        match (op.as_str(), y) {
+            ("==", Some(_)) => Ok(0x9000 | x << 8 | operand),
+            ("==", None) => Ok(0x4000 | x << 8 | operand),
+            ("!=", Some(_)) => Ok(0x5000 | x << 8 | operand),
+            ("!=", None) => Ok(0x3000 | x << 8 | operand),
+            // Comparisons go through VF: it is loaded with the right hand
+            // side, then VF -= VX or VF =- VX leaves whether it borrowed.
+            (">" | "<" | ">=" | "<=", _) => {
+                let load = if y.is_some() { 0x8F00 } else { 0x6F00 };
+                self.op(load | operand)?;
+                let (sub, skip) = match op.as_str() {
+                    ">" => (0x5, 0x4F00),
+                    "<" => (0x7, 0x4F00),
+                    ">=" => (0x7, 0x3F00),
+                    _ => (0x5, 0x3F00),
+                };
+                self.op(0x8F00 | x << 4 | sub)?;
+                Ok(skip)
+            }
+            _ => Err(self.error(format!("unknown comparison {}", op))),
+        }
+    }
+
+    fn expand_macro(&mut self, name: &str) -> Result<(), OctoError> {
+        let mut args = HashMap::new();
+        let names = self.macros[name].args.clone();
+        for arg in names {
+            let token = self.next()?;
+            args.insert(arg, token.tok);
+        }
+        let mac = self.macros.get_mut(name).unwrap();
+        let calls = mac.calls;
+        mac.calls += 1;
+        let body: Vec<_> = mac
+            .body
+            .iter()
+            .map(|token| {
+                let tok = match &token.tok {
+                    Tok::Word(w) if w == "CALLS" => Tok::Num(calls as f64),
+                    Tok::Word(w) => args.get(w).cloned().unwrap_or_else(|| token.tok.clone()),
+                    tok => tok.clone(),
+                };
+                Token {
+                    tok,
+                    line: token.line,
+                }
+            })
+            .collect();
+        self.splice(body)
+    }
+
+    // One copy of the mode's code per character, with CHAR, INDEX and VALUE
+    // standing for the character's code, its position in the string and its
+    // position in the mode's alphabet.
+    fn expand_string(&mut self, name: &str) -> Result<(), OctoError> {
+        let text = self.string()?;
+        let mut code = Vec::new();
+        for (index, c) in text.chars().enumerate() {
+            let Some((value, body)) = self.stringmodes[name].get(&c) else {
+                return Err(self.error(format!("stringmode {} has no {:?}", name, c)));
+            };
+            code.extend(body.iter().map(|token| {
+                let tok = match &token.tok {
+                    Tok::Word(w) if w == "CHAR" => Tok::Num(c as u32 as f64),
+                    Tok::Word(w) if w == "INDEX" => Tok::Num(index as f64),
+                    Tok::Word(w) if w == "VALUE" => Tok::Num(*value as f64),
+                    tok => tok.clone(),
+                };
+                Token {
+                    tok,
+                    line: token.line,
+                }
+            }));
+        }
+        self.splice(code)
+    }
+
+    fn splice(&mut self, code: Vec<Token>) -> Result<(), OctoError> {
+        self.expansions += 1;
+        if self.expansions > MAX_EXPANSIONS {
+            return Err(self.error("macros expand forever"));
+        }
+        for token in code.into_iter().rev() {
+            self.tokens.push_front(token);
+        }
+        Ok(())
+    }
+
+    fn define(&mut self, name: String) -> Result<(), OctoError> {
+        if self.labels.contains_key(&name) || self.consts.contains_key(&name) {
+            return Err(self.error(format!("{} is already defined", name)));
+        }
+        // A main right at the start needs no jump to it.
+        if name == "main" && self.here == START + 2 && self.rom.len() == 2 {
+            self.fixups.retain(|f| f.at != START);
+            self.rom.clear();
+            self.here = START;
+        }
+        self.labels.insert(name, self.here);
+        Ok(())
+    }
+
+    // An address operand, which may be a label defined further on. Those
+    // are filled in at the end, `offset` bytes into the instruction.
+    fn target(&mut self, kind: Use, offset: u32) -> Result<u32, OctoError> {
+        let token = self.next()?;
+        if let Tok::Word(name) = &token.tok
+            && name != "{"
+            && !self.labels.contains_key(name)
+            && !self.consts.contains_key(name)
+        {
+            self.fixups.push(Fixup {
+                name: name.clone(),
+                at: self.here + offset,
+                kind,
+                line: token.line,
+            });
+            return Ok(0);
+        }
+        self.tokens.push_front(token);
+        let value = self.value()?;
+        if !(0.0..END as f64).contains(&value) {
+            return Err(self.error(format!("address {} is out of range", value)));
+        }
+        Ok(value as u32)
+    }
+
+    fn addr12(&mut self, offset: u32) -> Result<u16, OctoError> {
+        let addr = self.target(Use::Addr12, offset)?;
+        if addr > 0xFFF {
+            return Err(self.error(format!("address 0x{:X} is out of range", addr)));
+        }
+        Ok(addr as u16)
+    }
+
+    fn fill(&mut self, at: u32, addr: u32, kind: Use) -> Result<(), OctoError> {
+        let fits = match kind {
+            Use::Addr12 | Use::Unpack(Some(_)) => addr <= 0xFFF,
+            Use::Addr16 | Use::Unpack(None) => addr < END,
+        };
+        if !fits {
+            return Err(self.error(format!("address 0x{:X} is out of range", addr)));
+        }
+        let idx = (at - START) as usize;
+        let [hi, lo] = (addr as u16).to_be_bytes();
+        match kind {
+            Use::Addr12 => {
+                self.rom[idx] = self.rom[idx] & 0xF0 | hi;
+                self.rom[idx + 1] = lo;
+            }
+            Use::Addr16 => {
+                self.rom[idx] = hi;
+                self.rom[idx + 1] = lo;
+            }
+            Use::Unpack(nibble) => {
+                self.rom[idx + 1] = nibble.map_or(hi, |n| n << 4 | hi);
+                self.rom[idx + 3] = lo;
+            }
+        }
+        Ok(())
+    }
+
+    fn byte(&mut self, byte: u8) -> Result<(), OctoError> {
+        if self.here >= END {
+            return Err(self.error("program is bigger than memory"));
+        }
+        let idx = (self.here - START) as usize;
+        if self.rom.len() <= idx {
+            self.rom.resize(idx + 1, 0);
+        }
+        self.rom[idx] = byte;
+        self.here += 1;
+        Ok(())
+    }
+
+    fn op(&mut self, op: u16) -> Result<(), OctoError> {
+        let [hi, lo] = op.to_be_bytes();
+        self.byte(hi)?;
+        self.byte(lo)
+    }
+
+    fn reg_op(&mut self, op: u16) -> Result<(), OctoError> {
+        let x = self.register()? as u16;
+        self.op(op | x << 8)
+    }
+
+    fn next(&mut self) -> Result<Token, OctoError> {
+        let token = self
+            .tokens
+            .pop_front()
+            .ok_or_else(|| self.error("program ends early"))?;
+        self.line = token.line;
+        Ok(token)
+    }
+
+    fn peek_is(&self, word: &str) -> bool {
+        self.tokens.front().is_some_and(|t| t.is(word))
+    }
+
+    fn word(&mut self) -> Result<String, OctoError> {
+        match self.next()?.tok {
+            Tok::Word(word) => Ok(word),
+            tok => Err(self.error(format!("unexpected {}", describe(&tok)))),
+        }
+    }
+
+    fn name(&mut self) -> Result<String, OctoError> {
+        let name = self.word()?;
+        if self.as_register(&name).is_some() {
+            return Err(self.error(format!("{} is a register", name)));
+        }
+        Ok(name)
+    }
+
+    fn string(&mut self) -> Result<String, OctoError> {
+        match self.next()?.tok {
+            Tok::Str(text) => Ok(text),
+            tok => Err(self.error(format!("expected a string, found {}", describe(&tok)))),
+        }
+    }
+
+    fn expect(&mut self, word: &str) -> Result<(), OctoError> {
+        let token = self.next()?;
+        if !token.is(word) {
+            return Err(self.error(format!("expected {}, found {}", word, describe(&token.tok))));
+        }
+        Ok(())
+    }
+
+    fn as_register(&self, word: &str) -> Option<u8> {
+        if let Some(&reg) = self.aliases.get(word) {
+            return Some(reg);
+        }
+        let digit = word.strip_prefix(['v', 'V'])?;
+        if digit.len() != 1 {
+            return None;
+        }
+        u8::from_str_radix(digit, 16).ok()
+    }
+
+    fn register(&mut self) -> Result<u8, OctoError> {
+        let word = self.word()?;
+        self.as_register(&word)
+            .ok_or_else(|| self.error(format!("expected a register, found {}", word)))
+    }
+
+    fn peek_register(&self) -> Option<u8> {
+        match &self.tokens.front()?.tok {
+            Tok::Word(word) => self.as_register(word),
+            _ => None,
+        }
+    }
+
+    // A number, constant, defined label or { expression }.
+    fn value(&mut self) -> Result<f64, OctoError> {
+        let token = self.next()?;
+        match token.tok {
+            Tok::Num(n) => Ok(n),
+            Tok::Word(word) if word == "{" => self.calc(),
+            Tok::Word(word) => self
+                .lookup(&word)
+                .ok_or_else(|| self.error(format!("undefined name {}", word))),
+            tok => Err(self.error(format!("expected a number, found {}", describe(&tok)))),
+        }
+    }
+
+    fn lookup(&self, name: &str) -> Option<f64> {
+        self.consts
+            .get(name)
+            .copied()
+            .or_else(|| self.labels.get(name).map(|&addr| addr as f64))
+    }
+
+    fn nibble(&mut self) -> Result<u8, OctoError> {
+        let value = self.value()?;
+        if !(0.0..16.0).contains(&value) {
+            return Err(self.error(format!("{} does not fit in 4 bits", value)));
+        }
+        Ok(value as u8)
+    }
+
+    // Bytes may be given signed.
+    fn check_byte(&self, value: f64) -> Result<u8, OctoError> {
+        if !(-128.0..256.0).contains(&value) {
+            return Err(self.error(format!("{} does not fit in a byte", value)));
+        }
+        Ok(value as i32 as u8)
+    }
+
+    // The tokens up to the } closing an opened {.
+    fn block(&mut self) -> Result<Vec<Token>, OctoError> {
+        let mut depth = 0;
+        let mut tokens = Vec::new();
+        loop {
+            let token = self.next()?;
+            if token.is("{") {
+                depth += 1;
+            } else if token.is("}") {
+                if depth == 0 {
+                    return Ok(tokens);
+                }
+                depth -= 1;
+            }
+            tokens.push(token);
+        }
+    }
+
+    // The expression after an opened {.
+    fn calc(&mut self) -> Result<f64, OctoError> {
+        let tokens = self.block()?;
+        let mut pos = 0;
+        let value = self.expr(&tokens, &mut pos)?;
+        if let Some(token) = tokens.get(pos) {
+            return Err(self.error(format!("unexpected {} in expression", describe(&token.tok))));
+        }
+        Ok(value)
+    }
+
+    fn expr(&self, tokens: &[Token], pos: &mut usize) -> Result<f64, OctoError> {
+        let left = self.term(tokens, pos)?;
+        let op = match tokens.get(*pos).map(|t| &t.tok) {
+            Some(Tok::Word(op)) if BINARY.contains(&op.as_str()) => op,
+            _ => return Ok(left),
+        };
+        *pos += 1;
+        let right = self.expr(tokens, pos)?;
+        let (a, b) = (left as i64, right as i64);
+        Ok(match op.as_str() {
+            "-" => left - right,
+            "+" => left + right,
+            "*" => left * right,
+            "/" => left / right,
+            "%" => left % right,
+            "&" => (a & b) as f64,
+            "|" => (a | b) as f64,
+            "^" => (a ^ b) as f64,
+            "<<" => a.wrapping_shl(b as u32) as f64,
+            ">>" => a.wrapping_shr(b as u32) as f64,
+            "pow" => left.powf(right),
+            "min" => left.min(right),
+            "max" => left.max(right),
+            "<" => (left < right) as u8 as f64,
+            "<=" => (left <= right) as u8 as f64,
+            "==" => (left == right) as u8 as f64,
+            "!=" => (left != right) as u8 as f64,
+            ">=" => (left >= right) as u8 as f64,
+            _ => (left > right) as u8 as f64,
+        })
+    }
+
+    fn term(&self, tokens: &[Token], pos: &mut usize) -> Result<f64, OctoError> {
+        let token = tokens
+            .get(*pos)
+            .ok_or_else(|| self.error("expression ends early"))?;
+        *pos += 1;
+        let word = match &token.tok {
+            Tok::Num(n) => return Ok(*n),
+            Tok::Str(_) => return Err(self.error("unexpected string in expression")),
+            Tok::Word(word) => word.as_str(),
+        };
+        let mut unary =
+            |f: fn(f64) -> f64| -> Result<f64, OctoError> { Ok(f(self.term(tokens, pos)?)) };
+        match word {
+            "(" => {
+                let value = self.expr(tokens, pos)?;
+                if !tokens.get(*pos).is_some_and(|t| t.is(")")) {
+                    return Err(self.error("missing ) in expression"));
+                }
+                *pos += 1;
+                Ok(value)
+            }
+            "-" => unary(|v| -v),
+            "~" => unary(|v| !(v as i64) as f64),
+            "!" => unary(|v| (v == 0.0) as u8 as f64),
+            "sin" => unary(f64::sin),
+            "cos" => unary(f64::cos),
+            "tan" => unary(f64::tan),
+            "exp" => unary(f64::exp),
+            "log" => unary(f64::ln),
+            "abs" => unary(f64::abs),
+            "sqrt" => unary(f64::sqrt),
+            "sign" => unary(f64::signum),
+            "ceil" => unary(f64::ceil),
+            "floor" => unary(f64::floor),
+            // The byte assembled so far at an address.
+            "@" => {
+                let addr = self.term(tokens, pos)?;
+                let idx = (addr as i64 - START as i64).max(0) as usize;
+                Ok(self.rom.get(idx).copied().unwrap_or(0) as f64)
+            }
+            "strlen" => match tokens.get(*pos).map(|t| &t.tok) {
+                Some(Tok::Str(text)) => {
+                    *pos += 1;
+                    Ok(text.chars().count() as f64)
+                }
+                _ => Err(self.error("strlen takes a string")),
+            },
+            "PI" => Ok(std::f64::consts::PI),
+            "E" => Ok(std::f64::consts::E),
+            "HERE" => Ok(self.here as f64),
+            name => self
+                .lookup(name)
+                .ok_or_else(|| self.error(format!("undefined name {}", name))),
+        }
+    }
+
+    fn error(&self, msg: impl Into<String>) -> OctoError {
+        OctoError {
+            line: self.line,
+            msg: msg.into(),
+        }
+    }
+
+    fn not_emulated(&self, what: &str, platform: &str) -> OctoError {
+        self.error(format!("{} is {}, which isn't emulated", what, platform))
+    }
+}
+
+// The skip for the opposite condition.
+fn negate(skip: u16) -> u16 {
+    match skip >> 12 {
+        0x3 => skip + 0x1000,
+        0x4 => skip - 0x1000,
+        0x5 => skip + 0x4000,
+        0x9 => skip - 0x4000,
+        // EX9E and EXA1.
+        _ => skip ^ (0x9E ^ 0xA1),
+    }
+}
+
+fn describe(tok: &Tok) -> String {
+    match tok {
+        Tok::Word(word) => word.clone(),
+        Tok::Num(n) => n.to_string(),
+        Tok::Str(text) => format!("{:?}", text),
+    }
+}
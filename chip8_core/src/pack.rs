@@ -0,0 +1,154 @@
+// ROM packs: zip archives of .ch8, .sc8, .xo8 and the like, the way ROM
+// collections are shipped. Whatever else is in the archive, like readmes or
+// the resource forks macOS adds, is left out.
+use crate::{Platform, sha1_hex};
+use std::{
+    fmt,
+    io::{Cursor, Read},
+};
+
+// MegaChip8's 16M of memory; anything bigger can't be a ROM.
+const MAX_ENTRY_SIZE: u64 = 1 << 24;
+// All the ROMs together, so a pack of many big entries can't exhaust memory.
+const MAX_PACK_SIZE: u64 = 1 << 26;
+
+#[derive(Debug)]
+pub enum PackError {
+    // Not a zip archive, or a broken one.
+    Zip(String),
+    // An entry, or with an empty name the whole pack, unpacks to too much.
+    TooLarge { name: String, size: u64 },
+}
+
+impl fmt::Display for PackError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PackError::Zip(e) => write!(f, "bad zip archive: {}", e),
+            PackError::TooLarge { name, .. } if name.is_empty() => {
+                write!(f, "pack unpacks to over {} bytes", MAX_PACK_SIZE)
+            }
+            PackError::TooLarge { name, size } => {
+                write!(f, "{} is {} bytes, too big for a ROM", name, size)
+            }
+        }
+    }
+}
+
+impl std::error::Error for PackError {}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PackedRom {
+    // Its path in the archive.
+    pub name: String,
+    pub data: Vec<u8>,
+}
+
+impl PackedRom {
+    pub fn sha1(&self) -> String {
+        sha1_hex(&self.data)
+    }
+
+    // The platform its extension names, if any.
+    pub fn platform(&self) -> Option<Platform> {
+        Platform::from_file_name(&self.name)
+    }
+
+    // The name without the directories it is in.
+    pub fn file_name(&self) -> &str {
+        self.name.rsplit('/').next().unwrap_or(&self.name)
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RomPack {
+    // In archive order.
+    pub roms: Vec<PackedRom>,
+}
+
+impl RomPack {
+    pub fn detect(data: &[u8]) -> bool {
+        // A local file header, or the end record of an empty archive.
+        data.starts_with(b"PK\x03\x04") || data.starts_with(b"PK\x05\x06")
+    }
+
+    pub fn open(zip: &[u8]) -> Result<RomPack, PackError> {
+        let bad_zip = |e: &dyn fmt::Display| PackError::Zip(e.to_string());
+        let mut archive = zip::ZipArchive::new(Cursor::new(zip)).map_err(|e| bad_zip(&e))?;
+        let mut roms = Vec::new();
+        let mut total = 0;
+        for idx in 0..archive.len() {
+            let file = archive.by_index(idx).map_err(|e| bad_zip(&e))?;
+            let name = file.name().to_string();
+            if !file.is_file() || !is_rom(&name) {
+                continue;
+            }
+            let too_large = |size| PackError::TooLarge {
+                name: name.clone(),
+                size,
+            };
+            if file.size() > MAX_ENTRY_SIZE {
+                return Err(too_large(file.size()));
+            }
+            // The size in the archive may be lying.
+            let limit = MAX_ENTRY_SIZE.min(MAX_PACK_SIZE - total);
+            let mut data = Vec::new();
+            file.take(limit + 1)
+                .read_to_end(&mut data)
+                .map_err(|e| bad_zip(&e))?;
+            if data.len() as u64 > MAX_ENTRY_SIZE {
+                return Err(too_large(data.len() as u64));
+            }
+            total += data.len() as u64;
+            if total > MAX_PACK_SIZE {
+                return Err(PackError::TooLarge {
+                    name: String::new(),
+                    size: total,
+                });
+            }
+            roms.push(PackedRom { name, data });
+        }
+        Ok(RomPack { roms })
+    }
+
+    // The ROM at an index of `roms`, or by name: its path in the archive,
+    // or its file name with or without the extension, ignoring case.
+    pub fn find(&self, which: &str) -> Option<&PackedRom> {
+        if let Ok(idx) = which.parse::<usize>() {
+            return self.roms.get(idx);
+        }
+        self.roms.iter().find(|rom| rom.name == which).or_else(|| {
+            self.roms.iter().find(|rom| {
+                let file_name = rom.file_name();
+                let stem = file_name
+                    .rsplit_once('.')
+                    .map_or(file_name, |(stem, _)| stem);
+                file_name.eq_ignore_ascii_case(which) || stem.eq_ignore_ascii_case(which)
+            })
+        })
+    }
+}
+
+fn is_rom(name: &str) -> bool {
+    if name.starts_with("__MACOSX/") {
+        return false;
+    }
+    let ext = name.rsplit_once('.').map_or("", |(_, ext)| ext);
+    ext.eq_ignore_ascii_case("ch8") || Platform::from_extension(ext).is_some()
+}
+
+// One line per ROM: its index, name, size and SHA-1.
+impl fmt::Display for RomPack {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (idx, rom) in self.roms.iter().enumerate() {
+            writeln!(
+                f,
+                "{:3}  {}  {} bytes  {}",
+                idx,
+                rom.name,
+                rom.data.len(),
+                rom.sha1()
+            )?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,277 @@
+// IPS and BPS patches, the formats fan translations, fixes and hacks are
+// shared in. The format is told by the patch's magic; BPS patches carry
+// CRC32s of the source, the result and themselves, which are all checked.
+use alloc::vec::Vec;
+use core::fmt;
+
+// MegaChip8's 16M of memory, the most any platform has.
+const MAX_TARGET_SIZE: usize = 1 << 24;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PatchFormat {
+    Ips,
+    Bps,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PatchError {
+    // Neither an IPS nor a BPS patch.
+    UnknownFormat,
+    // The patch ends in the middle of a record.
+    Truncated,
+    // A BPS copy reaches outside the source or what was built so far, or
+    // the result doesn't come out at the size the patch promises.
+    BadCopy,
+    // A BPS patch making a ROM bigger than any platform's memory.
+    TooLarge { size: usize },
+    // The patch is for a ROM of another size.
+    SourceSize { expected: usize, actual: usize },
+    SourceChecksum { expected: u32, actual: u32 },
+    TargetChecksum { expected: u32, actual: u32 },
+    PatchChecksum { expected: u32, actual: u32 },
+}
+
+impl fmt::Display for PatchError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PatchError::UnknownFormat => write!(f, "not an IPS or BPS patch"),
+            PatchError::Truncated => write!(f, "patch is truncated"),
+            PatchError::BadCopy => write!(f, "patch copies from outside the ROM"),
+            PatchError::TooLarge { size } => {
+                write!(f, "patched ROM would be {} bytes", size)
+            }
+            PatchError::SourceSize { expected, actual } => write!(
+                f,
+                "patch is for a {} byte ROM, this one is {} bytes",
+                expected, actual
+            ),
+            PatchError::SourceChecksum { expected, actual } => write!(
+                f,
+                "patch is for another ROM (CRC32 {:08X}, this one is {:08X})",
+                expected, actual
+            ),
+            PatchError::TargetChecksum { expected, actual } => write!(
+                f,
+                "patched ROM has CRC32 {:08X} instead of {:08X}",
+                actual, expected
+            ),
+            PatchError::PatchChecksum { expected, actual } => write!(
+                f,
+                "patch is damaged (CRC32 {:08X} instead of {:08X})",
+                actual, expected
+            ),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for PatchError {}
+
+impl PatchFormat {
+    pub fn detect(patch: &[u8]) -> Option<PatchFormat> {
+        if patch.starts_with(b"PATCH") {
+            Some(PatchFormat::Ips)
+        } else if patch.starts_with(b"BPS1") {
+            Some(PatchFormat::Bps)
+        } else {
+            None
+        }
+    }
+}
+
+// The ROM `patch` makes out of `rom`.
+pub fn apply_patch(rom: &[u8], patch: &[u8]) -> Result<Vec<u8>, PatchError> {
+    match PatchFormat::detect(patch) {
+        Some(PatchFormat::Ips) => apply_ips(rom, patch),
+        Some(PatchFormat::Bps) => apply_bps(rom, patch),
+        None => Err(PatchError::UnknownFormat),
+    }
+}
+
+struct Reader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn bytes(&mut self, len: usize) -> Result<&'a [u8], PatchError> {
+        let bytes = self
+            .data
+            .get(self.pos..self.pos.saturating_add(len))
+            .ok_or(PatchError::Truncated)?;
+        self.pos += len;
+        Ok(bytes)
+    }
+
+    // Big-endian, as IPS stores its numbers.
+    fn number(&mut self, len: usize) -> Result<usize, PatchError> {
+        Ok(self
+            .bytes(len)?
+            .iter()
+            .fold(0, |acc, b| acc << 8 | *b as usize))
+    }
+
+    // BPS's variable-length numbers, 7 bits a byte, lowest first.
+    fn varint(&mut self) -> Result<usize, PatchError> {
+        let mut value: usize = 0;
+        let mut shift: usize = 1;
+        loop {
+            let byte = self.bytes(1)?[0] as usize;
+            value = (byte & 0x7F)
+                .checked_mul(shift)
+                .and_then(|v| v.checked_add(value))
+                .ok_or(PatchError::Truncated)?;
+            if byte & 0x80 != 0 {
+                return Ok(value);
+            }
+            shift = shift.checked_shl(7).ok_or(PatchError::Truncated)?;
+            value = value.checked_add(shift).ok_or(PatchError::Truncated)?;
+        }
+    }
+}
+
+// Records of a 3 byte offset and 2 byte size, then the bytes; a size of 0
+// is a run of one byte instead. "EOF" ends the records, and may be followed
+// by the length to cut the result to.
+fn apply_ips(rom: &[u8], patch: &[u8]) -> Result<Vec<u8>, PatchError> {
+    let mut out = rom.to_vec();
+    let mut reader = Reader {
+        data: patch,
+        pos: 5,
+    };
+    loop {
+        if reader.bytes(3)? == b"EOF" {
+            break;
+        }
+        reader.pos -= 3;
+        let offset = reader.number(3)?;
+        let bytes = match reader.number(2)? {
+            0 => {
+                let count = reader.number(2)?;
+                let value = reader.bytes(1)?[0];
+                alloc::vec![value; count]
+            }
+            size => reader.bytes(size)?.to_vec(),
+        };
+        if out.len() < offset + bytes.len() {
+            out.resize(offset + bytes.len(), 0);
+        }
+        out[offset..offset + bytes.len()].copy_from_slice(&bytes);
+    }
+    if let Ok(len) = reader.number(3) {
+        out.truncate(len);
+    }
+    Ok(out)
+}
+
+fn apply_bps(rom: &[u8], patch: &[u8]) -> Result<Vec<u8>, PatchError> {
+    let footer = patch.len().checked_sub(12).ok_or(PatchError::Truncated)?;
+    let crc = |at: usize| u32::from_le_bytes(patch[at..at + 4].try_into().unwrap());
+    let (source_crc, target_crc, patch_crc) = (crc(footer), crc(footer + 4), crc(footer + 8));
+    let actual = crc32(&patch[..footer + 8]);
+    if actual != patch_crc {
+        return Err(PatchError::PatchChecksum {
+            expected: patch_crc,
+            actual,
+        });
+    }
+
+    let mut reader = Reader {
+        data: &patch[..footer],
+        pos: 4,
+    };
+    let source_size = reader.varint()?;
+    let target_size = reader.varint()?;
+    let metadata = reader.varint()?;
+    reader.bytes(metadata)?;
+    if source_size != rom.len() {
+        return Err(PatchError::SourceSize {
+            expected: source_size,
+            actual: rom.len(),
+        });
+    }
+    let actual = crc32(rom);
+    if actual != source_crc {
+        return Err(PatchError::SourceChecksum {
+            expected: source_crc,
+            actual,
+        });
+    }
+
+    if target_size > MAX_TARGET_SIZE {
+        return Err(PatchError::TooLarge { size: target_size });
+    }
+    let mut out: Vec<u8> = Vec::with_capacity(target_size);
+    let (mut source_pos, mut target_pos) = (0usize, 0usize);
+    while reader.pos < footer {
+        let data = reader.varint()?;
+        let len = (data >> 2) + 1;
+        if out.len().saturating_add(len) > target_size {
+            return Err(PatchError::BadCopy);
+        }
+        match data & 3 {
+            // SourceRead: the source's bytes at the same offset.
+            0 => {
+                let bytes = rom
+                    .get(out.len()..out.len() + len)
+                    .ok_or(PatchError::BadCopy)?;
+                out.extend_from_slice(bytes);
+            }
+            // TargetRead: bytes from the patch.
+            1 => out.extend_from_slice(reader.bytes(len)?),
+            // SourceCopy: from anywhere in the source.
+            2 => {
+                source_pos = relative(source_pos, reader.varint()?)?;
+                let bytes = rom
+                    .get(source_pos..source_pos.saturating_add(len))
+                    .ok_or(PatchError::BadCopy)?;
+                out.extend_from_slice(bytes);
+                source_pos += len;
+            }
+            // TargetCopy: from what was built so far, a byte at a time as
+            // the copy may overlap itself.
+            _ => {
+                target_pos = relative(target_pos, reader.varint()?)?;
+                if target_pos >= out.len() {
+                    return Err(PatchError::BadCopy);
+                }
+                for _ in 0..len {
+                    out.push(out[target_pos]);
+                    target_pos += 1;
+                }
+            }
+        }
+    }
+    if out.len() != target_size {
+        return Err(PatchError::BadCopy);
+    }
+    let actual = crc32(&out);
+    if actual != target_crc {
+        return Err(PatchError::TargetChecksum {
+            expected: target_crc,
+            actual,
+        });
+    }
+    Ok(out)
+}
+
+// Moves `pos` by a BPS offset: the magnitude shifted up by one, with the
+// sign in the low bit.
+fn relative(pos: usize, offset: usize) -> Result<usize, PatchError> {
+    let by = offset >> 1;
+    if offset & 1 != 0 {
+        pos.checked_sub(by)
+    } else {
+        pos.checked_add(by)
+    }
+    .ok_or(PatchError::BadCopy)
+}
+
+// The CRC32 of zlib and PNG.
+fn crc32(data: &[u8]) -> u32 {
+    !data.iter().fold(!0u32, |crc, byte| {
+        (0..8).fold(crc ^ *byte as u32, |crc, _| {
+            (crc >> 1) ^ (0xEDB8_8320 & (crc & 1).wrapping_neg())
+        })
+    })
+}
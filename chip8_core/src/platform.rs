@@ -0,0 +1,259 @@
+#[cfg(feature = "alloc")]
+use crate::{Emu, Machine};
+use crate::{FONTSET, FONTSET_SIZE, Quirks, RAM_SIZE, SCREEN_H, SCREEN_W};
+
+// CHIP-8 variants, named after the platform ids of the community
+// chip-8-database (platforms.json) where it has one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Platform {
+    // COSMAC VIP interpreter.
+    OriginalChip8,
+    // COSMAC VIP with the common hybrid extensions.
+    HybridVip,
+    // What most modern interpreters implement.
+    ModernChip8,
+    Chip8X,
+    Chip48,
+    SuperChip1,
+    SuperChip,
+    MegaChip8,
+    XoChip,
+    // ETI-660 hobby computer, whose programs start at 0x600.
+    Eti660,
+    // COSMAC VIP running the CHIP-8 HIRES interpreter: a 64x64 screen over
+    // two display pages.
+    Chip8Hires,
+    // DREAM 6800 running CHIPOS: its own font and keypad, and 50 Hz timers.
+    Dream6800,
+}
+
+impl Platform {
+    pub const ALL: [Platform; 12] = [
+        Platform::OriginalChip8,
+        Platform::HybridVip,
+        Platform::ModernChip8,
+        Platform::Chip8X,
+        Platform::Chip48,
+        Platform::SuperChip1,
+        Platform::SuperChip,
+        Platform::MegaChip8,
+        Platform::XoChip,
+        Platform::Eti660,
+        Platform::Chip8Hires,
+        Platform::Dream6800,
+    ];
+
+    pub fn id(self) -> &'static str {
+        match self {
+            Platform::OriginalChip8 => "originalChip8",
+            Platform::HybridVip => "hybridVIP",
+            Platform::ModernChip8 => "modernChip8",
+            Platform::Chip8X => "chip8x",
+            Platform::Chip48 => "chip48",
+            Platform::SuperChip1 => "superchip1",
+            Platform::SuperChip => "superchip",
+            Platform::MegaChip8 => "megachip8",
+            Platform::XoChip => "xochip",
+            Platform::Eti660 => "eti660",
+            Platform::Chip8Hires => "chip8hires",
+            Platform::Dream6800 => "dream6800",
+        }
+    }
+
+    pub fn from_id(id: &str) -> Option<Platform> {
+        Platform::ALL.into_iter().find(|p| p.id() == id)
+    }
+
+    // The platform a ROM's file extension names, ignoring case. Plain .ch8
+    // only says CHIP-8 of some kind, so it names none.
+    pub fn from_extension(ext: &str) -> Option<Platform> {
+        const EXTENSIONS: [(&str, Platform); 4] = [
+            ("sc8", Platform::SuperChip),
+            ("xo8", Platform::XoChip),
+            ("mc8", Platform::MegaChip8),
+            ("c8x", Platform::Chip8X),
+        ];
+        EXTENSIONS
+            .into_iter()
+            .find(|(known, _)| known.eq_ignore_ascii_case(ext))
+            .map(|(_, platform)| platform)
+    }
+
+    // The platform named by the extension of a file like games/ant.sc8.
+    pub fn from_file_name(name: &str) -> Option<Platform> {
+        let (_, ext) = name.rsplit_once('.')?;
+        Platform::from_extension(ext)
+    }
+
+    // The platform's quirks as listed in the database. MEMORY_INCREMENT_BY_X
+    // (CHIP-48) has no separate flag here and counts as incrementing.
+    pub fn quirks(self) -> Quirks {
+        match self {
+            Platform::OriginalChip8
+            | Platform::HybridVip
+            | Platform::Chip8X
+            | Platform::Eti660
+            | Platform::Chip8Hires
+            | Platform::Dream6800 => Quirks::CHIP8,
+            Platform::ModernChip8 => Quirks {
+                vf_reset: false,
+                memory_increment: true,
+                shift_vx: false,
+                jump_vx: false,
+                clip_sprites: true,
+            },
+            Platform::Chip48 => Quirks {
+                memory_increment: true,
+                ..Quirks::SCHIP
+            },
+            Platform::SuperChip1 | Platform::SuperChip | Platform::MegaChip8 => Quirks::SCHIP,
+            Platform::XoChip => Quirks {
+                vf_reset: false,
+                memory_increment: true,
+                shift_vx: false,
+                jump_vx: false,
+                clip_sprites: false,
+            },
+        }
+    }
+
+    // Instructions per 60 Hz frame the database recommends by default.
+    pub fn tickrate(self) -> u32 {
+        match self {
+            Platform::OriginalChip8
+            | Platform::HybridVip
+            | Platform::Chip8X
+            | Platform::Eti660
+            | Platform::Chip8Hires
+            | Platform::Dream6800 => 15,
+            Platform::ModernChip8 => 12,
+            Platform::Chip48 | Platform::SuperChip1 | Platform::SuperChip => 30,
+            Platform::MegaChip8 => 1000,
+            Platform::XoChip => 100,
+        }
+    }
+
+    // A machine for this platform with its quirks, ready for load().
+    // `random` supplies CXNN's random bytes, as for Emu::new.
+    #[cfg(feature = "alloc")]
+    pub fn machine(
+        self,
+        random: impl FnMut() -> u8 + Clone + Send + Sync + 'static,
+    ) -> alloc::boxed::Box<dyn Machine> {
+        let mut emu = Emu::new(random);
+        emu.set_platform(self);
+        emu.set_quirks(self.quirks());
+        alloc::boxed::Box::new(emu)
+    }
+
+    // The platform `classify` thinks most likely, unless that is just the
+    // default of ModernChip8 or a platform Emu can't run.
+    pub fn detect(rom: &[u8]) -> Option<Platform> {
+        let [best, ..] = Platform::classify(rom);
+        (best.platform != Platform::ModernChip8 && best.platform.emulated())
+            .then_some(best.platform)
+    }
+
+    // Whether Emu runs the platform's own opcodes. SUPER-CHIP's and
+    // XO-CHIP's aren't implemented; CHIP-48 only differs in its quirks.
+    pub fn emulated(self) -> bool {
+        let schip = matches!(
+            self,
+            Platform::SuperChip1 | Platform::SuperChip | Platform::XoChip
+        );
+        !schip && (self != Platform::MegaChip8 || cfg!(feature = "megachip"))
+    }
+
+    // Where programs are loaded.
+    pub fn load_addr(self) -> u16 {
+        match self {
+            Platform::Eti660 => 0x600,
+            // The CHIP-8X interpreter is a page longer.
+            Platform::Chip8X => 0x300,
+            _ => 0x200,
+        }
+    }
+
+    // Where programs start running. CHIP-8 HIRES programs begin with a
+    // header that jumps into the interpreter's setup code, which is emulated
+    // by starting right after it.
+    pub fn entry(self) -> u16 {
+        match self {
+            Platform::Chip8Hires => 0x244,
+            _ => self.load_addr(),
+        }
+    }
+
+    // Display width and height in pixels.
+    pub fn screen_size(self) -> (usize, usize) {
+        match self {
+            Platform::Chip8Hires => (64, 64),
+            _ => (SCREEN_W, SCREEN_H),
+        }
+    }
+
+    // Bytes of memory the program can use, counted from address 0.
+    pub fn memory_size(self) -> usize {
+        match self {
+            #[cfg(feature = "megachip")]
+            Platform::MegaChip8 => crate::MEGA_RAM_SIZE,
+            _ => RAM_SIZE,
+        }
+    }
+
+    // The hex digits FX29 points at.
+    pub fn font(self) -> &'static [u8; FONTSET_SIZE] {
+        match self {
+            Platform::Dream6800 => &DREAM6800_FONT,
+            _ => &FONTSET,
+        }
+    }
+
+    // The hex keypad's keys by position, top row first. Frontends map their
+    // own 4x4 block of keys onto it.
+    pub fn keypad(self) -> [[u8; 4]; 4] {
+        match self {
+            Platform::Dream6800 => [
+                [0xC, 0xD, 0xE, 0xF],
+                [0x8, 0x9, 0xA, 0xB],
+                [0x4, 0x5, 0x6, 0x7],
+                [0x0, 0x1, 0x2, 0x3],
+            ],
+            _ => [
+                [0x1, 0x2, 0x3, 0xC],
+                [0x4, 0x5, 0x6, 0xD],
+                [0x7, 0x8, 0x9, 0xE],
+                [0xA, 0x0, 0xB, 0xF],
+            ],
+        }
+    }
+
+    // How often the delay and sound timers count down. The DREAM 6800 took
+    // its interrupt from the 50 Hz Australian mains.
+    pub fn timer_hz(self) -> u32 {
+        match self {
+            Platform::Dream6800 => 50,
+            _ => 60,
+        }
+    }
+}
+
+// CHIPOS's digits are three pixels wide.
+const DREAM6800_FONT: [u8; FONTSET_SIZE] = [
+    0xE0, 0xA0, 0xA0, 0xA0, 0xE0, // 0
+    0x40, 0x40, 0x40, 0x40, 0x40, // 1
+    0xE0, 0x20, 0xE0, 0x80, 0xE0, // 2
+    0xE0, 0x20, 0xE0, 0x20, 0xE0, // 3
+    0x80, 0xA0, 0xA0, 0xE0, 0x20, // 4
+    0xE0, 0x80, 0xE0, 0x20, 0xE0, // 5
+    0xE0, 0x80, 0xE0, 0xA0, 0xE0, // 6
+    0xE0, 0x20, 0x20, 0x20, 0x20, // 7
+    0xE0, 0xA0, 0xE0, 0xA0, 0xE0, // 8
+    0xE0, 0xA0, 0xE0, 0x20, 0xE0, // 9
+    0xE0, 0xA0, 0xE0, 0xA0, 0xA0, // A
+    0xC0, 0xA0, 0xE0, 0xA0, 0xC0, // B
+    0xE0, 0x80, 0x80, 0x80, 0xE0, // C
+    0xC0, 0xA0, 0xA0, 0xA0, 0xC0, // D
+    0xE0, 0x80, 0xE0, 0x80, 0xE0, // E
+    0xE0, 0x80, 0xC0, 0x80, 0x80, // F
+];
@@ -0,0 +1,48 @@
+// Behaviours that differ between CHIP-8 interpreters.
+// See https://github.com/Timendus/chip8-test-suite#quirks-test for the details.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Quirks {
+    // 8XY1, 8XY2 and 8XY3 reset VF to 0.
+    pub vf_reset: bool,
+    // FX55 and FX65 leave I pointing past the last register (I += X + 1).
+    pub memory_increment: bool,
+    // 8XY6 and 8XYE shift VX in place instead of shifting VY into VX.
+    pub shift_vx: bool,
+    // BNNN jumps to VX + NNN (read as BXNN) instead of V0 + NNN.
+    pub jump_vx: bool,
+    // Sprites are clipped at the screen edges instead of wrapping around.
+    pub clip_sprites: bool,
+}
+
+impl Quirks {
+    // The COSMAC VIP interpreter.
+    pub const CHIP8: Quirks = Quirks {
+        vf_reset: true,
+        memory_increment: true,
+        shift_vx: false,
+        jump_vx: false,
+        clip_sprites: true,
+    };
+    // SUPER-CHIP 1.1 on the HP 48.
+    pub const SCHIP: Quirks = Quirks {
+        vf_reset: false,
+        memory_increment: false,
+        shift_vx: true,
+        jump_vx: true,
+        clip_sprites: true,
+    };
+    // What this emulator has always done, and still does by default.
+    pub const MODERN: Quirks = Quirks {
+        vf_reset: false,
+        memory_increment: false,
+        shift_vx: true,
+        jump_vx: false,
+        clip_sprites: false,
+    };
+}
+
+impl Default for Quirks {
+    fn default() -> Self {
+        Quirks::MODERN
+    }
+}
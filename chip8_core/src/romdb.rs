@@ -0,0 +1,156 @@
+// Per-game settings from the community chip-8-database
+// (https://github.com/chip-8/chip-8-database), matched on the SHA-1 of the
+// ROM image. Only programs.json is needed; platform defaults come from
+// Platform.
+use crate::{Platform, Quirks};
+use serde::Deserialize;
+use std::collections::HashMap;
+
+#[derive(Debug)]
+pub struct RomDbError(String);
+
+impl std::fmt::Display for RomDbError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "invalid ROM database: {}", self.0)
+    }
+}
+
+impl std::error::Error for RomDbError {}
+
+// What the database recommends for one ROM.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RomInfo {
+    pub title: String,
+    pub platform: Platform,
+    pub quirks: Quirks,
+    // Instructions per 60 Hz frame.
+    pub tickrate: u32,
+    // Background then foreground colour(s); empty when the ROM has none.
+    pub palette: Vec<[u8; 3]>,
+    // Buttons like "up" or "a" and the CHIP-8 key each one maps to.
+    pub keys: Vec<(String, u8)>,
+}
+
+pub struct RomDb {
+    // Keyed by lowercase hex SHA-1.
+    roms: HashMap<String, RomInfo>,
+}
+
+impl RomDb {
+    // Parses the database's programs.json.
+    pub fn from_json(json: &str) -> Result<RomDb, RomDbError> {
+        let programs: Vec<Program> =
+            serde_json::from_str(json).map_err(|e| RomDbError(e.to_string()))?;
+        let mut roms = HashMap::new();
+        for program in programs {
+            for (hash, rom) in program.roms {
+                roms.insert(hash.to_ascii_lowercase(), rom.info(&program.title));
+            }
+        }
+        Ok(RomDb { roms })
+    }
+
+    pub fn len(&self) -> usize {
+        self.roms.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.roms.is_empty()
+    }
+
+    pub fn lookup(&self, rom: &[u8]) -> Option<&RomInfo> {
+        self.roms.get(&sha1_hex(rom))
+    }
+}
+
+pub fn sha1_hex(rom: &[u8]) -> String {
+    sha1_smol::Sha1::from(rom).digest().to_string()
+}
+
+#[derive(Deserialize)]
+struct Program {
+    title: String,
+    roms: HashMap<String, Rom>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Rom {
+    // Most suitable first.
+    #[serde(default)]
+    platforms: Vec<String>,
+    tickrate: Option<u32>,
+    // Per platform, quirks that differ from the platform's defaults.
+    #[serde(default)]
+    quirky_platforms: HashMap<String, HashMap<String, bool>>,
+    #[serde(default)]
+    colors: Colors,
+    #[serde(default)]
+    keys: HashMap<String, u8>,
+}
+
+#[derive(Deserialize, Default)]
+struct Colors {
+    #[serde(default)]
+    pixels: Vec<String>,
+}
+
+impl Rom {
+    fn info(self, title: &str) -> RomInfo {
+        // Platforms this emulator doesn't know are skipped, and one it can
+        // run is preferred over the first listed.
+        let known: Vec<_> = self
+            .platforms
+            .iter()
+            .filter_map(|id| Some((id.as_str(), Platform::from_id(id)?)))
+            .collect();
+        let (id, platform) = known
+            .iter()
+            .find(|(_, platform)| platform.emulated())
+            .or(known.first())
+            .copied()
+            .unwrap_or(("", Platform::OriginalChip8));
+        let mut quirks = platform.quirks();
+        for (name, &on) in self.quirky_platforms.get(id).into_iter().flatten() {
+            apply_quirk(&mut quirks, name, on);
+        }
+        let mut keys: Vec<_> = self.keys.into_iter().collect();
+        keys.sort();
+        RomInfo {
+            title: title.to_string(),
+            platform,
+            quirks,
+            tickrate: self.tickrate.unwrap_or_else(|| platform.tickrate()),
+            palette: self
+                .colors
+                .pixels
+                .iter()
+                .filter_map(|c| parse_color(c))
+                .collect(),
+            keys,
+        }
+    }
+}
+
+// Quirk names from the database's quirks.json. memoryIncrementByX and vblank
+// aren't emulated and are ignored.
+fn apply_quirk(quirks: &mut Quirks, name: &str, on: bool) {
+    match name {
+        "shift" => quirks.shift_vx = on,
+        "memoryLeaveIUnchanged" => quirks.memory_increment = !on,
+        "wrap" => quirks.clip_sprites = !on,
+        "jump" => quirks.jump_vx = on,
+        "logic" => quirks.vf_reset = on,
+        _ => {}
+    }
+}
+
+// "#rrggbb"
+pub(crate) fn parse_color(s: &str) -> Option<[u8; 3]> {
+    let hex = s.strip_prefix('#')?;
+    if hex.len() != 6 {
+        return None;
+    }
+    let channel = |i: usize| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok();
+    Some([channel(0)?, channel(2)?, channel(4)?])
+}
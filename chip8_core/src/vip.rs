@@ -0,0 +1,103 @@
+// 0NNN on the COSMAC VIP: machine code subroutines, run on an emulated 1802.
+// While one runs, memory is laid out the way the 4K VIP interpreter keeps it,
+// and the registers hold what the interpreter leaves in them.
+use crate::{Emu, EmuError, NUM_REGISTERS, SCREEN_H, SCREEN_W};
+use cdp1802::{Bus, Cpu};
+
+// Where the interpreter keeps V0-VF and the display page.
+const V_ADDR: u16 = 0xEF0;
+const DISPLAY_ADDR: u16 = 0xF00;
+// Top of the 1802 stack, which grows down.
+const STACK_ADDR: u16 = 0xECF;
+// Subroutines return to the interpreter with SEP R4.
+const RETURN_P: u8 = 4;
+// 1802 instructions a subroutine may run before it counts as hung.
+const STEP_LIMIT: u32 = 1_000_000;
+
+struct VipBus<'a> {
+    emu: &'a mut Emu,
+    // Key selected by OUT 2, which EF3 then reports.
+    key: u8,
+}
+
+impl Bus for VipBus<'_> {
+    fn read(&mut self, addr: u16) -> u8 {
+        self.emu.read(addr)
+    }
+    fn write(&mut self, addr: u16, val: u8) {
+        self.emu.write(addr, val);
+    }
+    fn output(&mut self, port: u8, val: u8) {
+        if port == 2 {
+            self.key = val & 0xF;
+        }
+    }
+    fn flag(&mut self, ef: u8) -> bool {
+        ef == 3 && self.emu.keys[self.key as usize]
+    }
+}
+
+impl Emu {
+    // Runs the subroutine at `addr` until it hands control back with SEP R4.
+    // There are no interrupts, so IDL doesn't wait, and timers don't run
+    // while it is busy.
+    pub(crate) fn call_machine_code(&mut self, addr: u16) -> Result<(), EmuError> {
+        let pc = self.op_addr();
+        let (x, y) = ((addr >> 8) & 0xF, (addr >> 4) & 0xF);
+        self.store_vip_state();
+
+        let mut cpu = Cpu::new();
+        cpu.p = 3;
+        cpu.x = 2;
+        cpu.r[2] = STACK_ADDR;
+        cpu.r[3] = addr;
+        cpu.r[5] = self.pc;
+        // The VX and VY pointers of the 0NNN opcode itself.
+        cpu.r[6] = V_ADDR + x;
+        cpu.r[7] = V_ADDR + y;
+        cpu.r[8] = u16::from_be_bytes([self.dt, self.st]);
+        cpu.r[0xA] = self.i_reg as u16;
+        cpu.r[0xB] = DISPLAY_ADDR;
+
+        let mut bus = VipBus { emu: self, key: 0 };
+        let mut steps = 0;
+        while cpu.p != RETURN_P {
+            if steps == STEP_LIMIT {
+                return Err(EmuError::MachineCodeTimeout { addr, pc });
+            }
+            cpu.step(&mut bus);
+            cpu.idle = false;
+            steps += 1;
+        }
+
+        self.load_vip_state();
+        self.set_pc_wrapped(cpu.r[5]);
+        self.i_reg = cpu.r[0xA] as u32;
+        [self.dt, self.st] = cpu.r[8].to_be_bytes();
+        Ok(())
+    }
+
+    // Puts V0-VF and the screen where the interpreter would have them.
+    fn store_vip_state(&mut self) {
+        for (idx, v) in self.v_reg.into_iter().enumerate() {
+            self.write(V_ADDR + idx as u16, v);
+        }
+        for (idx, byte) in (DISPLAY_ADDR..).zip(0..SCREEN_W * SCREEN_H / 8) {
+            let bits = (0..8).fold(0, |acc, bit| acc << 1 | self.screen[byte * 8 + bit] as u8);
+            self.write(idx, bits);
+        }
+    }
+
+    // Reads back what the subroutine left in the V registers and the display.
+    fn load_vip_state(&mut self) {
+        for idx in 0..NUM_REGISTERS {
+            self.v_reg[idx] = self.read(V_ADDR + idx as u16);
+        }
+        for (idx, byte) in (DISPLAY_ADDR..).zip(0..SCREEN_W * SCREEN_H / 8) {
+            let bits = self.read(idx);
+            for bit in 0..8 {
+                self.screen[byte * 8 + bit] = bits & (0x80 >> bit) != 0;
+            }
+        }
+    }
+}
@@ -0,0 +1,99 @@
+// CHIP-8X colour zones, second keypad and expansion port.
+use chip8_core::*;
+
+const RED: [u8; 3] = VP590_FOREGROUND[1];
+const GREEN: [u8; 3] = VP590_FOREGROUND[4];
+const WHITE: [u8; 3] = VP590_FOREGROUND[7];
+
+fn chip8x_emu(program: &[u8]) -> Emu {
+    let mut emu = Emu::new(|| 0);
+    emu.set_platform(Platform::Chip8X);
+    emu.load(program).unwrap();
+    emu
+}
+
+fn run(emu: &mut Emu, ticks: usize) {
+    for _ in 0..ticks {
+        emu.tick().unwrap();
+    }
+}
+
+#[test]
+fn loads_at_0x300() {
+    let emu = chip8x_emu(&[0x60, 0x01]);
+    assert_eq!(emu.pc(), 0x300);
+    assert_eq!(emu.peek(0x300), 0x60);
+}
+
+#[test]
+fn colors_zones_and_rows() {
+    let mut emu = chip8x_emu(&[
+        0x60, 0x11, // V0: zones 1-2 across
+        0x61, 0x02, // V1: zone row 2 (pixel rows 8-11)
+        0x62, 0x04, // V2 = green
+        0xB0, 0x20, // BXY0
+        0x63, 0x38, // V3: x = 56, last column
+        0x64, 0x1E, // V4: y = 30
+        0x65, 0x07, // V5 = white
+        0xB3, 0x53, // BXYN, 3 rows from row 30, wrapping to row 0
+    ]);
+    assert_eq!(emu.color_layer().unwrap().foreground(8, 8), RED);
+    run(&mut emu, 8);
+    let layer = emu.color_layer().unwrap();
+    assert_eq!(layer.foreground(8, 8), GREEN);
+    assert_eq!(layer.foreground(23, 11), GREEN);
+    assert_eq!(layer.foreground(24, 8), RED);
+    assert_eq!(layer.foreground(8, 12), RED);
+    assert_eq!(layer.foreground(63, 30), WHITE);
+    assert_eq!(layer.foreground(56, 31), WHITE);
+    assert_eq!(layer.foreground(56, 0), WHITE);
+    assert_eq!(layer.foreground(56, 1), RED);
+}
+
+#[test]
+fn cycles_background() {
+    let mut emu = chip8x_emu(&[0x02, 0xA0, 0x02, 0xA0, 0x02, 0xA0, 0x02, 0xA0]);
+    let mut seen = Vec::new();
+    for _ in 0..4 {
+        seen.push(emu.color_layer().unwrap().background());
+        emu.tick().unwrap();
+    }
+    assert_eq!(seen, VP590_BACKGROUND);
+    assert_eq!(emu.color_layer().unwrap().background(), VP590_BACKGROUND[0]);
+}
+
+#[test]
+fn second_keypad_and_port() {
+    let mut emu = chip8x_emu(&[
+        0x60, 0x05, // V0 = 5
+        0xE0, 0xF2, // skip if pad 2 key 5 is down
+        0x61, 0x01, // skipped
+        0xE0, 0xF5, // skip if pad 2 key 5 is up
+        0xF0, 0xF8, // port <- V0
+        0xF2, 0xFB, // V2 <- port
+        0x63, 0x34, // V3 = 0x34
+        0x64, 0x45, // V4 = 0x45
+        0x53, 0x41, // V3 = 0x71: 3 + 5 and 4 + 4, each & 7
+    ]);
+    emu.keypress(5, true);
+    emu.keypress2(5, true);
+    emu.set_port_input(0x42);
+    run(&mut emu, 8);
+    assert_eq!(emu.v_reg()[1], 0);
+    assert_eq!(emu.port_output(), 5);
+    assert_eq!(emu.v_reg()[2], 0x42);
+    assert_eq!(emu.v_reg()[3], 0x71);
+}
+
+#[test]
+fn other_platforms_keep_bnnn() {
+    assert_eq!(
+        Instruction::decode_for(Platform::ModernChip8, 0xB123),
+        Instruction::JumpOffset(1, 0x123)
+    );
+    assert_eq!(
+        Instruction::decode_for(Platform::Chip8X, 0xB123),
+        Instruction::ColorRows(1, 2, 3)
+    );
+    assert!(Emu::new(|| 0).color_layer().is_none());
+}
@@ -0,0 +1,112 @@
+// Platform guesses from ROM contents.
+use chip8_core::*;
+
+fn best(rom: &[u8]) -> Platform {
+    Platform::classify(rom)[0].platform
+}
+
+#[test]
+fn ranks_every_platform() {
+    let guesses = Platform::classify(&[]);
+    assert_eq!(guesses[0].platform, Platform::ModernChip8);
+    for platform in Platform::ALL {
+        assert_eq!(guesses.iter().filter(|g| g.platform == platform).count(), 1);
+    }
+    let total: f32 = guesses.iter().map(|g| g.confidence).sum();
+    assert!((total - 1.0).abs() < 1e-5);
+    assert!(
+        guesses
+            .windows(2)
+            .all(|w| w[0].confidence >= w[1].confidence)
+    );
+    assert_eq!(Platform::detect(&[]), None);
+}
+
+#[test]
+fn spots_platform_opcodes() {
+    // 00FF hires, DXY0 16x16 sprite, 00FD exit.
+    let schip = [0x00, 0xFF, 0xA3, 0x00, 0xD0, 0x10, 0x00, 0xFD];
+    assert_eq!(best(&schip), Platform::SuperChip);
+    // Emu can't run it, so it isn't picked.
+    assert_eq!(Platform::detect(&schip), None);
+    let guesses = Platform::classify(&schip);
+    assert!(guesses[0].confidence > 0.5);
+
+    // F000 NNNN long I and F201 planes, along with SCHIP's 00FF.
+    let xo = [0x00, 0xFF, 0xF0, 0x00, 0x03, 0x00, 0xF2, 0x01];
+    assert_eq!(best(&xo), Platform::XoChip);
+
+    assert_eq!(best(&[0x00, 0x11, 0x02, 0x10]), Platform::MegaChip8);
+    assert_eq!(best(&[0x02, 0xA0, 0x50, 0x11]), Platform::Chip8X);
+
+    // 0NNN machine code calls.
+    let vip = [0x0A, 0x00, 0x60, 0x00, 0x0A, 0x10, 0x0A, 0x20];
+    assert_eq!(best(&vip), Platform::OriginalChip8);
+}
+
+#[test]
+fn only_counts_code_that_can_run() {
+    // Draws the sprite at 206, whose 50 11 is also CHIP-8X's 5XY1.
+    let rom = [0x60, 0x05, 0xA2, 0x06, 0xD0, 0x02, 0x50, 0x11];
+    assert_eq!(best(&rom), Platform::ModernChip8);
+    assert_eq!(Platform::detect(&rom), None);
+
+    // 00FF after a jump never runs.
+    assert_eq!(
+        best(&[0x12, 0x04, 0x00, 0xFF, 0x12, 0x04]),
+        Platform::ModernChip8
+    );
+    // But it does after a skip, and after a subroutine returns.
+    assert_eq!(
+        best(&[0x30, 0x00, 0x12, 0x06, 0x00, 0xFF, 0x12, 0x06]),
+        Platform::SuperChip
+    );
+    assert_eq!(
+        best(&[0x22, 0x06, 0x00, 0xFF, 0x12, 0x04, 0x00, 0xEE]),
+        Platform::SuperChip
+    );
+}
+
+#[test]
+fn code_for_another_load_address_gives_it_away() {
+    // CHIP-8X loads at 300, where a jump to 202 would land below the program.
+    let rom = [0x02, 0xA0, 0x12, 0x02];
+    assert_eq!(best(&rom), Platform::ModernChip8);
+    let rom = [0x02, 0xA0, 0x13, 0x02];
+    assert_eq!(best(&rom), Platform::Chip8X);
+    assert_eq!(Platform::detect(&rom), Some(Platform::Chip8X));
+}
+
+#[test]
+fn uses_headers_and_size() {
+    let mut hires = vec![0x12, 0x60];
+    hires.resize(0x50, 0);
+    assert_eq!(best(&hires), Platform::Chip8Hires);
+
+    // Too big for 4K of RAM.
+    let big = vec![0x60; MAX_ROM_SIZE + 2];
+    assert_eq!(best(&big), Platform::XoChip);
+}
+
+#[test]
+fn one_sprite_byte_is_not_enough() {
+    // D010 could be sprite data as well as a 16x16 draw.
+    assert_eq!(best(&[0x60, 0x00, 0xD0, 0x10]), Platform::ModernChip8);
+    assert_eq!(Platform::detect(&[0x60, 0x00, 0xD0, 0x10]), None);
+}
+
+#[test]
+fn platforms_from_extensions() {
+    assert_eq!(Platform::from_extension("sc8"), Some(Platform::SuperChip));
+    assert_eq!(Platform::from_extension("XO8"), Some(Platform::XoChip));
+    assert_eq!(Platform::from_extension("ch8"), None);
+    assert_eq!(
+        Platform::from_file_name("games/ant.c8x"),
+        Some(Platform::Chip8X)
+    );
+    assert_eq!(
+        Platform::from_file_name("mega.mc8"),
+        Some(Platform::MegaChip8)
+    );
+    assert_eq!(Platform::from_file_name("sc8"), None);
+}
@@ -0,0 +1,87 @@
+// Self-modifying code must see its own writes even though `Emu` caches
+// decoded instructions per address.
+use chip8_core::*;
+
+fn emu_with(rom: &[u8]) -> Emu {
+    let mut emu = Emu::new(|| 0);
+    emu.load(rom).unwrap();
+    emu
+}
+
+fn run(emu: &mut Emu, ticks: usize) {
+    for _ in 0..ticks {
+        emu.tick().unwrap();
+    }
+}
+
+#[test]
+fn store_rewrites_cached_instruction() {
+    let rom = [
+        0x12, 0x0C, // 200: JMP 20C
+        0x60, 0x62, // 202: V0 = 62
+        0x61, 0x09, // 204: V1 = 09
+        0xA2, 0x0C, // 206: I = 20C
+        0xF1, 0x55, // 208: STORE V0-V1
+        0x12, 0x0C, // 20A: JMP 20C
+        0x62, 0x07, // 20C: V2 = 07, rewritten to V2 = 09
+        0x12, 0x02, // 20E: JMP 202
+    ];
+    let mut emu = emu_with(&rom);
+    run(&mut emu, 2);
+    assert_eq!(emu.v_reg()[2], 0x07);
+    run(&mut emu, 7);
+    assert_eq!(emu.v_reg()[2], 0x09);
+}
+
+#[test]
+fn bcd_rewrites_both_overlapping_instructions() {
+    let rom = [
+        0x12, 0x10, // 200: JMP 210
+        0x60, 0x05, // 202: V0 = 05
+        0xA2, 0x11, // 204: I = 211
+        0xF0, 0x33, // 206: BCD V0 -> 211..213
+        0x12, 0x10, // 208: JMP 210
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, //
+        0x62, 0x07, // 210: V2 = 07, low byte rewritten to 00
+        0x12, 0x02, // 212: JMP 202, rewritten to 0005
+    ];
+    let mut emu = emu_with(&rom);
+    run(&mut emu, 3);
+    assert_eq!(emu.v_reg()[2], 0x07);
+    run(&mut emu, 5);
+    assert_eq!(emu.v_reg()[2], 0x00);
+    assert_eq!(
+        emu.tick(),
+        Err(EmuError::InvalidOpcode {
+            op: 0x0005,
+            pc: 0x212
+        })
+    );
+}
+
+#[test]
+fn poke_invalidates_cached_instruction() {
+    let mut emu = emu_with(&[0x60, 0x01, 0x12, 0x00]);
+    run(&mut emu, 2);
+    assert_eq!(emu.v_reg()[0], 0x01);
+
+    // Low byte: the operand of the cached instruction at 200.
+    emu.poke(0x201, 0x05);
+    run(&mut emu, 1);
+    assert_eq!(emu.v_reg()[0], 0x05);
+
+    // High byte: changes the target register.
+    emu.poke(0x200, 0x61);
+    run(&mut emu, 2);
+    assert_eq!(emu.v_reg()[1], 0x05);
+    assert_eq!(emu.peek(0x200), 0x61);
+}
+
+#[test]
+fn load_replaces_cached_program() {
+    let mut emu = emu_with(&[0x60, 0x01, 0x12, 0x00]);
+    run(&mut emu, 2);
+    emu.load(&[0x60, 0x02, 0x12, 0x00]).unwrap();
+    run(&mut emu, 1);
+    assert_eq!(emu.v_reg()[0], 0x02);
+}
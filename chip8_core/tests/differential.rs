@@ -0,0 +1,248 @@
+// Runs `Emu` and the reference model side by side on random programs and
+// reports the first register, memory byte or pixel where they disagree.
+mod reference;
+
+use chip8_core::*;
+use proptest::prelude::*;
+use reference::{H, RefMachine, W};
+
+const PROFILES: [(&str, Quirks); 3] = [
+    ("chip8", Quirks::CHIP8),
+    ("schip", Quirks::SCHIP),
+    ("modern", Quirks::MODERN),
+];
+
+// Instructions per generated program, and how many steps to run it for.
+const PROGRAM_LEN: usize = 32;
+const STEPS: usize = 200;
+const TICKS_PER_TIMER: usize = 8;
+// Both machines get this byte for CXNN, so its masking can be compared.
+const RANDOM: u8 = 0xA5;
+
+#[derive(Debug, Clone)]
+struct Setup {
+    program: Vec<u16>,
+    data: Vec<u8>,
+    v: [u8; 16],
+    i: u16,
+    dt: u8,
+    st: u8,
+    keys: [bool; 16],
+}
+
+impl Setup {
+    fn rom(&self) -> Vec<u8> {
+        let mut rom: Vec<u8> = self
+            .program
+            .iter()
+            .flat_map(|op| op.to_be_bytes())
+            .collect();
+        rom.extend_from_slice(&self.data);
+        rom
+    }
+}
+
+// Jump and call targets land on an instruction of the generated program.
+fn target() -> impl Strategy<Value = u16> {
+    (0..PROGRAM_LEN as u16).prop_map(|k| 0x200 + 2 * k)
+}
+
+// Every opcode `Emu` implements.
+fn opcode() -> impl Strategy<Value = u16> {
+    let x = || 0..16u16;
+    let nn = || 0..=0xFFu16;
+    prop_oneof![
+        Just(0x00E0),
+        Just(0x00EE),
+        target().prop_map(|t| 0x1000 | t),
+        target().prop_map(|t| 0x2000 | t),
+        (x(), nn()).prop_map(|(x, nn)| 0x3000 | x << 8 | nn),
+        (x(), nn()).prop_map(|(x, nn)| 0x4000 | x << 8 | nn),
+        (x(), x()).prop_map(|(x, y)| 0x5000 | x << 8 | y << 4),
+        (x(), nn()).prop_map(|(x, nn)| 0x6000 | x << 8 | nn),
+        (x(), nn()).prop_map(|(x, nn)| 0x7000 | x << 8 | nn),
+        (
+            x(),
+            x(),
+            prop::sample::select(vec![0u16, 1, 2, 3, 4, 5, 6, 7, 0xE])
+        )
+            .prop_map(|(x, y, n)| 0x8000 | x << 8 | y << 4 | n),
+        (x(), x()).prop_map(|(x, y)| 0x9000 | x << 8 | y << 4),
+        (0..0x1000u16).prop_map(|nnn| 0xA000 | nnn),
+        (0..0x1000u16).prop_map(|nnn| 0xB000 | nnn),
+        (x(), nn()).prop_map(|(x, nn)| 0xC000 | x << 8 | nn),
+        (x(), x(), x()).prop_map(|(x, y, n)| 0xD000 | x << 8 | y << 4 | n),
+        x().prop_map(|x| 0xE09E | x << 8),
+        x().prop_map(|x| 0xE0A1 | x << 8),
+        (
+            x(),
+            prop::sample::select(vec![
+                0x07u16, 0x0A, 0x15, 0x18, 0x1E, 0x29, 0x33, 0x55, 0x65
+            ])
+        )
+            .prop_map(|(x, nn)| 0xF000 | x << 8 | nn),
+    ]
+}
+
+fn setup() -> impl Strategy<Value = Setup> {
+    (
+        prop::collection::vec(opcode(), PROGRAM_LEN),
+        prop::collection::vec(any::<u8>(), 0..64),
+        any::<[u8; 16]>(),
+        0..0x1000u16,
+        any::<u8>(),
+        any::<u8>(),
+        any::<[bool; 16]>(),
+    )
+        .prop_map(|(program, data, v, i, dt, st, keys)| Setup {
+            program,
+            data,
+            v,
+            i,
+            dt,
+            st,
+            keys,
+        })
+}
+
+fn build(setup: &Setup, quirks: Quirks) -> (Emu, RefMachine) {
+    let rom = setup.rom();
+    let mut emu = Emu::new(|| RANDOM);
+    emu.set_quirks(quirks);
+    emu.load(&rom).unwrap();
+    emu.set_i_reg(setup.i.into());
+    emu.set_timers(setup.dt, setup.st);
+    let mut model = RefMachine::new(&rom, quirks);
+    model.random = RANDOM;
+    model.i = setup.i as usize;
+    model.dt = setup.dt;
+    model.st = setup.st;
+    for r in 0..16 {
+        emu.set_v_reg(r, setup.v[r]);
+        model.v[r] = setup.v[r];
+    }
+    for k in 0..16 {
+        emu.keypress(k, setup.keys[k]);
+        model.keys[k] = setup.keys[k];
+    }
+    (emu, model)
+}
+
+// The first piece of state that differs, if any.
+fn divergence(emu: &Emu, model: &RefMachine) -> Option<String> {
+    if emu.pc() as usize != model.pc {
+        return Some(format!("pc: emu {:03X}, ref {:03X}", emu.pc(), model.pc));
+    }
+    if emu.sp() as usize != model.sp {
+        return Some(format!("sp: emu {}, ref {}", emu.sp(), model.sp));
+    }
+    if emu.i_reg() as usize != model.i {
+        return Some(format!("I: emu {:03X}, ref {:03X}", emu.i_reg(), model.i));
+    }
+    if let Some(r) = (0..16).find(|&r| emu.v_reg()[r] != model.v[r]) {
+        return Some(format!(
+            "V{:X}: emu {:02X}, ref {:02X}",
+            r,
+            emu.v_reg()[r],
+            model.v[r]
+        ));
+    }
+    if emu.delay_timer() != model.dt || emu.sound_timer() != model.st {
+        return Some(format!(
+            "timers: emu {}/{}, ref {}/{}",
+            emu.delay_timer(),
+            emu.sound_timer(),
+            model.dt,
+            model.st
+        ));
+    }
+    if let Some(a) = (0..RAM_SIZE).find(|&a| emu.ram()[a] != model.ram[a]) {
+        return Some(format!(
+            "RAM[{:03X}]: emu {:02X}, ref {:02X}",
+            a,
+            emu.ram()[a],
+            model.ram[a]
+        ));
+    }
+    let screen = emu.get_display();
+    if let Some(p) = (0..W * H).find(|&p| screen[p] != model.screen[p]) {
+        return Some(format!(
+            "pixel ({}, {}): emu {}, ref {}",
+            p % W,
+            p / W,
+            screen[p],
+            model.screen[p]
+        ));
+    }
+    None
+}
+
+// Steps both machines in lockstep; the error names the profile, the step and
+// the instruction that caused the first divergence.
+fn compare(setup: &Setup, name: &str, quirks: Quirks) -> Result<(), String> {
+    let (mut emu, mut model) = build(setup, quirks);
+    for step in 0..STEPS {
+        let pc = model.pc;
+        let op = (model.ram[pc] as u16) << 8 | model.ram[(pc + 1) % RAM_SIZE] as u16;
+        let emu_res = emu.tick();
+        let ref_res = model.step();
+        if emu_res.is_err() != ref_res.is_err() {
+            return Err(format!(
+                "{}: step {} ({:04X} at {:03X}): emu {:?}, ref {:?}",
+                name, step, op, pc, emu_res, ref_res
+            ));
+        }
+        if step % TICKS_PER_TIMER == TICKS_PER_TIMER - 1 {
+            emu.tick_timers();
+            model.tick_timers();
+        }
+        if let Some(diff) = divergence(&emu, &model) {
+            return Err(format!(
+                "{}: step {} ({:04X} at {:03X}): {}",
+                name, step, op, pc, diff
+            ));
+        }
+        if emu_res.is_err() {
+            break;
+        }
+    }
+    Ok(())
+}
+
+proptest! {
+    #![proptest_config(ProptestConfig::with_cases(256))]
+
+    #[test]
+    fn emu_matches_reference(setup in setup()) {
+        for (name, quirks) in PROFILES {
+            if let Err(msg) = compare(&setup, name, quirks) {
+                prop_assert!(false, "{}", msg);
+            }
+        }
+    }
+}
+
+#[test]
+fn test_roms_match_reference() {
+    let roms = [
+        &include_bytes!("../../test_roms/font_grid.ch8")[..],
+        include_bytes!("../../test_roms/bcd_memory.ch8"),
+        include_bytes!("../../test_roms/subroutines.ch8"),
+        include_bytes!("../../test_roms/keys_timers.ch8"),
+        include_bytes!("../../test_roms/arith.ch8"),
+    ];
+    for rom in roms {
+        let setup = Setup {
+            program: Vec::new(),
+            data: rom.to_vec(),
+            v: [0; 16],
+            i: 0,
+            dt: 0,
+            st: 0,
+            keys: [false; 16],
+        };
+        for (name, quirks) in PROFILES {
+            compare(&setup, name, quirks).unwrap();
+        }
+    }
+}
@@ -0,0 +1,48 @@
+// DREAM 6800 under CHIPOS: font, keypad and 50 Hz timers.
+use chip8_core::*;
+
+fn dream_emu(program: &[u8]) -> Emu {
+    let mut emu = Emu::new(|| 0);
+    emu.set_platform(Platform::Dream6800);
+    emu.set_quirks(Platform::Dream6800.quirks());
+    emu.load(program).unwrap();
+    emu
+}
+
+#[test]
+fn draws_chipos_digits() {
+    let mut emu = dream_emu(&[
+        0x60, 0x01, // V0 = 1
+        0xF0, 0x29, // I = digit 1
+        0xD0, 0x05, // draw it at (1, 1)
+    ]);
+    assert_eq!(emu.pc(), 0x200);
+    for _ in 0..3 {
+        emu.tick().unwrap();
+    }
+    // A one pixel wide bar in the middle of the 3 pixel cell.
+    let screen = emu.get_display();
+    for y in 1..6 {
+        assert_eq!(&screen[y * 64..y * 64 + 4], [false, false, true, false]);
+    }
+}
+
+#[test]
+fn keypad_counts_up_from_bottom_left() {
+    let keypad = Platform::Dream6800.keypad();
+    assert_eq!(keypad[3], [0x0, 0x1, 0x2, 0x3]);
+    assert_eq!(keypad[0], [0xC, 0xD, 0xE, 0xF]);
+    assert_eq!(Platform::ModernChip8.keypad()[0], [0x1, 0x2, 0x3, 0xC]);
+}
+
+#[test]
+fn timers_run_at_50hz() {
+    let mut emu = dream_emu(&[0x60, 0x3C, 0xF0, 0x15]); // DT = 60
+    emu.tick().unwrap();
+    emu.tick().unwrap();
+    // One second of 60 Hz calls.
+    for _ in 0..60 {
+        emu.tick_timers();
+    }
+    assert_eq!(emu.delay_timer(), 10);
+}
@@ -0,0 +1,87 @@
+// Custom opcodes through OpcodeExtension, and their disassembly.
+use chip8_core::*;
+use std::sync::Arc;
+
+// 5XY2: swap VX and VY.
+struct Swap;
+
+impl OpcodeExtension for Swap {
+    fn name(&self) -> &str {
+        "swap"
+    }
+    fn claims(&self, op: u16) -> bool {
+        op & 0xF00F == 0x5002
+    }
+    fn execute(&self, op: u16, emu: &mut Emu) -> Handling {
+        if !self.claims(op) {
+            return Handling::Unhandled;
+        }
+        let (x, y) = ((op >> 8) as usize & 0xF, (op >> 4) as usize & 0xF);
+        emu.v_reg_mut().swap(x, y);
+        Handling::Handled
+    }
+}
+
+// Sees every opcode but handles none.
+struct Declines;
+
+impl OpcodeExtension for Declines {
+    fn name(&self) -> &str {
+        "declines"
+    }
+    fn claims(&self, _op: u16) -> bool {
+        false
+    }
+    fn execute(&self, _op: u16, _emu: &mut Emu) -> Handling {
+        Handling::Unhandled
+    }
+}
+
+fn emu_with_swap(program: &[u8]) -> Emu {
+    let mut emu = Emu::new(|| 0);
+    emu.add_extension(Arc::new(Declines));
+    emu.add_extension(Arc::new(Swap));
+    emu.load(program).unwrap();
+    emu
+}
+
+#[test]
+fn runs_extension_opcodes() {
+    let mut emu = emu_with_swap(&[
+        0x60, 0x01, // V0 = 1
+        0x61, 0x02, // V1 = 2
+        0x50, 0x12, // swap V0, V1
+        0x50, 0x13, // nobody's
+    ]);
+    for _ in 0..3 {
+        emu.tick().unwrap();
+    }
+    assert_eq!(emu.v_reg()[..2], [2, 1]);
+    assert_eq!(
+        emu.tick(),
+        Err(EmuError::InvalidOpcode {
+            op: 0x5013,
+            pc: 0x206
+        })
+    );
+    assert_eq!(emu.extension_names(), ["declines", "swap"]);
+}
+
+#[test]
+fn disassembles_with_extension_names() {
+    let emu = emu_with_swap(&[0x60, 0x01, 0x50, 0x12, 0x50, 0x13, 0xD0, 0x15]);
+    assert_eq!(emu.disassemble(0x200), "LD V0, 0x01");
+    assert_eq!(emu.disassemble(0x202), "swap 0x5012");
+    assert_eq!(emu.disassemble(0x204), "DW 0x5013");
+    assert_eq!(emu.disassemble(0x206), "DRW V0, V1, 5");
+}
+
+#[test]
+fn known_opcodes_never_reach_extensions() {
+    // 5010 is SE V0, V1, so it runs as that and Swap is never asked.
+    let mut emu = emu_with_swap(&[0x60, 0x01, 0x50, 0x10]);
+    emu.tick().unwrap();
+    emu.tick().unwrap();
+    assert_eq!(emu.v_reg()[0], 1);
+    assert_eq!(emu.pc(), 0x204);
+}
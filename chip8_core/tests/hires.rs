@@ -0,0 +1,72 @@
+// CHIP-8 HIRES: 64x64 screen, programs entered at 0x244 behind a 1260 header.
+use chip8_core::*;
+
+// 1260 header, padding up to 0x244, then `program`.
+fn hires_rom(program: &[u8]) -> Vec<u8> {
+    let mut rom = vec![0x12, 0x60];
+    rom.resize(0x44, 0);
+    rom.extend_from_slice(program);
+    rom
+}
+
+fn hires_emu(program: &[u8]) -> Emu {
+    let rom = hires_rom(program);
+    let mut emu = Emu::new(|| 0);
+    emu.set_platform(Platform::detect(&rom).unwrap());
+    emu.load(&rom).unwrap();
+    emu
+}
+
+#[test]
+fn detects_header() {
+    assert_eq!(
+        Platform::detect(&hires_rom(&[])),
+        Some(Platform::Chip8Hires)
+    );
+    assert_eq!(Platform::detect(&[0x12, 0x00]), None);
+    assert_eq!(Platform::detect(&[]), None);
+}
+
+#[test]
+fn draws_below_row_32() {
+    let mut emu = hires_emu(&[
+        0x60, 0x00, // V0 = 0
+        0x61, 0x28, // V1 = 40
+        0xF0, 0x29, // I = font 0
+        0xD0, 0x11, // draw one row at (0, 40)
+    ]);
+    assert_eq!(emu.pc(), 0x244);
+    assert_eq!(emu.screen_size(), (64, 64));
+    for _ in 0..4 {
+        emu.tick().unwrap();
+    }
+    let screen = emu.get_display();
+    assert_eq!(screen.len(), 64 * 64);
+    assert!(screen[40 * 64]);
+    assert_eq!(emu.v_reg()[0xF], 0);
+}
+
+#[test]
+fn hires_clear_screen() {
+    let mut emu = hires_emu(&[
+        0xF0, 0x29, // I = font 0
+        0xD0, 0x01, // draw one row at (0, 0)
+        0x02, 0x30, // clear the 64x64 screen
+    ]);
+    emu.tick().unwrap();
+    emu.tick().unwrap();
+    assert!(emu.get_display()[0]);
+    emu.tick().unwrap();
+    assert!(emu.get_display().iter().all(|&p| !p));
+
+    // Anywhere else 0230 is a machine-code call.
+    let mut emu = Emu::new(|| 0);
+    emu.load(&[0x02, 0x30]).unwrap();
+    assert_eq!(
+        emu.tick(),
+        Err(EmuError::InvalidOpcode {
+            op: 0x0230,
+            pc: 0x200
+        })
+    );
+}
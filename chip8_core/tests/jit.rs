@@ -0,0 +1,138 @@
+// The JIT must be indistinguishable from Emu::tick. Every test runs one
+// machine through Jit::run (in differential mode, which also checks each
+// native run) and a copy through the interpreter, then compares them.
+#![cfg(all(target_arch = "x86_64", target_os = "linux"))]
+
+use chip8_core::*;
+use proptest::prelude::*;
+
+const PROGRAM_LEN: u16 = 24;
+const TICKS: usize = 500;
+
+fn target() -> impl Strategy<Value = u16> {
+    (0..PROGRAM_LEN).prop_map(|k| 0x200 + 2 * k)
+}
+
+// Mostly instructions the JIT compiles, with enough interpreted ones
+// (CALL/RET, DXYN, CXNN, FX33/FX55 into the program) to cross back and forth.
+fn opcode() -> impl Strategy<Value = u16> {
+    let x = || 0..16u16;
+    let nn = || 0..=0xFFu16;
+    prop_oneof![
+        4 => (x(), nn()).prop_map(|(x, nn)| 0x6000 | x << 8 | nn),
+        4 => (x(), nn()).prop_map(|(x, nn)| 0x7000 | x << 8 | nn),
+        8 => (x(), x(), prop::sample::select(vec![0u16, 1, 2, 3, 4, 5, 6, 7, 0xE]))
+            .prop_map(|(x, y, n)| 0x8000 | x << 8 | y << 4 | n),
+        2 => (x(), nn()).prop_map(|(x, nn)| 0x3000 | x << 8 | nn),
+        2 => (x(), nn()).prop_map(|(x, nn)| 0x4000 | x << 8 | nn),
+        1 => (x(), x()).prop_map(|(x, y)| 0x5000 | x << 8 | y << 4),
+        1 => (x(), x()).prop_map(|(x, y)| 0x9000 | x << 8 | y << 4),
+        2 => target().prop_map(|t| 0x1000 | t),
+        2 => target().prop_map(|t| 0xA000 | t),
+        1 => x().prop_map(|x| 0xF01E | x << 8),
+        1 => target().prop_map(|t| 0x2000 | t),
+        1 => Just(0x00EE),
+        1 => (x(), x(), x()).prop_map(|(x, y, n)| 0xD000 | x << 8 | y << 4 | n),
+        1 => x().prop_map(|x| 0xF033 | x << 8),
+        1 => x().prop_map(|x| 0xF055 | x << 8),
+        1 => x().prop_map(|x| 0xF065 | x << 8),
+        1 => (x(), nn()).prop_map(|(x, nn)| 0xC000 | x << 8 | nn),
+    ]
+}
+
+fn assert_same(jit: &Emu, interp: &Emu) {
+    assert_eq!(jit.pc(), interp.pc(), "pc");
+    assert_eq!(jit.sp(), interp.sp(), "sp");
+    assert_eq!(jit.i_reg(), interp.i_reg(), "I");
+    assert_eq!(jit.v_reg(), interp.v_reg(), "V registers");
+    assert_eq!(&jit.ram()[..], &interp.ram()[..], "RAM");
+    assert_eq!(jit.get_display(), interp.get_display(), "screen");
+}
+
+// Runs in bursts with a timer tick in between, like a frontend frame.
+fn run_both(rom: &[u8], quirks: Quirks, v: [u8; 16]) {
+    let mut emu = Emu::new(|| 0xA5);
+    emu.set_quirks(quirks);
+    emu.load(rom).unwrap();
+    for (r, val) in v.into_iter().enumerate() {
+        emu.set_v_reg(r, val);
+    }
+    let mut interp = emu.clone();
+
+    let mut jit = Jit::new().unwrap();
+    jit.set_differential(true);
+    for _ in 0..TICKS / 10 {
+        let jit_res = jit.run(&mut emu, 10);
+        let mut interp_res = Ok(());
+        for _ in 0..10 {
+            interp_res = interp.tick();
+            if interp_res.is_err() {
+                break;
+            }
+        }
+        assert_eq!(jit_res, interp_res);
+        assert_same(&emu, &interp);
+        if jit_res.is_err() {
+            break;
+        }
+        emu.tick_timers();
+        interp.tick_timers();
+    }
+}
+
+proptest! {
+    #![proptest_config(ProptestConfig::with_cases(128))]
+
+    #[test]
+    fn jit_matches_interpreter(
+        program in prop::collection::vec(opcode(), PROGRAM_LEN as usize),
+        v in any::<[u8; 16]>(),
+    ) {
+        let rom: Vec<u8> = program.iter().flat_map(|op| op.to_be_bytes()).collect();
+        for quirks in [Quirks::CHIP8, Quirks::SCHIP, Quirks::MODERN] {
+            run_both(&rom, quirks, v);
+        }
+    }
+}
+
+#[test]
+fn test_roms_match_interpreter() {
+    for rom in [
+        &include_bytes!("../../test_roms/font_grid.ch8")[..],
+        include_bytes!("../../test_roms/bcd_memory.ch8"),
+        include_bytes!("../../test_roms/subroutines.ch8"),
+        include_bytes!("../../test_roms/arith.ch8"),
+    ] {
+        run_both(rom, Quirks::default(), [0; 16]);
+    }
+}
+
+#[test]
+fn recompiles_overwritten_region() {
+    // The region at 20C is compiled, then rewritten by FX55 from 6207 to 6209.
+    let rom = [
+        0x12, 0x0C, 0x60, 0x62, 0x61, 0x09, 0xA2, 0x0C, 0xF1, 0x55, 0x12, 0x0C, 0x62, 0x07, 0x12,
+        0x02,
+    ];
+    let mut emu = Emu::new(|| 0xA5);
+    emu.load(&rom).unwrap();
+    let mut jit = Jit::new().unwrap();
+    jit.run(&mut emu, 3).unwrap();
+    assert_eq!(emu.v_reg()[2], 0x07);
+    jit.run(&mut emu, 7).unwrap();
+    assert_eq!(emu.v_reg()[2], 0x09);
+    assert!(jit.compiled_regions() > 0);
+}
+
+#[test]
+fn rechecks_regions_on_another_machine() {
+    // The same loop at 200 in both ROMs, adding 1 to V0 in one and 2 in the
+    // other.
+    let mut jit = Jit::new().unwrap();
+    for add in [1, 2] {
+        let mut emu = Emu::new(|| 0);
+        emu.load(&[0x70, add, 0x12, 0x04, 0x12, 0x04]).unwrap();
+        jit.run(&mut emu, 3).unwrap();
+        assert_eq!(emu.v_reg()[0], add);
+    }
+}
@@ -0,0 +1,69 @@
+// Where ROMs end up in memory, and what the loader refuses.
+use chip8_core::*;
+
+#[test]
+fn eti660_loads_at_0x600() {
+    let mut emu = Emu::new(|| 0);
+    emu.set_platform(Platform::Eti660);
+    emu.load(&[0x60, 0x2A]).unwrap();
+    assert_eq!(emu.pc(), 0x600);
+    assert_eq!(emu.peek(0x600), 0x60);
+    emu.tick().unwrap();
+    assert_eq!(emu.v_reg()[0], 0x2A);
+
+    let max = RAM_SIZE - 0x600;
+    assert_eq!(
+        emu.load(&vec![0; max + 1]),
+        Err(EmuError::RomTooLarge { size: max + 1, max })
+    );
+}
+
+#[test]
+fn interpreter_prefix_is_skipped() {
+    let mut image = vec![0xFF; 0x200];
+    image.extend([0x00, 0x00, 0x61, 0x07]);
+    let mut emu = Emu::new(|| 0);
+    emu.load_at(&image, 0, 0x202).unwrap();
+    // The font below 0x200 is still there.
+    assert_eq!(emu.peek(0), 0xF0);
+    assert_eq!(emu.peek(0x203), 0x07);
+    emu.tick().unwrap();
+    assert_eq!(emu.v_reg()[1], 0x07);
+}
+
+#[test]
+fn load_clears_previous_rom() {
+    let mut emu = Emu::new(|| 0);
+    emu.load(&[0x60, 0x01, 0x61, 0x02]).unwrap();
+    emu.tick().unwrap();
+    emu.load(&[0x12, 0x00]).unwrap();
+    assert_eq!(emu.peek(0x202), 0);
+    assert_eq!(emu.v_reg()[0], 0);
+    assert_eq!(emu.pc(), 0x200);
+}
+
+#[test]
+fn rejects_bad_addresses() {
+    let mut emu = Emu::new(|| 0);
+    emu.load(&[0x60, 0x01]).unwrap();
+    assert_eq!(
+        emu.load_at(&[0x12, 0x00], 0x1000, 0x1000),
+        Err(EmuError::BadLoadAddress { addr: 0x1000 })
+    );
+    assert_eq!(
+        emu.load_at(&[0x12, 0x00], 0x200, 0x100),
+        Err(EmuError::BadEntryPoint { entry: 0x100 })
+    );
+    // An image too short to reach past the interpreter, and one that ends
+    // before its entry point.
+    assert_eq!(
+        emu.load_at(&[0; 0x100], 0, 0x200),
+        Err(EmuError::EntryPastRom { entry: 0x200 })
+    );
+    assert_eq!(
+        emu.load_at(&[0x12, 0x00], 0x200, 0x202),
+        Err(EmuError::EntryPastRom { entry: 0x202 })
+    );
+    // A failed load leaves the running ROM alone.
+    assert_eq!(emu.peek(0x200), 0x60);
+}
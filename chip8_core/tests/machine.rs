@@ -0,0 +1,95 @@
+// Driving platforms through the Machine trait.
+// Frame only has its Color variant with the megachip feature.
+#![allow(irrefutable_let_patterns)]
+use chip8_core::*;
+
+fn run(machine: &mut dyn Machine, steps: usize) {
+    for _ in 0..steps {
+        machine.step().unwrap();
+    }
+}
+
+#[test]
+fn factory_applies_platform_and_quirks() {
+    for platform in Platform::ALL {
+        let machine = platform.machine(|| 0);
+        assert_eq!(machine.platform(), platform);
+        assert_eq!(machine.quirks(), platform.quirks());
+        assert_eq!(machine.frame().size(), platform.screen_size());
+    }
+}
+
+#[test]
+fn random_source_keeps_its_state_across_clones() {
+    // A counter standing in for a seeded generator.
+    let mut next = 7u8;
+    let mut emu = Emu::new(move || {
+        next = next.wrapping_add(1);
+        next
+    });
+    // V0 = random, V1 = random.
+    emu.load(&[0xC0, 0xFF, 0xC1, 0xFF]).unwrap();
+    emu.tick().unwrap();
+    let mut copy = emu.clone();
+    emu.tick().unwrap();
+    copy.tick().unwrap();
+    for emu in [&emu, &copy] {
+        assert_eq!(&emu.state().v_reg[..2], [8, 9]);
+    }
+}
+
+#[test]
+fn runs_any_platform_generically() {
+    // V0 = 1, I = font 1, draw it at (V0, V0).
+    let rom = [0x60, 0x01, 0xF0, 0x29, 0xD0, 0x05];
+    for platform in [Platform::ModernChip8, Platform::Chip8X, Platform::Eti660] {
+        let mut machine = platform.machine(|| 0);
+        machine.load(&rom).unwrap();
+        run(machine.as_mut(), 3);
+        let Frame::Mono { pixels, width, .. } = machine.frame() else {
+            panic!("{:?} should draw in monochrome", platform);
+        };
+        assert!(pixels.iter().any(|&p| p), "{:?} drew nothing", platform);
+        assert_eq!(width, 64);
+        assert_eq!(machine.state().pc, platform.entry() + 6);
+    }
+}
+
+#[test]
+fn chip8x_frames_carry_colours() {
+    let machine = Platform::Chip8X.machine(|| 0);
+    let Frame::Mono { colors, .. } = machine.frame() else {
+        panic!("CHIP-8X draws in monochrome");
+    };
+    assert!(colors.is_some());
+}
+
+#[test]
+fn state_round_trips() {
+    let mut machine = Platform::ModernChip8.machine(|| 0);
+    machine.load(&[0x22, 0x04, 0x00, 0x00, 0x60, 0x07]).unwrap();
+    run(machine.as_mut(), 2); // call 204, V0 = 7
+    machine.tick_timers();
+    let state = machine.state();
+    assert_eq!((state.sp, state.stack[0], state.v_reg[0]), (1, 0x202, 7));
+
+    let mut copy = Platform::ModernChip8.machine(|| 0);
+    copy.load(&[0x22, 0x04, 0x00, 0x00, 0x60, 0x07]).unwrap();
+    copy.set_state(&state);
+    assert_eq!(copy.state(), state);
+}
+
+#[test]
+fn hostile_states_stay_in_memory() {
+    let mut machine = Platform::ModernChip8.machine(|| 0);
+    machine.load(&[0x00, 0xEE]).unwrap(); // return
+    let mut state = machine.state();
+    state.stack[0] = 0xFFFF;
+    state.sp = 1;
+    state.i_reg = u32::MAX;
+    machine.set_state(&state);
+    assert_eq!(machine.state().i_reg, 0xFFFF);
+    machine.step().unwrap();
+    assert_eq!(machine.state().pc, 0xFFF);
+    let _ = machine.step();
+}
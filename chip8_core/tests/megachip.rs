@@ -0,0 +1,166 @@
+// MegaChip8: 24-bit I, the colour screen and samples.
+use chip8_core::*;
+
+// `code` at 0x200 and `data` at DATA, which is past the first 4 KiB.
+const DATA: u32 = 0x1100;
+
+fn mega_emu(code: &[u8], data: &[u8]) -> Emu {
+    let mut rom = code.to_vec();
+    rom.resize(DATA as usize - 0x200, 0);
+    rom.extend_from_slice(data);
+    let mut emu = Emu::new(|| 0);
+    emu.set_platform(Platform::MegaChip8);
+    emu.load(&rom).unwrap();
+    emu
+}
+
+fn run(emu: &mut Emu, ticks: usize) {
+    for _ in 0..ticks {
+        emu.tick().unwrap();
+    }
+}
+
+// 01NN NNNN pointing I at DATA + offset.
+fn load_i(offset: u32) -> [u8; 4] {
+    let [_, hi, mid, lo] = (DATA + offset).to_be_bytes();
+    [0x01, hi, mid, lo]
+}
+
+#[test]
+fn long_i_reaches_past_4k() {
+    let mut code = load_i(0).to_vec();
+    code.extend([0xF1, 0x65]); // V0, V1 = data
+    let mut emu = mega_emu(&code, &[0xAB, 0xCD]);
+    run(&mut emu, 1);
+    assert_eq!(emu.i_reg(), DATA);
+    assert_eq!(emu.pc(), 0x204);
+    run(&mut emu, 1);
+    assert_eq!(emu.v_reg()[..2], [0xAB, 0xCD]);
+
+    // The same ROM doesn't fit anywhere else.
+    let mut classic = Emu::new(|| 0);
+    let rom = vec![0; DATA as usize - 0x200 + 2];
+    assert!(matches!(
+        classic.load(&rom),
+        Err(EmuError::RomTooLarge { .. })
+    ));
+}
+
+#[test]
+fn entry_point_stays_in_the_first_4k() {
+    // Code only runs from the first 4K; the rest is for data.
+    let mut emu = mega_emu(&[0x12, 0x00], &[]);
+    assert_eq!(
+        emu.load_at(&vec![0; 0x2000], 0x200, 0x2000),
+        Err(EmuError::BadEntryPoint { entry: 0x2000 })
+    );
+    emu.tick().unwrap();
+}
+
+#[test]
+fn draws_palette_sprites_on_00e0() {
+    let red = [0xFF, 0xFF, 0x00, 0x00];
+    let green = [0xFF, 0x00, 0xFF, 0x00];
+    let mut data = [red, green].concat();
+    data.extend([1, 2]); // the sprite, at DATA + 8
+    let mut code = vec![0x00, 0x11]; // colour screen on
+    code.extend(load_i(0));
+    code.extend([
+        0x02, 0x02, // palette entries 1 and 2
+        0x03, 0x02, // 2 pixels wide
+        0x04, 0x01, // 1 pixel high
+    ]);
+    code.extend(load_i(8));
+    code.extend([
+        0x60, 0x0A, // V0 = 10
+        0x61, 0x05, // V1 = 5
+        0xD0, 0x11, // draw at (10, 5)
+        0x09, 0x01, // red pixels collide
+        0xD0, 0x11, // draw again
+        0x00, 0xE0, // show it
+    ]);
+    let mut emu = mega_emu(&code, &data);
+    assert!(emu.framebuffer().is_none());
+    run(&mut emu, 9);
+    assert_eq!(emu.v_reg()[0xF], 0);
+    let picture = emu.framebuffer().unwrap();
+    assert_eq!((picture.width(), picture.height()), (256, 192));
+    assert_eq!(picture.pixel(10, 5), [0; 3]);
+
+    run(&mut emu, 3);
+    assert_eq!(emu.v_reg()[0xF], 1);
+    let picture = emu.framebuffer().unwrap();
+    assert_eq!(picture.pixel(10, 5), [0xFF, 0x00, 0x00]);
+    assert_eq!(picture.pixel(11, 5), [0x00, 0xFF, 0x00]);
+    assert_eq!(picture.pixel(12, 5), [0; 3]);
+}
+
+#[test]
+fn add_blend_mixes_colours() {
+    let mut data = [[0xFF, 0xFF, 0x00, 0x00], [0xFF, 0x00, 0xFF, 0x00]].concat();
+    data.extend([1, 2]); // red sprite at DATA + 8, green at DATA + 9
+    let mut code = vec![0x00, 0x11];
+    code.extend(load_i(0));
+    code.extend([0x02, 0x02, 0x03, 0x01, 0x04, 0x01]);
+    code.extend(load_i(8));
+    code.extend([0xD0, 0x01, 0x08, 0x03]); // draw red, then add
+    code.extend(load_i(9));
+    code.extend([0xD0, 0x01, 0x00, 0xE0]); // draw green, show
+    let mut emu = mega_emu(&code, &data);
+    run(&mut emu, 11);
+    assert_eq!(emu.framebuffer().unwrap().pixel(0, 0), [0xFF, 0xFF, 0x00]);
+}
+
+#[test]
+fn plays_and_stops_samples() {
+    // 8000 Hz, 3 samples, reserved byte, then the samples.
+    let data = [0x1F, 0x40, 0x00, 0x00, 0x03, 0x00, 10, 20, 30];
+    let mut code = load_i(0).to_vec();
+    code.extend([0x06, 0x01, 0x07, 0x00]);
+    let mut emu = mega_emu(&code, &data);
+    run(&mut emu, 2);
+    assert_eq!(
+        emu.sample(),
+        Some(Sample {
+            rate: 8000,
+            start: DATA + 6,
+            len: 3,
+            looping: false
+        })
+    );
+    assert_eq!(emu.sample_data().collect::<Vec<_>>(), [10, 20, 30]);
+    run(&mut emu, 1);
+    assert_eq!(emu.sample(), None);
+    assert_eq!(emu.sample_data().count(), 0);
+}
+
+#[test]
+fn samples_stop_at_the_end_of_memory() {
+    // The largest length, saved ten bytes before the end of memory.
+    let code = [
+        0x60, 0x1F, 0x61, 0x40, 0x62, 0xFF, 0x63, 0xFF, 0x64, 0xFF, 0x01, 0xFF, 0xFF, 0xF6, 0xF4,
+        0x55, 0x01, 0xFF, 0xFF, 0xF6, 0x06, 0x00,
+    ];
+    let mut emu = mega_emu(&code, &[]);
+    run(&mut emu, 9);
+    let sample = emu.sample().unwrap();
+    assert_eq!((sample.start, sample.len), (0xFF_FFFC, 4));
+    assert!(sample.looping);
+    assert_eq!(emu.sample_data().count(), 4);
+}
+
+#[test]
+fn hostile_i_wraps_around_memory() {
+    let code = [
+        0x00, 0x11, // colour screen on
+        0x03, 0x03, // 3 pixels wide
+        0x04, 0x01, // 1 pixel high
+        0xD0, 0x01, // draw from the last byte of memory on
+    ];
+    let mut emu = mega_emu(&code, &[]);
+    let mut state = emu.state();
+    state.i_reg = u32::MAX;
+    emu.set_state(&state);
+    assert_eq!(emu.i_reg(), 0xFF_FFFF);
+    run(&mut emu, 4);
+}
@@ -0,0 +1,117 @@
+// Observer hooks.
+use chip8_core::*;
+use std::sync::{Arc, Mutex};
+
+#[derive(Debug, PartialEq)]
+enum Event {
+    Fetch(u16, Instruction),
+    Read(u32, u8),
+    Write(u32, u8),
+    Register(Register, u32),
+    Draw(u8, u8, u8, bool),
+    Timers(u8, u8),
+}
+
+#[derive(Default)]
+struct Log(Mutex<Vec<Event>>);
+
+impl Log {
+    fn take(&self) -> Vec<Event> {
+        std::mem::take(&mut self.0.lock().unwrap())
+    }
+}
+
+impl Observer for Log {
+    fn fetch(&self, addr: u16, ins: Instruction) {
+        self.0.lock().unwrap().push(Event::Fetch(addr, ins));
+    }
+    fn read(&self, addr: u32, val: u8) {
+        self.0.lock().unwrap().push(Event::Read(addr, val));
+    }
+    fn write(&self, addr: u32, val: u8) {
+        self.0.lock().unwrap().push(Event::Write(addr, val));
+    }
+    fn register(&self, reg: Register, val: u32) {
+        self.0.lock().unwrap().push(Event::Register(reg, val));
+    }
+    fn draw(&self, x: u8, y: u8, height: u8, collision: bool) {
+        self.0
+            .lock()
+            .unwrap()
+            .push(Event::Draw(x, y, height, collision));
+    }
+    fn timers(&self, delay: u8, sound: u8) {
+        self.0.lock().unwrap().push(Event::Timers(delay, sound));
+    }
+}
+
+// Only counts fetches.
+#[derive(Default)]
+struct Counter(Mutex<usize>);
+
+impl Observer for Counter {
+    fn fetch(&self, _addr: u16, _ins: Instruction) {
+        *self.0.lock().unwrap() += 1;
+    }
+}
+
+#[test]
+fn reports_what_each_instruction_does() {
+    let mut emu = Emu::new(|| 0);
+    emu.load(&[
+        0x60, 0x05, // V0 = 5
+        0xA3, 0x00, // I = 300
+        0xF0, 0x33, // BCD of V0 at I
+        0xF0, 0x15, // DT = V0
+        0xF0, 0x29, // I = font 5
+        0xD0, 0x01, // draw one row at (5, 5)
+    ])
+    .unwrap();
+    let log = Arc::new(Log::default());
+    emu.add_observer(log.clone());
+
+    emu.tick().unwrap();
+    assert_eq!(
+        log.take(),
+        [
+            Event::Fetch(0x200, Instruction::LoadImm(0, 5)),
+            Event::Register(Register::V(0), 5),
+        ]
+    );
+    emu.tick().unwrap();
+    emu.tick().unwrap();
+    assert_eq!(
+        log.take()[2..],
+        [
+            Event::Fetch(0x204, Instruction::Bcd(0)),
+            Event::Write(0x300, 0),
+            Event::Write(0x301, 0),
+            Event::Write(0x302, 5),
+        ]
+    );
+    emu.tick().unwrap();
+    assert_eq!(log.take()[1], Event::Register(Register::Delay, 5));
+    emu.tick().unwrap();
+    log.take();
+    emu.tick().unwrap();
+    assert_eq!(
+        log.take()[1..],
+        [Event::Read(25, 0xF0), Event::Draw(5, 5, 1, false),]
+    );
+    emu.tick_timers();
+    assert_eq!(log.take(), [Event::Timers(4, 0)]);
+}
+
+#[test]
+fn observers_compose() {
+    let mut emu = Emu::new(|| 0);
+    emu.load(&[0x12, 0x00]).unwrap(); // jump to itself
+    let (first, second) = (Arc::new(Counter::default()), Arc::new(Counter::default()));
+    emu.tick().unwrap();
+    emu.add_observer(first.clone());
+    emu.tick().unwrap();
+    emu.add_observer(second.clone());
+    emu.tick().unwrap();
+    assert_eq!(*first.0.lock().unwrap(), 2);
+    assert_eq!(*second.0.lock().unwrap(), 1);
+}
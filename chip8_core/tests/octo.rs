@@ -0,0 +1,283 @@
+// Octo source and cartridges.
+use chip8_core::*;
+
+fn words(rom: &[u8]) -> Vec<u16> {
+    rom.chunks(2)
+        .map(|w| u16::from_be_bytes([w[0], *w.get(1).unwrap_or(&0)]))
+        .collect()
+}
+
+#[test]
+fn assembles_statements() {
+    let rom = assemble(
+        ": main
+           clear
+           v0 := 5
+           v1 := v0
+           v1 += 3
+           v2 -= 1   # subtracts by adding
+           i := sprite
+           sprite v0 v1 4
+           v3 := random 0xFF
+           save v3
+           bcd v2
+           loop again
+         : sprite
+           0xF0 0x90",
+    )
+    .unwrap();
+    assert_eq!(
+        words(&rom),
+        [
+            0x00E0, 0x6005, 0x8100, 0x7103, 0x72FF, 0xA216, 0xD014, 0xC3FF, 0xF355, 0xF233, 0x1214,
+            0xF090
+        ]
+    );
+}
+
+#[test]
+fn jumps_to_main_unless_it_comes_first() {
+    let rom = assemble(": data 1 2 : main jump main").unwrap();
+    assert_eq!(rom, [0x12, 0x04, 1, 2, 0x12, 0x04]);
+}
+
+#[test]
+fn assembles_control_flow() {
+    let rom = assemble(
+        ": main
+           if v0 == 1 then v1 := 2
+           if v0 != v1 begin
+             v2 := 3
+           else
+             v2 := 4
+           end
+           loop
+             while v3 key
+             v3 += 1
+           again
+           if v4 > 5 then return",
+    )
+    .unwrap();
+    assert_eq!(
+        words(&rom),
+        [
+            0x4001, 0x6102, 0x9010, 0x120C, 0x6203, 0x120E, 0x6204, 0xE39E, 0x1216, 0x7301, 0x120E,
+            0x6F05, 0x8F45, 0x4F00, 0x00EE
+        ]
+    );
+}
+
+#[test]
+fn expands_macros_constants_and_strings() {
+    let rom = assemble(
+        ":const SPEED 3
+         :calc DOUBLE { SPEED * 2 }
+         :macro add reg amount { reg += amount }
+         :stringmode text \"AB\" { :byte { VALUE + 10 } }
+         : main
+           add v1 DOUBLE
+           :unpack 0xA data
+           v5 := SPEED
+           :next patch
+           v6 := 0
+           text \"BA\"
+           i := patch
+           jump main
+         :org 0x300
+         : data",
+    )
+    .unwrap();
+    assert_eq!(
+        rom,
+        [
+            0x71, 0x06, 0x60, 0xA3, 0x61, 0x00, 0x65, 0x03, 0x66, 0x00, 0x0B, 0x0A, 0xA2, 0x09,
+            0x12, 0x00
+        ]
+    );
+    assert_eq!(
+        assemble(": main :org 0x204 1 2").unwrap(),
+        [0, 0, 0, 0, 1, 2]
+    );
+}
+
+#[test]
+fn reports_errors_by_line() {
+    let error = |source| assemble(source).unwrap_err().to_string();
+    assert_eq!(
+        error(": main\n  jump nowhere"),
+        "line 2: undefined name nowhere"
+    );
+    assert_eq!(
+        error(": start\n  clear"),
+        "line 1: program has no main label"
+    );
+    assert_eq!(
+        error(": main\n  v0 := 300"),
+        "line 2: 300 does not fit in a byte"
+    );
+    assert_eq!(
+        error(": main\n  if v0 == 1 begin"),
+        "line 2: if without end"
+    );
+    assert_eq!(
+        error(": main\n:assert \"too big\" { 2 < 1 }"),
+        "line 2: assertion failed: too big"
+    );
+}
+
+#[test]
+fn rejects_what_emu_cannot_run() {
+    let error = |source| assemble(source).unwrap_err().to_string();
+    assert_eq!(
+        error(": main hires clear loop again"),
+        "line 1: hires is SUPER-CHIP, which isn't emulated"
+    );
+    assert_eq!(
+        error(": main\n  plane 1"),
+        "line 2: plane is XO-CHIP, which isn't emulated"
+    );
+    assert_eq!(
+        error(": main\n  i := long main"),
+        "line 2: i := long is XO-CHIP, which isn't emulated"
+    );
+    assert_eq!(
+        error(": main\n  save v0 - v3"),
+        "line 2: save with a range is XO-CHIP, which isn't emulated"
+    );
+    // Emu has 4K of memory.
+    assert_eq!(
+        error(": main\n:org 0x1000"),
+        "line 2: cannot assemble at 4096"
+    );
+    assert_eq!(
+        error(": main\n:org 0xFFF 1 2"),
+        "line 2: program is bigger than memory"
+    );
+}
+
+#[test]
+fn assembled_program_runs() {
+    let rom = assemble(
+        ": main
+           v0 := 0
+           loop
+             v0 += 1
+             while v0 < 10
+           again
+           i := result
+           save v0
+         : halt
+           jump halt
+         : result
+           0",
+    )
+    .unwrap();
+    let mut emu = Emu::new(|| 0);
+    emu.load(&rom).unwrap();
+    for _ in 0..100 {
+        emu.tick().unwrap();
+    }
+    assert_eq!(emu.peek(0x214), 10);
+}
+
+// A cartridge holding `data`, over two frames of a 64 pixel wide picture
+// whose label is all palette colour 1.
+fn picture(data: &[u8]) -> Vec<u8> {
+    let mut pixels: Vec<u8> = data
+        .iter()
+        .flat_map(|b| [b >> 6, b >> 4 & 3, b >> 2 & 3, b & 3].map(|bits| 4 | bits))
+        .collect();
+    let rows = pixels.len().div_ceil(64).div_ceil(2) as u16;
+    pixels.resize(rows as usize * 64 * 2, 4);
+    let palette: Vec<u8> = (0..16).flat_map(|c| [c * 16; 3]).collect();
+    let mut gif = Vec::new();
+    let mut encoder = gif::Encoder::new(&mut gif, 64, rows * 2, &palette).unwrap();
+    for half in pixels.chunks(rows as usize * 64) {
+        let frame = gif::Frame::from_indexed_pixels(64, rows, half, None);
+        encoder.write_frame(&frame).unwrap();
+    }
+    drop(encoder);
+    gif
+}
+
+fn cartridge(json: &str) -> Vec<u8> {
+    let mut data = (json.len() as u32).to_be_bytes().to_vec();
+    data.extend_from_slice(json.as_bytes());
+    picture(&data)
+}
+
+#[test]
+fn decodes_cartridge_program_and_options() {
+    let program = ": main\n  v0 := 7\n  loop again\n";
+    let json = format!(
+        r##"{{"key":"","program":{},"options":{{"tickrate":7,
+            "fillColor":"#112233","fillColor2":"#445566","blendColor":"#778899",
+            "backgroundColor":"#000000","buzzColor":"#FFAA00","quietColor":"#000000",
+            "shiftQuirks":true,"loadStoreQuirks":true,"vfOrderQuirks":false,
+            "clipQuirks":true,"vBlankQuirks":false,"jumpQuirks":false,
+            "screenRotation":0,"maxSize":3584,"touchInputMode":"none",
+            "logicQuirks":false,"fontStyle":"octo"}}}}"##,
+        serde_json::to_string(program).unwrap()
+    );
+    let gif = cartridge(&json);
+    assert!(Cartridge::detect(&gif));
+    let cart = Cartridge::decode(&gif).unwrap();
+    assert_eq!(cart.program, program);
+    assert_eq!(cart.info.platform, Platform::ModernChip8);
+    assert_eq!(cart.info.tickrate, 7);
+    assert_eq!(
+        cart.info.quirks,
+        Quirks {
+            vf_reset: false,
+            memory_increment: false,
+            shift_vx: true,
+            jump_vx: false,
+            clip_sprites: true,
+        }
+    );
+    assert_eq!(
+        cart.info.palette,
+        [
+            [0, 0, 0],
+            [0x11, 0x22, 0x33],
+            [0x44, 0x55, 0x66],
+            [0x77, 0x88, 0x99]
+        ]
+    );
+    assert_eq!(cart.rom().unwrap(), [0x60, 0x07, 0x12, 0x02]);
+}
+
+#[test]
+fn missing_options_are_octos_defaults() {
+    let cart = Cartridge::decode(&cartridge(r#"{"program":": main clear"}"#)).unwrap();
+    assert_eq!(cart.info.tickrate, 20);
+    assert_eq!(
+        cart.info.quirks,
+        Quirks {
+            vf_reset: false,
+            memory_increment: true,
+            shift_vx: false,
+            jump_vx: false,
+            clip_sprites: false,
+        }
+    );
+    assert_eq!(cart.info.palette.len(), 4);
+}
+
+#[test]
+fn rejects_broken_cartridges() {
+    assert!(!Cartridge::detect(&[0x12, 0x00]));
+    assert!(matches!(
+        Cartridge::decode(b"GIF89a"),
+        Err(CartridgeError::Gif(_))
+    ));
+    assert!(matches!(
+        Cartridge::decode(&cartridge("{")),
+        Err(CartridgeError::Json(_))
+    ));
+    // 256 bytes promised, a few dozen there.
+    assert!(matches!(
+        Cartridge::decode(&picture(&[0, 0, 1, 0, b'{'])),
+        Err(CartridgeError::Truncated)
+    ));
+}
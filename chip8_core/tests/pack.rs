@@ -0,0 +1,101 @@
+// Zip archives of ROMs.
+use chip8_core::*;
+use std::io::{Cursor, Write};
+use zip::{CompressionMethod, write::SimpleFileOptions};
+
+// A zip of `files`, deflated except for the first.
+fn zip(files: &[(&str, &[u8])]) -> Vec<u8> {
+    let mut zip = zip::ZipWriter::new(Cursor::new(Vec::new()));
+    zip.add_directory("games/", SimpleFileOptions::default())
+        .unwrap();
+    for (idx, (name, data)) in files.iter().enumerate() {
+        let method = if idx == 0 {
+            CompressionMethod::Stored
+        } else {
+            CompressionMethod::Deflated
+        };
+        let options = SimpleFileOptions::default().compression_method(method);
+        zip.start_file(*name, options).unwrap();
+        zip.write_all(data).unwrap();
+    }
+    zip.finish().unwrap().into_inner()
+}
+
+fn pack() -> RomPack {
+    let data = zip(&[
+        ("games/Pong.ch8", &[0x12, 0x00]),
+        ("README.txt", b"Public domain games"),
+        ("games/ant.sc8", &[0x00, 0xFF, 0x12, 0x02]),
+        ("__MACOSX/games/._ant.sc8", &[0; 4]),
+        ("xo/t8nks.XO8", &[0xF0, 0x00, 0x02, 0x00]),
+    ]);
+    assert!(RomPack::detect(&data));
+    RomPack::open(&data).unwrap()
+}
+
+#[test]
+fn lists_only_roms() {
+    let pack = pack();
+    let names: Vec<_> = pack.roms.iter().map(|rom| rom.name.as_str()).collect();
+    assert_eq!(names, ["games/Pong.ch8", "games/ant.sc8", "xo/t8nks.XO8"]);
+    assert_eq!(pack.roms[1].data, [0x00, 0xFF, 0x12, 0x02]);
+    assert_eq!(
+        pack.to_string(),
+        format!(
+            "  0  games/Pong.ch8  2 bytes  {}\n  1  games/ant.sc8  4 bytes  {}\n  2  xo/t8nks.XO8  4 bytes  {}\n",
+            sha1_hex(&[0x12, 0x00]),
+            sha1_hex(&[0x00, 0xFF, 0x12, 0x02]),
+            sha1_hex(&[0xF0, 0x00, 0x02, 0x00]),
+        )
+    );
+}
+
+#[test]
+fn platform_comes_from_the_extension() {
+    let platforms: Vec<_> = pack().roms.iter().map(PackedRom::platform).collect();
+    assert_eq!(
+        platforms,
+        [None, Some(Platform::SuperChip), Some(Platform::XoChip)]
+    );
+}
+
+#[test]
+fn finds_roms_by_index_or_name() {
+    let pack = pack();
+    let found = |which| pack.find(which).map(|rom| rom.name.as_str());
+    assert_eq!(found("1"), Some("games/ant.sc8"));
+    assert_eq!(found("3"), None);
+    assert_eq!(found("games/Pong.ch8"), Some("games/Pong.ch8"));
+    assert_eq!(found("pong.ch8"), Some("games/Pong.ch8"));
+    assert_eq!(found("T8NKS"), Some("xo/t8nks.XO8"));
+    assert_eq!(found("tank"), None);
+    assert_eq!(pack.roms[2].file_name(), "t8nks.XO8");
+}
+
+#[test]
+fn rejects_bad_archives() {
+    assert!(!RomPack::detect(&[0x12, 0x00]));
+    assert!(matches!(
+        RomPack::open(b"PK\x03\x04 not really"),
+        Err(PackError::Zip(_))
+    ));
+    let big = vec![0; (1 << 24) + 1];
+    let data = zip(&[("small.ch8", &[0x12, 0x00]), ("big.xo8", &big)]);
+    assert!(matches!(
+        RomPack::open(&data),
+        Err(PackError::TooLarge { name, .. }) if name == "big.xo8"
+    ));
+    assert_eq!(RomPack::open(&zip(&[])).unwrap(), RomPack::default());
+}
+
+#[test]
+fn caps_the_whole_pack() {
+    // Every entry fits in MegaChip8's memory, but not all five together.
+    let big = vec![0; 1 << 24];
+    let names = ["a.mc8", "b.mc8", "c.mc8", "d.mc8", "e.mc8"];
+    let files: Vec<(&str, &[u8])> = names.iter().map(|name| (*name, &big[..])).collect();
+    let err = RomPack::open(&zip(&files)).unwrap_err();
+    assert!(matches!(&err, PackError::TooLarge { name, .. } if name.is_empty()));
+    assert_eq!(err.to_string(), "pack unpacks to over 67108864 bytes");
+    assert_eq!(RomPack::open(&zip(&files[..4])).unwrap().roms.len(), 4);
+}
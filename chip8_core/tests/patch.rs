@@ -0,0 +1,157 @@
+// IPS and BPS patches.
+use chip8_core::*;
+
+const ROM: [u8; 8] = [0x60, 0x01, 0x61, 0x02, 0x12, 0x04, 0xAA, 0xBB];
+
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for byte in data {
+        crc ^= *byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 {
+                (crc >> 1) ^ 0xEDB8_8320
+            } else {
+                crc >> 1
+            };
+        }
+    }
+    !crc
+}
+
+fn varint(out: &mut Vec<u8>, mut value: usize) {
+    loop {
+        let x = (value & 0x7F) as u8;
+        value >>= 7;
+        if value == 0 {
+            out.push(0x80 | x);
+            return;
+        }
+        out.push(x);
+        value -= 1;
+    }
+}
+
+// A BPS patch of `actions` (varint-encoded command, then any payload),
+// with its footer.
+fn bps(source: &[u8], target: &[u8], actions: &[u8]) -> Vec<u8> {
+    let mut patch = b"BPS1".to_vec();
+    varint(&mut patch, source.len());
+    varint(&mut patch, target.len());
+    varint(&mut patch, 0);
+    patch.extend_from_slice(actions);
+    patch.extend_from_slice(&crc32(source).to_le_bytes());
+    patch.extend_from_slice(&crc32(target).to_le_bytes());
+    let crc = crc32(&patch);
+    patch.extend_from_slice(&crc.to_le_bytes());
+    patch
+}
+
+// Makes `target` from ROM: keep 2 bytes, write 2 new ones, copy 2 from
+// the source's start, then repeat the last byte twice.
+fn bps_actions() -> (Vec<u8>, Vec<u8>) {
+    let target = vec![0x60, 0x01, 0x65, 0x05, 0x60, 0x01, 0x01, 0x01];
+    let mut actions = Vec::new();
+    varint(&mut actions, (2 - 1) << 2); // SourceRead 2
+    varint(&mut actions, ((2 - 1) << 2) | 1); // TargetRead 2
+    actions.extend_from_slice(&[0x65, 0x05]);
+    varint(&mut actions, ((2 - 1) << 2) | 2); // SourceCopy 2
+    varint(&mut actions, 0); // from 0
+    varint(&mut actions, ((2 - 1) << 2) | 3); // TargetCopy 2
+    varint(&mut actions, 5 << 1); // from 5, overlapping
+    (target, actions)
+}
+
+#[test]
+fn applies_ips_records_runs_and_truncation() {
+    let mut patch = b"PATCH".to_vec();
+    // Two bytes at 2.
+    patch.extend_from_slice(&[0x00, 0x00, 0x02, 0x00, 0x02, 0x65, 0x05]);
+    // Three 0xEE from 8, growing the ROM.
+    patch.extend_from_slice(&[0x00, 0x00, 0x08, 0x00, 0x00, 0x00, 0x03, 0xEE]);
+    patch.extend_from_slice(b"EOF");
+    assert_eq!(PatchFormat::detect(&patch), Some(PatchFormat::Ips));
+    assert_eq!(
+        apply_patch(&ROM, &patch).unwrap(),
+        [
+            0x60, 0x01, 0x65, 0x05, 0x12, 0x04, 0xAA, 0xBB, 0xEE, 0xEE, 0xEE
+        ]
+    );
+
+    // Cut to 4 bytes after EOF.
+    let mut cut = b"PATCHEOF".to_vec();
+    cut.extend_from_slice(&[0x00, 0x00, 0x04]);
+    assert_eq!(apply_patch(&ROM, &cut).unwrap(), ROM[..4]);
+
+    assert_eq!(
+        apply_patch(&ROM, b"PATCH\x00\x00\x02\x00\x05\x01"),
+        Err(PatchError::Truncated)
+    );
+    assert_eq!(
+        apply_patch(&ROM, b"garbage"),
+        Err(PatchError::UnknownFormat)
+    );
+}
+
+#[test]
+fn applies_bps_actions() {
+    let (target, actions) = bps_actions();
+    let patch = bps(&ROM, &target, &actions);
+    assert_eq!(PatchFormat::detect(&patch), Some(PatchFormat::Bps));
+    assert_eq!(apply_patch(&ROM, &patch).unwrap(), target);
+}
+
+#[test]
+fn checks_bps_checksums() {
+    let (target, actions) = bps_actions();
+    let patch = bps(&ROM, &target, &actions);
+
+    let mut other = ROM;
+    other[7] = 0;
+    assert!(matches!(
+        apply_patch(&other, &patch),
+        Err(PatchError::SourceChecksum { .. })
+    ));
+    assert_eq!(
+        apply_patch(&ROM[..6], &patch),
+        Err(PatchError::SourceSize {
+            expected: 8,
+            actual: 6
+        })
+    );
+
+    let mut damaged = patch.clone();
+    damaged[10] ^= 1;
+    assert!(matches!(
+        apply_patch(&ROM, &damaged),
+        Err(PatchError::PatchChecksum { .. })
+    ));
+
+    // A patch whose footer promises another result.
+    let mut wrong = target.clone();
+    wrong[0] = 0;
+    let patch = bps(&ROM, &wrong, &actions);
+    assert!(matches!(
+        apply_patch(&ROM, &patch),
+        Err(PatchError::TargetChecksum { .. })
+    ));
+}
+
+#[test]
+fn rejects_bps_copies_out_of_range() {
+    let target = [0; 4];
+    let mut actions = Vec::new();
+    varint(&mut actions, ((4 - 1) << 2) | 2); // SourceCopy 4
+    varint(&mut actions, 6 << 1); // from 6, past the end of ROM
+    assert_eq!(
+        apply_patch(&ROM, &bps(&ROM, &target, &actions)),
+        Err(PatchError::BadCopy)
+    );
+
+    let mut actions = Vec::new();
+    varint(&mut actions, ((4 - 1) << 2) | 3); // TargetCopy with no target yet
+    varint(&mut actions, 0);
+    assert_eq!(
+        apply_patch(&ROM, &bps(&ROM, &target, &actions)),
+        Err(PatchError::BadCopy)
+    );
+}
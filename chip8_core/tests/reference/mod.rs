@@ -0,0 +1,220 @@
+// A deliberately plain CHIP-8 interpreter, written straight from the spec.
+// It shares nothing with `Emu` except the `Quirks` settings, so a refactor of
+// `Emu::execute` can be checked against it instruction by instruction.
+use chip8_core::Quirks;
+
+pub const W: usize = 64;
+pub const H: usize = 32;
+
+const FONT: [u8; 80] = [
+    0xF0, 0x90, 0x90, 0x90, 0xF0, 0x20, 0x60, 0x20, 0x20, 0x70, 0xF0, 0x10, 0xF0, 0x80, 0xF0, 0xF0,
+    0x10, 0xF0, 0x10, 0xF0, 0x90, 0x90, 0xF0, 0x10, 0x10, 0xF0, 0x80, 0xF0, 0x10, 0xF0, 0xF0, 0x80,
+    0xF0, 0x90, 0xF0, 0xF0, 0x10, 0x20, 0x40, 0x40, 0xF0, 0x90, 0xF0, 0x90, 0xF0, 0xF0, 0x90, 0xF0,
+    0x10, 0xF0, 0xF0, 0x90, 0xF0, 0x90, 0x90, 0xE0, 0x90, 0xE0, 0x90, 0xE0, 0xF0, 0x80, 0x80, 0x80,
+    0xF0, 0xE0, 0x90, 0x90, 0x90, 0xE0, 0xF0, 0x80, 0xF0, 0x80, 0xF0, 0xF0, 0x80, 0xF0, 0x80, 0x80,
+];
+
+// Execution stopped: bad opcode, or the stack over/underflowed.
+#[derive(Debug, PartialEq, Eq)]
+pub struct Fault;
+
+pub struct RefMachine {
+    pub pc: usize,
+    pub i: usize,
+    pub v: [u8; 16],
+    pub sp: usize,
+    pub stack: [usize; 16],
+    pub ram: [u8; 4096],
+    pub screen: [bool; W * H],
+    pub keys: [bool; 16],
+    pub dt: u8,
+    pub st: u8,
+    pub quirks: Quirks,
+    pub random: u8,
+}
+
+impl RefMachine {
+    pub fn new(rom: &[u8], quirks: Quirks) -> RefMachine {
+        let mut ram = [0; 4096];
+        ram[..80].copy_from_slice(&FONT);
+        ram[0x200..0x200 + rom.len()].copy_from_slice(rom);
+        RefMachine {
+            pc: 0x200,
+            i: 0,
+            v: [0; 16],
+            sp: 0,
+            stack: [0; 16],
+            ram,
+            screen: [false; W * H],
+            keys: [false; 16],
+            dt: 0,
+            st: 0,
+            quirks,
+            random: 0,
+        }
+    }
+
+    pub fn tick_timers(&mut self) {
+        self.dt = self.dt.saturating_sub(1);
+        self.st = self.st.saturating_sub(1);
+    }
+
+    pub fn step(&mut self) -> Result<(), Fault> {
+        let start = self.pc;
+        let res = self.run_one();
+        if res.is_err() {
+            self.pc = start;
+        }
+        res
+    }
+
+    fn run_one(&mut self) -> Result<(), Fault> {
+        let op = ((self.ram[self.pc] as u16) << 8) | self.ram[(self.pc + 1) % 4096] as u16;
+        self.pc = (self.pc + 2) % 4096;
+
+        let x = ((op >> 8) & 0xF) as usize;
+        let y = ((op >> 4) & 0xF) as usize;
+        let n = (op & 0xF) as usize;
+        let nn = (op & 0xFF) as u8;
+        let nnn = (op & 0xFFF) as usize;
+
+        match op >> 12 {
+            0x0 if op == 0x00E0 => self.screen = [false; W * H],
+            0x0 if op == 0x00EE => {
+                if self.sp == 0 {
+                    return Err(Fault);
+                }
+                self.sp -= 1;
+                self.pc = self.stack[self.sp];
+            }
+            0x1 => self.pc = nnn,
+            0x2 => {
+                if self.sp == 16 {
+                    return Err(Fault);
+                }
+                self.stack[self.sp] = self.pc;
+                self.sp += 1;
+                self.pc = nnn;
+            }
+            0x3 => self.skip_if(self.v[x] == nn),
+            0x4 => self.skip_if(self.v[x] != nn),
+            0x5 if n == 0 => self.skip_if(self.v[x] == self.v[y]),
+            0x6 => self.v[x] = nn,
+            0x7 => self.v[x] = self.v[x].wrapping_add(nn),
+            0x8 => self.alu(x, y, n)?,
+            0x9 if n == 0 => self.skip_if(self.v[x] != self.v[y]),
+            0xA => self.i = nnn,
+            0xB => {
+                let reg = if self.quirks.jump_vx { x } else { 0 };
+                self.pc = (nnn + self.v[reg] as usize) % 4096;
+            }
+            0xC => self.v[x] = self.random & nn,
+            0xD => self.draw(x, y, n),
+            0xE if nn == 0x9E => self.skip_if(self.keys[(self.v[x] & 0xF) as usize]),
+            0xE if nn == 0xA1 => self.skip_if(!self.keys[(self.v[x] & 0xF) as usize]),
+            0xF => self.misc(x, nn)?,
+            _ => return Err(Fault),
+        }
+        Ok(())
+    }
+
+    fn skip_if(&mut self, cond: bool) {
+        if cond {
+            self.pc = (self.pc + 2) % 4096;
+        }
+    }
+
+    fn alu(&mut self, x: usize, y: usize, n: usize) -> Result<(), Fault> {
+        let (vx, vy) = (self.v[x], self.v[y]);
+        // VF is written last, so it wins when X is F.
+        let (result, flag) = match n {
+            0x0 => (vy, None),
+            0x1 => (vx | vy, self.quirks.vf_reset.then_some(0)),
+            0x2 => (vx & vy, self.quirks.vf_reset.then_some(0)),
+            0x3 => (vx ^ vy, self.quirks.vf_reset.then_some(0)),
+            0x4 => (
+                vx.wrapping_add(vy),
+                Some((vx as u16 + vy as u16 > 0xFF) as u8),
+            ),
+            0x5 => (vx.wrapping_sub(vy), Some((vx >= vy) as u8)),
+            0x7 => (vy.wrapping_sub(vx), Some((vy >= vx) as u8)),
+            0x6 => {
+                let src = if self.quirks.shift_vx { vx } else { vy };
+                (src >> 1, Some(src & 1))
+            }
+            0xE => {
+                let src = if self.quirks.shift_vx { vx } else { vy };
+                (src << 1, Some(src >> 7))
+            }
+            _ => return Err(Fault),
+        };
+        self.v[x] = result;
+        if let Some(f) = flag {
+            self.v[0xF] = f;
+        }
+        Ok(())
+    }
+
+    fn draw(&mut self, x: usize, y: usize, n: usize) {
+        let (mut x0, mut y0) = (self.v[x] as usize, self.v[y] as usize);
+        if self.quirks.clip_sprites {
+            x0 %= W;
+            y0 %= H;
+        }
+        let mut collision = false;
+        for row in 0..n {
+            let bits = self.ram[(self.i + row) % 4096];
+            for col in 0..8 {
+                if bits & (0x80 >> col) == 0 {
+                    continue;
+                }
+                let (px, py) = (x0 + col, y0 + row);
+                if self.quirks.clip_sprites && (px >= W || py >= H) {
+                    continue;
+                }
+                let idx = (py % H) * W + (px % W);
+                collision |= self.screen[idx];
+                self.screen[idx] = !self.screen[idx];
+            }
+        }
+        self.v[0xF] = collision as u8;
+    }
+
+    fn misc(&mut self, x: usize, nn: u8) -> Result<(), Fault> {
+        match nn {
+            0x07 => self.v[x] = self.dt,
+            0x0A => match self.keys.iter().position(|&k| k) {
+                Some(k) => self.v[x] = k as u8,
+                None => self.pc = (self.pc + 4096 - 2) % 4096,
+            },
+            0x15 => self.dt = self.v[x],
+            0x18 => self.st = self.v[x],
+            0x1E => self.i = (self.i + self.v[x] as usize) % 0x10000,
+            0x29 => self.i = (self.v[x] & 0xF) as usize * 5,
+            0x33 => {
+                let vx = self.v[x];
+                self.ram[self.i % 4096] = vx / 100;
+                self.ram[(self.i + 1) % 4096] = vx / 10 % 10;
+                self.ram[(self.i + 2) % 4096] = vx % 10;
+            }
+            0x55 => {
+                for r in 0..=x {
+                    self.ram[(self.i + r) % 4096] = self.v[r];
+                }
+                if self.quirks.memory_increment {
+                    self.i = (self.i + x + 1) % 0x10000;
+                }
+            }
+            0x65 => {
+                for r in 0..=x {
+                    self.v[r] = self.ram[(self.i + r) % 4096];
+                }
+                if self.quirks.memory_increment {
+                    self.i = (self.i + x + 1) % 0x10000;
+                }
+            }
+            _ => return Err(Fault),
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,122 @@
+// RomDb against a small programs.json in the chip-8-database format.
+use chip8_core::*;
+
+const FONT_GRID: &[u8] = include_bytes!("../../test_roms/font_grid.ch8");
+const ARITH: &[u8] = include_bytes!("../../test_roms/arith.ch8");
+const SUBROUTINES: &[u8] = include_bytes!("../../test_roms/subroutines.ch8");
+
+const PROGRAMS: &str = r##"[
+  {
+    "title": "Font grid",
+    "authors": ["r-ec8"],
+    "roms": {
+      "EC2DE7C0DEA7953011EE74516D05DA415DFB1578": {
+        "file": "font_grid.ch8",
+        "platforms": ["superchip", "originalChip8"],
+        "quirkyPlatforms": {
+          "superchip": { "wrap": true, "logic": true, "memoryIncrementByX": true },
+          "originalChip8": { "logic": false }
+        },
+        "colors": { "pixels": ["#101020", "#e0e0ff"], "buzzer": "#ff0000" },
+        "keys": { "up": 5, "down": 8, "a": 6 }
+      }
+    }
+  },
+  {
+    "title": "Arithmetic",
+    "roms": {
+      "0dfd8d652089c0967cf014ad2abfef206cc43859": {
+        "file": "arith.ch8",
+        "platforms": ["chip8e", "modernChip8"],
+        "tickrate": 500
+      }
+    }
+  },
+  {
+    "title": "Subroutines",
+    "roms": {
+      "f879e273be8ccf6f775e52ca8ae0aceb157aa945": {
+        "file": "subroutines.ch8",
+        "platforms": ["superchip"],
+        "quirkyPlatforms": {
+          "superchip": { "wrap": true, "logic": true }
+        }
+      }
+    }
+  }
+]"##;
+
+#[test]
+fn hash_is_lowercase_hex_sha1() {
+    assert_eq!(
+        sha1_hex(FONT_GRID),
+        "ec2de7c0dea7953011ee74516d05da415dfb1578"
+    );
+}
+
+#[test]
+fn finds_rom_and_applies_platform_overrides() {
+    let db = RomDb::from_json(PROGRAMS).unwrap();
+    assert_eq!(db.len(), 3);
+
+    // SUPER-CHIP is listed first but isn't emulated.
+    let info = db.lookup(FONT_GRID).unwrap();
+    assert_eq!(info.title, "Font grid");
+    assert_eq!(info.platform, Platform::OriginalChip8);
+    assert_eq!(
+        info.quirks,
+        Quirks {
+            vf_reset: false,
+            ..Quirks::CHIP8
+        }
+    );
+    assert_eq!(info.tickrate, Platform::OriginalChip8.tickrate());
+    assert_eq!(info.palette, vec![[0x10, 0x10, 0x20], [0xE0, 0xE0, 0xFF]]);
+    assert_eq!(
+        info.keys,
+        vec![("a".into(), 6), ("down".into(), 8), ("up".into(), 5)]
+    );
+}
+
+#[test]
+fn skips_unknown_platforms_and_keeps_rom_tickrate() {
+    let db = RomDb::from_json(PROGRAMS).unwrap();
+    let info = db.lookup(ARITH).unwrap();
+    assert_eq!(info.platform, Platform::ModernChip8);
+    // The database's modernChip8 profile, not a plain Emu's.
+    assert_eq!(
+        info.quirks,
+        Quirks {
+            vf_reset: false,
+            memory_increment: true,
+            shift_vx: false,
+            jump_vx: false,
+            clip_sprites: true,
+        }
+    );
+    assert_eq!(info.tickrate, 500);
+    assert!(info.palette.is_empty());
+    assert!(info.keys.is_empty());
+}
+
+#[test]
+fn falls_back_to_the_first_known_platform() {
+    let db = RomDb::from_json(PROGRAMS).unwrap();
+    let info = db.lookup(SUBROUTINES).unwrap();
+    assert_eq!(info.platform, Platform::SuperChip);
+    assert_eq!(
+        info.quirks,
+        Quirks {
+            vf_reset: true,
+            clip_sprites: false,
+            ..Quirks::SCHIP
+        }
+    );
+}
+
+#[test]
+fn unknown_rom_and_bad_json() {
+    let db = RomDb::from_json(PROGRAMS).unwrap();
+    assert_eq!(db.lookup(&[0x12, 0x00]), None);
+    assert!(RomDb::from_json("{\"title\": 1}").is_err());
+}
@@ -0,0 +1,54 @@
+// 0NNN machine code subroutines on the COSMAC VIP.
+use chip8_core::*;
+
+// `program` at 0x200 and 1802 `code` at 0x300.
+fn vip_emu(program: &[u8], code: &[u8]) -> Emu {
+    let mut rom = program.to_vec();
+    rom.resize(0x100, 0);
+    rom.extend_from_slice(code);
+    let mut emu = Emu::new(|| 0);
+    emu.set_platform(Platform::OriginalChip8);
+    emu.set_quirks(Platform::OriginalChip8.quirks());
+    emu.load(&rom).unwrap();
+    emu
+}
+
+#[test]
+fn subroutine_sees_interpreter_state() {
+    let mut emu = vip_emu(
+        &[
+            0x63, 0x29, // V3 = 29
+            0x03, 0x00, // call 0300 with R6 pointing at V3
+            0x64, 0x01, // V4 = 1
+        ],
+        &[
+            0x06, // LDN R6: D = V3
+            0xFC, 0x01, // ADI 1
+            0x56, // STR R6
+            0xF8, 0x03, 0xBA, // PHI RA
+            0xF8, 0x45, 0xAA, // PLO RA: I = 0345
+            0xF8, 0x80, 0x5B, // STR RB: top left pixel on
+            0xD4, // SEP R4: back to the interpreter
+        ],
+    );
+    for _ in 0..3 {
+        emu.tick().unwrap();
+    }
+    assert_eq!(emu.v_reg()[3], 0x2A);
+    assert_eq!(emu.v_reg()[4], 1);
+    assert_eq!(emu.i_reg(), 0x345);
+    assert!(emu.get_display()[0]);
+    assert_eq!(emu.pc(), 0x206);
+}
+
+#[test]
+fn reports_subroutines_that_never_return() {
+    let mut emu = vip_emu(&[0x03, 0x00], &[0x30, 0x00]); // BR 00, forever
+    assert_eq!(
+        emu.tick(),
+        Err(EmuError::MachineCodeTimeout {
+            addr: 0x300,
+            pc: 0x200
+        })
+    );
+}
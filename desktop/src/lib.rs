@@ -0,0 +1,359 @@
+use chip8_core::*;
+use sdl2::{
+    event::Event,
+    keyboard::Keycode,
+    pixels::{Color, PixelFormatEnum},
+    rect::Rect,
+    render::{Canvas, Texture},
+    video::Window,
+};
+use std::{
+    env, fs,
+    io::{self, BufRead},
+    path::Path,
+    sync::{Arc, mpsc},
+    thread,
+};
+use tools::{CheatConsole, Coverage, Profiler, cheat_file};
+
+const SCALE: u32 = 15;
+// MegaChip8's 256x192 pictures are scaled less.
+const MEGA_SCALE: u32 = 4;
+
+const TICK_PERFRAME: usize = 10;
+
+// The chip-8-database's programs.json is read from $CHIP8_DB, or from
+// DB_FILE in the working directory.
+const DB_VAR: &str = "CHIP8_DB";
+const DB_FILE: &str = "programs.json";
+// Platform id to use instead of the database's or the detected one, for
+// platforms ROMs can't be recognised as, like dream6800.
+const PLATFORM_VAR: &str = "CHIP8_PLATFORM";
+// Set to text or json to profile the game and print the report on exit.
+// Translated code from the recompiler runs past the profiler's hooks.
+const PROFILE_VAR: &str = "CHIP8_PROFILE";
+// File to merge a map of the memory the game touched into on exit, for the
+// disassembler. Like profiles, it misses what translated code does.
+const COVERAGE_VAR: &str = "CHIP8_COVERAGE";
+// Set to search for and add cheats by typing commands on stdin while the
+// game runs. Saved cheats are applied either way.
+const CHEATS_VAR: &str = "CHIP8_CHEATS";
+
+// Opens a window and plays `rom`, which may be an Octo cartridge. Unless the
+// ROM database knows the game, an extension like .sc8 on `name` decides its
+// platform. Each frame `step` is asked to run TICK_PERFRAME instructions, or
+// the game's tickrate from its cartridge, the ROM database or its platform;
+// the plain binary passes a tick loop, crates generated by the recompiler
+// pass their translated code.
+pub fn run<F>(name: &str, rom: &[u8], mut step: F)
+where
+    F: FnMut(&mut Emu, usize) -> Result<(), EmuError>,
+{
+    let mut chip8 = Emu::default();
+    let mut ticks = TICK_PERFRAME;
+    let mut colors = (Color::RGB(0, 0, 0), Color::RGB(255, 255, 255));
+    // Octo cartridges carry their source, to assemble, and how to run it.
+    let cartridge = if Cartridge::detect(rom) {
+        match load_cartridge(name, rom) {
+            Ok(cartridge) => Some(cartridge),
+            Err(e) => {
+                println!("Unable to load {}: {}", name, e);
+                return;
+            }
+        }
+    } else {
+        None
+    };
+    let rom = cartridge.as_ref().map_or(rom, |(rom, _)| &rom[..]);
+    let db = load_database();
+    let info = match &cartridge {
+        Some((_, info)) => Some(info),
+        None => db.as_ref().and_then(|db| db.lookup(rom)),
+    };
+    if let Ok(id) = env::var(PLATFORM_VAR) {
+        let Some(platform) = Platform::from_id(&id) else {
+            println!("Unknown platform {}", id);
+            return;
+        };
+        chip8.set_platform(platform);
+        chip8.set_quirks(platform.quirks());
+        ticks = platform.tickrate() as usize;
+    } else if let Some(info) = info {
+        println!("{} ({})", info.title, info.platform.id());
+        chip8.set_platform(info.platform);
+        chip8.set_quirks(info.quirks);
+        ticks = info.tickrate as usize;
+        if let [back, fore, ..] = info.palette[..] {
+            colors = (
+                Color::RGB(back[0], back[1], back[2]),
+                Color::RGB(fore[0], fore[1], fore[2]),
+            );
+        }
+        for (button, key) in &info.keys {
+            println!("  {}: CHIP-8 key {:X}", button, key);
+        }
+    } else if let Some(platform) = Platform::from_file_name(name) {
+        println!("{} by its extension", platform.id());
+        chip8.set_platform(platform);
+        chip8.set_quirks(platform.quirks());
+        ticks = platform.tickrate() as usize;
+    } else if let [best, ..] = Platform::classify(rom)
+        && best.platform != Platform::ModernChip8
+    {
+        print!(
+            "Looks like {} ({:.0}% sure)",
+            best.platform.id(),
+            best.confidence * 100.0
+        );
+        if best.platform.emulated() {
+            println!();
+            chip8.set_platform(best.platform);
+            chip8.set_quirks(best.platform.quirks());
+            ticks = best.platform.tickrate() as usize;
+        } else {
+            println!(", which isn't emulated");
+        }
+    }
+    let profile = env::var(PROFILE_VAR).ok();
+    let profiler = Arc::new(Profiler::new());
+    if profile.is_some() {
+        chip8.add_observer(profiler.clone());
+    }
+    let coverage_file = env::var(COVERAGE_VAR).ok();
+    let coverage = Arc::new(Coverage::new());
+    if coverage_file.is_some() {
+        chip8.add_observer(coverage.clone());
+    }
+    if let Err(e) = chip8.load(rom) {
+        println!("Unable to load {}: {}", name, e);
+        return;
+    }
+    // Cheats live next to the ROM.
+    let mut cheats = match CheatConsole::new(cheat_file(Path::new(name))) {
+        Ok(console) => {
+            console.cheats.patch(&mut chip8);
+            Some(console)
+        }
+        Err(e) => {
+            println!("Cheats disabled: {}", e);
+            None
+        }
+    };
+    let commands = (cheats.is_some() && env::var_os(CHEATS_VAR).is_some()).then(|| {
+        println!("Cheat finder: type help for its commands");
+        let (command_tx, commands) = mpsc::channel();
+        thread::spawn(move || {
+            for line in io::stdin().lock().lines().map_while(Result::ok) {
+                if command_tx.send(line).is_err() {
+                    break;
+                }
+            }
+        });
+        commands
+    });
+
+    // Setup SDL
+    let (width, height) = if chip8.platform() == Platform::MegaChip8 {
+        (
+            MEGA_SCREEN_W as u32 * MEGA_SCALE,
+            MEGA_SCREEN_H as u32 * MEGA_SCALE,
+        )
+    } else {
+        let (width, height) = chip8.screen_size();
+        (width as u32 * SCALE, height as u32 * SCALE)
+    };
+    let sdl_context = sdl2::init().unwrap();
+    let video_subsystem = sdl_context.video().unwrap();
+    let window = video_subsystem
+        .window("Chip-8 Emulator", width, height)
+        .position_centered()
+        .opengl()
+        .build()
+        .unwrap();
+
+    let mut canvas = window.into_canvas().present_vsync().build().unwrap();
+    canvas.clear();
+    canvas.present();
+    let texture_creator = canvas.texture_creator();
+    let mut texture = texture_creator
+        .create_texture_streaming(
+            PixelFormatEnum::RGB24,
+            MEGA_SCREEN_W as u32,
+            MEGA_SCREEN_H as u32,
+        )
+        .unwrap();
+
+    let mut event_pump = sdl_context.event_pump().unwrap();
+    let keypad = chip8.platform().keypad();
+    'gameloop: loop {
+        for evt in event_pump.poll_iter() {
+            match evt {
+                Event::Quit { .. }
+                | Event::KeyDown {
+                    keycode: Some(Keycode::Escape),
+                    ..
+                } => {
+                    break 'gameloop;
+                }
+                Event::KeyDown {
+                    keycode: Some(key), ..
+                } => {
+                    if let Some(k) = key2btn(key, keypad) {
+                        chip8.keypress(k, true);
+                    }
+                    if let Some(k) = key2btn2(key, keypad) {
+                        chip8.keypress2(k, true);
+                    }
+                }
+                Event::KeyUp {
+                    keycode: Some(key), ..
+                } => {
+                    if let Some(k) = key2btn(key, keypad) {
+                        chip8.keypress(k, false);
+                    }
+                    if let Some(k) = key2btn2(key, keypad) {
+                        chip8.keypress2(k, false);
+                    }
+                }
+                _ => (),
+            }
+        }
+        if let Err(e) = step(&mut chip8, ticks) {
+            println!("Emulation stopped: {}", e);
+            break 'gameloop;
+        }
+        if let Some(cheats) = &mut cheats {
+            for line in commands.iter().flat_map(|commands| commands.try_iter()) {
+                println!("{}", cheats.command(&line, &mut chip8));
+            }
+            cheats.cheats.freeze(&mut chip8);
+        }
+        chip8.tick_timers();
+        match chip8.frame() {
+            Frame::Color(picture) => draw_framebuffer(picture, &mut canvas, &mut texture),
+            Frame::Mono {
+                pixels,
+                width,
+                height,
+                colors: layer,
+            } => draw_screen(pixels, (width, height), layer, &mut canvas, colors),
+        }
+    }
+    match profile.as_deref() {
+        Some("json") => println!("{}", profiler.report().to_json()),
+        Some(_) => print!("{}", profiler.report()),
+        None => (),
+    }
+    if let Some(file) = coverage_file
+        && let Err(e) = coverage.map().save_merged(file.as_ref())
+    {
+        println!("Unable to save {}: {}", file, e);
+    }
+}
+fn load_database() -> Option<RomDb> {
+    let path = env::var(DB_VAR).unwrap_or_else(|_| DB_FILE.to_string());
+    let json = fs::read_to_string(&path).ok()?;
+    match RomDb::from_json(&json) {
+        Ok(db) => Some(db),
+        Err(e) => {
+            println!("Ignoring {}: {}", path, e);
+            None
+        }
+    }
+}
+
+// The assembled program of the cartridge `gif` and its settings, titled
+// after the file.
+fn load_cartridge(name: &str, gif: &[u8]) -> Result<(Vec<u8>, RomInfo), String> {
+    let mut cartridge = Cartridge::decode(gif).map_err(|e| e.to_string())?;
+    let rom = cartridge.rom().map_err(|e| e.to_string())?;
+    let title = Path::new(name).file_stem().unwrap_or_default();
+    cartridge.info.title = title.to_string_lossy().into_owned();
+    Ok((rom, cartridge.info))
+}
+
+// CHIP-8X games are drawn in their own VP-590 colours instead of `colors`.
+fn draw_screen(
+    screen_buf: &[bool],
+    (width, height): (usize, usize),
+    layer: Option<&ColorLayer>,
+    canvas: &mut Canvas<Window>,
+    (background, foreground): (Color, Color),
+) {
+    let rgb = |[r, g, b]: [u8; 3]| Color::RGB(r, g, b);
+    canvas.set_draw_color(layer.map_or(background, |l| rgb(l.background())));
+    canvas.clear();
+
+    // Stretched to the window, which is only bigger than SCALE allows on
+    // MegaChip8 before it switches to its own screen.
+    let (window_w, window_h) = canvas.output_size().unwrap();
+    let (scale_x, scale_y) = (window_w / width as u32, window_h / height as u32);
+
+    canvas.set_draw_color(foreground);
+
+    for (i, pixel) in screen_buf.iter().enumerate() {
+        if *pixel {
+            let x = (i % width) as u32;
+            let y = (i / width) as u32;
+            if let Some(layer) = layer {
+                canvas.set_draw_color(rgb(layer.foreground(x as usize, y as usize)));
+            }
+
+            let rect = Rect::new((x * scale_x) as i32, (y * scale_y) as i32, scale_x, scale_y);
+            canvas.fill_rect(rect).unwrap();
+        }
+    }
+    canvas.present();
+}
+fn draw_framebuffer(picture: &Framebuffer, canvas: &mut Canvas<Window>, texture: &mut Texture) {
+    let bytes: Vec<u8> = picture.pixels().iter().flatten().copied().collect();
+    texture.update(None, &bytes, picture.width() * 3).unwrap();
+    canvas.copy(texture, None, None).unwrap();
+    canvas.present();
+}
+// Keys by position on the keypad, see Platform::keypad.
+fn key2btn(key: Keycode, keypad: [[u8; 4]; 4]) -> Option<usize> {
+    let (row, col) = match key {
+        Keycode::Num1 => (0, 0),
+        Keycode::Num2 => (0, 1),
+        Keycode::Num3 => (0, 2),
+        Keycode::Num4 => (0, 3),
+        Keycode::Q => (1, 0),
+        Keycode::W => (1, 1),
+        Keycode::E => (1, 2),
+        Keycode::R => (1, 3),
+        Keycode::A => (2, 0),
+        Keycode::S => (2, 1),
+        Keycode::D => (2, 2),
+        Keycode::F => (2, 3),
+        Keycode::Z => (3, 0),
+        Keycode::X => (3, 1),
+        Keycode::C => (3, 2),
+        Keycode::V => (3, 3),
+        _ => return None,
+    };
+    Some(keypad[row][col] as usize)
+}
+// CHIP-8X's second keypad, laid out like the first on the numeric keypad.
+fn key2btn2(key: Keycode, keypad: [[u8; 4]; 4]) -> Option<usize> {
+    let (row, col) = match key {
+        Keycode::Kp7 => (0, 0),
+        Keycode::Kp8 => (0, 1),
+        Keycode::Kp9 => (0, 2),
+        Keycode::KpDivide => (0, 3),
+        Keycode::Kp4 => (1, 0),
+        Keycode::Kp5 => (1, 1),
+        Keycode::Kp6 => (1, 2),
+        Keycode::KpMultiply => (1, 3),
+        Keycode::Kp1 => (2, 0),
+        Keycode::Kp2 => (2, 1),
+        Keycode::Kp3 => (2, 2),
+        Keycode::KpMinus => (2, 3),
+        Keycode::Kp0 => (3, 0),
+        Keycode::KpPeriod => (3, 1),
+        Keycode::KpEnter => (3, 2),
+        Keycode::KpPlus => (3, 3),
+        _ => return None,
+    };
+    Some(keypad[row][col] as usize)
+}
@@ -1,115 +1,68 @@
-use chip8_core::*;
-use sdl2::{
-    event::Event, keyboard::Keycode, pixels::Color, rect::Rect, render::Canvas, video::Window,
-};
-use std::{env, fs::File, io::Read};
-
-const SCALE: u32 = 15;
-const WINDOW_W: u32 = (SCREEN_W as u32) * SCALE;
-const WINDOW_H: u32 = (SCREEN_H as u32) * SCALE;
-
-const TICK_PERFRAME: usize = 10;
+use chip8_core::{Emu, EmuError, RomPack};
+use std::{env, path::Path};
+use tools::read_rom;
 
 fn main() {
-    let args: Vec<_> = env::args().collect();
-    if args.len() != 2 {
-        println!("Usage: cargo run path/to/game");
-        return;
-    }
-    // Setup SDL
-    let sdl_context = sdl2::init().unwrap();
-    let video_subsystem = sdl_context.video().unwrap();
-    let window = video_subsystem
-        .window("Chip-8 Emulator", WINDOW_W, WINDOW_H)
-        .position_centered()
-        .opengl()
-        .build()
-        .unwrap();
-
-    let mut canvas = window.into_canvas().present_vsync().build().unwrap();
-    canvas.clear();
-    canvas.present();
-
-    let mut chip8 = Emu::default();
-    let mut rom = File::open(&args[1]).expect("Unable to open file");
-
-    let mut buffer = Vec::new();
-
-    rom.read_to_end(&mut buffer).unwrap();
-    chip8.load(&buffer);
-
-    let mut event_pump = sdl_context.event_pump().unwrap();
-    'gameloop: loop {
-        for evt in event_pump.poll_iter() {
-            match evt {
-                Event::Quit { .. }
-                | Event::KeyDown {
-                    keycode: Some(Keycode::Escape),
-                    ..
-                } => {
-                    break 'gameloop;
-                }
-                Event::KeyDown {
-                    keycode: Some(key), ..
-                } => {
-                    if let Some(k) = key2btn(key) {
-                        chip8.keypress(k, true);
-                    }
-                }
-                Event::KeyUp {
-                    keycode: Some(key), ..
-                } => {
-                    if let Some(k) = key2btn(key) {
-                        chip8.keypress(k, false);
-                    }
-                }
-                _ => (),
-            }
+    let mut args: Vec<_> = env::args().skip(1).collect();
+    let list = match args.iter().position(|arg| arg == "--list") {
+        Some(idx) => {
+            args.remove(idx);
+            true
         }
-        for _ in 0..TICK_PERFRAME {
-            chip8.tick();
+        None => false,
+    };
+    // A zip pack's ROM is picked by its index or name.
+    let (path, pick) = match &args[..] {
+        [path] => (path, None),
+        [path, pick] => (path, Some(pick)),
+        _ => {
+            println!("Usage: cargo run [--list] path/to/game [ROM in a zip]");
+            return;
         }
-        chip8.tick_timers();
-        draw_screen(&chip8, &mut canvas);
+    };
+    // A game.bps or game.ips next to the game is applied on the way in.
+    let (buffer, patch) = read_rom(path.as_ref()).expect("Unable to open file");
+    if let Some(patch) = patch {
+        println!("Patched with {}", patch.display());
+    }
+    if !RomPack::detect(&buffer) {
+        if list {
+            println!("{} is not a zip archive", path);
+            return;
+        }
+        desktop::run(path, &buffer, tick);
+        return;
     }
-}
-fn draw_screen(emu: &Emu, canvas: &mut Canvas<Window>) {
-    canvas.set_draw_color(Color::RGB(0, 0, 0));
-    canvas.clear();
-
-    let screen_buf = emu.get_display();
-
-    canvas.set_draw_color(Color::RGB(255, 255, 255));
-
-    for (i, pixel) in screen_buf.iter().enumerate() {
-        if *pixel {
-            let x = (i % SCREEN_W) as u32;
-            let y = (i / SCREEN_W) as u32;
 
-            let rect = Rect::new((x * SCALE) as i32, (y * SCALE) as i32, SCALE, SCALE);
-            canvas.fill_rect(rect).unwrap();
+    let pack = match RomPack::open(&buffer) {
+        Ok(pack) => pack,
+        Err(e) => {
+            println!("Unable to open {}: {}", path, e);
+            return;
         }
+    };
+    if list {
+        print!("{}", pack);
+        return;
     }
-    canvas.present();
+    let rom = match (pick, &pack.roms[..]) {
+        (Some(pick), _) => pack.find(pick),
+        (None, [rom]) => Some(rom),
+        (None, _) => None,
+    };
+    let Some(rom) = rom else {
+        println!("Pick one of the ROMs in {}:", path);
+        print!("{}", pack);
+        return;
+    };
+    // Named as if it sat next to the archive, which is where its cheats go.
+    let name = Path::new(path).with_file_name(rom.file_name());
+    desktop::run(&name.to_string_lossy(), &rom.data, tick);
 }
-fn key2btn(key: Keycode) -> Option<usize> {
-    match key {
-        Keycode::Num1 => Some(0x1),
-        Keycode::Num2 => Some(0x2),
-        Keycode::Num3 => Some(0x3),
-        Keycode::Num4 => Some(0xC),
-        Keycode::Q => Some(0x4),
-        Keycode::W => Some(0x5),
-        Keycode::E => Some(0x6),
-        Keycode::R => Some(0xD),
-        Keycode::A => Some(0x7),
-        Keycode::S => Some(0x8),
-        Keycode::D => Some(0x9),
-        Keycode::F => Some(0xE),
-        Keycode::Z => Some(0xA),
-        Keycode::X => Some(0x0),
-        Keycode::C => Some(0xB),
-        Keycode::V => Some(0xF),
-        _ => None,
+
+fn tick(emu: &mut Emu, ticks: usize) -> Result<(), EmuError> {
+    for _ in 0..ticks {
+        emu.tick()?;
     }
+    Ok(())
 }
@@ -0,0 +1,16 @@
+#![no_main]
+// A ROM and an IPS or BPS patch, split at the first byte's offset.
+use chip8_core::*;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    let Some((&split, rest)) = data.split_first() else {
+        return;
+    };
+    let (rom, patch) = rest.split_at((split as usize).min(rest.len()));
+    if let Ok(patched) = apply_patch(rom, patch) {
+        // IPS offsets are 24 bits with records of at most 64K; BPS results
+        // are capped at 16M.
+        assert!(patched.len() <= 1 << 25);
+    }
+});
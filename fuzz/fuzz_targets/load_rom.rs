@@ -0,0 +1,18 @@
+#![no_main]
+// Raw bytes straight into Emu::load, then run whatever was loaded.
+use chip8_core::*;
+use chip8_core_fuzz::*;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    let mut emu = new_emu();
+    match emu.load(data) {
+        Ok(()) => run(&mut emu, MAX_TICKS),
+        // An empty ROM has nothing at its entry point.
+        Err(e) => assert!(
+            data.is_empty() || data.len() > MAX_ROM_SIZE,
+            "rejected a ROM that fits: {}",
+            e
+        ),
+    }
+});
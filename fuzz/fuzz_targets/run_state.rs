@@ -0,0 +1,60 @@
+#![no_main]
+// A ROM, a random starting state and a sequence of key/timer events.
+use arbitrary::Arbitrary;
+use chip8_core::*;
+use chip8_core_fuzz::*;
+use libfuzzer_sys::fuzz_target;
+
+#[derive(Debug, Arbitrary)]
+struct State {
+    pc: u16,
+    i_reg: u16,
+    v_reg: [u8; NUM_REGISTERS],
+    dt: u8,
+    st: u8,
+}
+
+#[derive(Debug, Arbitrary)]
+enum Event {
+    Key { idx: u8, pressed: bool },
+    Ticks(u8),
+    Timers,
+}
+
+#[derive(Debug, Arbitrary)]
+struct Input {
+    state: State,
+    events: Vec<Event>,
+    rom: Vec<u8>,
+}
+
+fuzz_target!(|input: Input| {
+    let mut emu = new_emu();
+    if emu.load(&input.rom).is_err() {
+        return;
+    }
+    let State {
+        pc,
+        i_reg,
+        v_reg,
+        dt,
+        st,
+    } = input.state;
+    emu.set_pc(pc);
+    emu.set_i_reg(i_reg.into());
+    for (x, val) in v_reg.into_iter().enumerate() {
+        emu.set_v_reg(x, val);
+    }
+    emu.set_timers(dt, st);
+
+    let screen_len = emu.get_display().len();
+    check_invariants(&emu, screen_len);
+    for event in input.events {
+        match event {
+            Event::Key { idx, pressed } => emu.keypress(idx as usize % NUM_KEYS, pressed),
+            Event::Ticks(n) => run(&mut emu, n as usize),
+            Event::Timers => emu.tick_timers(),
+        }
+        check_invariants(&emu, screen_len);
+    }
+});
@@ -0,0 +1,10 @@
+#!/bin/bash
+# Seed every fuzz target's corpus with the ROMs in test_roms/.
+cd "$(dirname "$0")"
+
+for target in load_rom run_state; do
+    mkdir -p "corpus/$target"
+    cp ../test_roms/*.ch8 "corpus/$target/"
+done
+
+echo "Seeded corpus from $(ls ../test_roms/*.ch8 | wc -l) test ROMs."
@@ -0,0 +1,38 @@
+// Shared helpers for the fuzz targets.
+use chip8_core::*;
+
+// Upper bound on ticks per input, so looping ROMs still finish quickly.
+pub const MAX_TICKS: usize = 2048;
+
+// CXNN gets a fixed byte, so a crashing input replays the same way.
+pub fn new_emu() -> Emu {
+    Emu::new(|| 0xA5)
+}
+
+// Properties that must hold after every tick, whatever the ROM does.
+pub fn check_invariants(emu: &Emu, screen_len: usize) {
+    assert!(
+        (emu.pc() as usize) < RAM_SIZE,
+        "pc {:#X} outside RAM",
+        emu.pc()
+    );
+    assert!(
+        emu.sp() as usize <= STACK_SIZE,
+        "sp {} beyond stack size",
+        emu.sp()
+    );
+    assert_eq!(emu.get_display().len(), screen_len, "screen size changed");
+}
+
+// Run until MAX_TICKS, checking invariants after each step. Errors such as
+// invalid opcodes are expected from random input; only panics are bugs.
+pub fn run(emu: &mut Emu, ticks: usize) {
+    let screen_len = emu.get_display().len();
+    for n in 0..ticks.min(MAX_TICKS) {
+        let _ = emu.tick();
+        if n % 10 == 9 {
+            emu.tick_timers();
+        }
+        check_invariants(emu, screen_len);
+    }
+}
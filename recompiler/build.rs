@@ -0,0 +1,23 @@
+// Translates every ROM in test_roms/ into $OUT_DIR/<name>.rs for the tests.
+use std::{env, fs, path::Path};
+
+#[path = "src/translate.rs"]
+mod translate;
+
+fn main() {
+    let roms = Path::new("../test_roms");
+    let out = env::var("OUT_DIR").unwrap();
+    println!("cargo::rerun-if-changed=src/translate.rs");
+    println!("cargo::rerun-if-changed={}", roms.display());
+    for entry in fs::read_dir(roms).unwrap() {
+        let path = entry.unwrap().path();
+        if path.extension().is_none_or(|ext| ext != "ch8") {
+            continue;
+        }
+        let rom = fs::read(&path).unwrap();
+        let name = path.file_name().unwrap().to_string_lossy();
+        let module = translate::translate(&rom, &name).unwrap();
+        let stem = path.file_stem().unwrap().to_string_lossy();
+        fs::write(Path::new(&out).join(format!("{}.rs", stem)), module).unwrap();
+    }
+}
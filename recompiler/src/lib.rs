@@ -0,0 +1,65 @@
+// Ahead-of-time recompiler: turns a ROM into a crate that plays it in the
+// desktop frontend, running translated code instead of the interpreter.
+use std::{fs, io, path::Path};
+
+mod translate;
+
+pub use translate::translate;
+
+// Package name for a ROM file, e.g. "Space Invaders.ch8" -> "space_invaders".
+pub fn crate_name(rom_path: &Path) -> String {
+    let stem = rom_path
+        .file_stem()
+        .map(|s| s.to_string_lossy())
+        .unwrap_or_default();
+    let mut name: String = stem
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() {
+                c.to_ascii_lowercase()
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    if !name.starts_with(|c: char| c.is_ascii_alphabetic()) {
+        name.insert_str(0, "rom_");
+    }
+    name
+}
+
+// Writes Cargo.toml, src/main.rs and the translated src/rom.rs to `dir`. The
+// crate depends on this workspace's chip8_core and desktop by path.
+pub fn write_crate(dir: &Path, name: &str, module: &str) -> io::Result<()> {
+    let workspace = Path::new(env!("CARGO_MANIFEST_DIR"))
+        .parent()
+        .expect("recompiler lives inside the workspace");
+    let manifest = format!(
+        "[package]
+name = {name:?}
+version = \"0.1.0\"
+edition = \"2024\"
+
+[dependencies]
+chip8_core = {{ path = {core:?} }}
+desktop = {{ path = {desktop:?} }}
+
+# Not a member of the emulator's workspace.
+[workspace]
+",
+        core = workspace.join("chip8_core"),
+        desktop = workspace.join("desktop"),
+    );
+    let main = format!(
+        "mod rom;
+
+fn main() {{
+    desktop::run({name:?}, rom::ROM, rom::run);
+}}
+"
+    );
+    fs::create_dir_all(dir.join("src"))?;
+    fs::write(dir.join("Cargo.toml"), manifest)?;
+    fs::write(dir.join("src/main.rs"), main)?;
+    fs::write(dir.join("src/rom.rs"), module)
+}
@@ -0,0 +1,31 @@
+use recompiler::*;
+use std::{env, fs, path::Path};
+
+fn main() {
+    let args: Vec<_> = env::args().collect();
+    if args.len() != 3 {
+        println!("Usage: cargo run -p recompiler path/to/game path/to/new_crate");
+        return;
+    }
+    let rom_path = Path::new(&args[1]);
+    let rom = fs::read(rom_path).expect("Unable to open file");
+    let name = crate_name(rom_path);
+    let file_name = rom_path.file_name().unwrap_or_default().to_string_lossy();
+    let module = match translate(&rom, &file_name) {
+        Ok(module) => module,
+        Err(e) => {
+            println!("Unable to translate {}: {}", &args[1], e);
+            return;
+        }
+    };
+    let dir = Path::new(&args[2]);
+    if let Err(e) = write_crate(dir, &name, &module) {
+        println!("Unable to write {}: {}", dir.display(), e);
+        return;
+    }
+    println!(
+        "Wrote {}. Play it with: cargo run --release --manifest-path {}",
+        name,
+        dir.join("Cargo.toml").display()
+    );
+}
@@ -0,0 +1,348 @@
+// Ahead-of-time translation of a ROM into Rust source.
+//
+// Code reachable from 0x200 is split into basic blocks and each block becomes
+// one function over the Emu. Register instructions are written out as Rust;
+// the rest run through Emu::tick, so the generated code shares every rule
+// with the interpreter. run() falls back to the interpreter wherever there is
+// no block: targets of BNNN and RET, code that was never reached statically,
+// and blocks whose bytes have been overwritten since the ROM was loaded.
+use chip8_core::{EmuError, Instruction, MAX_ROM_SIZE, RAM_SIZE};
+use std::collections::{BTreeMap, BTreeSet};
+
+const START_ADDR: u16 = 0x200;
+// Frontends run about 10 instructions per frame and a block only runs when
+// it fits in what is left of the frame, so keep them shorter than that.
+const MAX_BLOCK_LEN: usize = 8;
+
+// How an instruction is translated.
+#[derive(PartialEq)]
+enum Kind {
+    // Written out as Rust.
+    Inline,
+    // Run by Emu::tick, the block carries on after it.
+    Interpreted,
+    // Run by Emu::tick and ends the block, because it moves pc or may write
+    // over code.
+    InterpretedExit,
+}
+
+fn kind(ins: Instruction) -> Kind {
+    use Instruction::*;
+    match ins {
+        LoadImm(..) | AddImm(..) | Move(..) | Or(..) | And(..) | Xor(..) | Add(..) | Sub(..)
+        | ShiftRight(..) | SubN(..) | ShiftLeft(..) | LoadI(_) | AddI(_) | Jump(_)
+        | SkipEqImm(..) | SkipNeImm(..) | SkipEqReg(..) | SkipNeReg(..) => Kind::Inline,
+        Cls | Random(..) | Draw(..) | GetDelay(_) | SetDelay(_) | SetSound(_) | Font(_)
+        | Load(_) => Kind::Interpreted,
+        Ret | Call(_) | JumpOffset(..) | SkipKey(_) | SkipNotKey(_) | WaitKey(_) | Bcd(_)
+        | Store(_) | Invalid(_) => Kind::InterpretedExit,
+        // Other platforms' opcodes only come from Instruction::decode_for,
+        // and translation targets plain CHIP-8.
+        _ => Kind::InterpretedExit,
+    }
+}
+
+fn after(addr: u16, step: u16) -> u16 {
+    (addr + step) % RAM_SIZE as u16
+}
+
+// Where control can go after `ins`, and whether it ends a block.
+fn flow(addr: u16, ins: Instruction) -> (Vec<u16>, bool) {
+    use Instruction::*;
+    match ins {
+        Jump(nnn) => (vec![nnn], true),
+        Call(nnn) => (vec![nnn, after(addr, 2)], true),
+        SkipEqImm(..) | SkipNeImm(..) | SkipEqReg(..) | SkipNeReg(..) | SkipKey(_)
+        | SkipNotKey(_) => (vec![after(addr, 2), after(addr, 4)], true),
+        Ret | JumpOffset(..) | Invalid(_) => (vec![], true),
+        Bcd(_) | Store(_) | WaitKey(_) => (vec![after(addr, 2)], true),
+        _ => (vec![after(addr, 2)], false),
+    }
+}
+
+struct Block {
+    start: u16,
+    ops: Vec<(u16, u16, Instruction)>,
+    // pc to set after the last instruction, unless that instruction sets it.
+    next: Option<u16>,
+}
+
+fn find_blocks(rom: &[u8]) -> Vec<Block> {
+    let fetch = |addr: u16| {
+        let offset = addr.checked_sub(START_ADDR)? as usize;
+        let bytes = rom.get(offset..offset + 2)?;
+        let op = u16::from_be_bytes([bytes[0], bytes[1]]);
+        Some((op, Instruction::decode(op)))
+    };
+
+    // Everything reachable from the entry point, and where blocks start.
+    let mut code = BTreeMap::new();
+    let mut leaders = BTreeSet::from([START_ADDR]);
+    let mut work = vec![START_ADDR];
+    while let Some(addr) = work.pop() {
+        if code.contains_key(&addr) {
+            continue;
+        }
+        let Some((op, ins)) = fetch(addr) else {
+            continue;
+        };
+        code.insert(addr, (op, ins));
+        if let Instruction::Invalid(_) = ins {
+            continue;
+        }
+        let (succ, ends) = flow(addr, ins);
+        if ends {
+            leaders.extend(&succ);
+        }
+        work.extend(succ);
+    }
+
+    let mut blocks = Vec::new();
+    let mut pending: Vec<u16> = leaders.iter().rev().copied().collect();
+    while let Some(start) = pending.pop() {
+        let Some(&(_, first)) = code.get(&start) else {
+            continue;
+        };
+        if let Instruction::Invalid(_) = first {
+            continue;
+        }
+        let mut ops = Vec::new();
+        let mut addr = start;
+        let next = loop {
+            let (op, ins) = code[&addr];
+            ops.push((addr, op, ins));
+            if flow(addr, ins).1 {
+                break None;
+            }
+            let next = after(addr, 2);
+            let more = code
+                .get(&next)
+                .is_some_and(|&(_, ins)| !matches!(ins, Instruction::Invalid(_)));
+            if !more || leaders.contains(&next) {
+                break Some(next);
+            }
+            if ops.len() == MAX_BLOCK_LEN {
+                if leaders.insert(next) {
+                    pending.push(next);
+                }
+                break Some(next);
+            }
+            addr = next;
+        };
+        blocks.push(Block { start, ops, next });
+    }
+    blocks.sort_by_key(|b| b.start);
+    blocks
+}
+
+// Generated source, plus whether `v` currently borrows the registers. Any
+// other call on emu ends that borrow, so it is taken again before the next
+// register access.
+struct Writer {
+    out: String,
+    borrowed: bool,
+}
+
+impl Writer {
+    fn line(&mut self, text: impl AsRef<str>) {
+        self.out.push_str(text.as_ref());
+        self.out.push('\n');
+    }
+
+    fn regs(&mut self) {
+        if !self.borrowed {
+            self.line("    let v = emu.v_reg_mut();");
+            self.borrowed = true;
+        }
+    }
+
+    fn call(&mut self, text: impl AsRef<str>) {
+        self.line(text);
+        self.borrowed = false;
+    }
+}
+
+// Rust source for a module with `pub const ROM` and
+// `pub fn run(emu: &mut Emu, ticks: usize) -> Result<(), EmuError>`, which
+// behaves like calling emu.tick() `ticks` times on an Emu that loaded ROM.
+pub fn translate(rom: &[u8], name: &str) -> Result<String, EmuError> {
+    if rom.len() > MAX_ROM_SIZE {
+        return Err(EmuError::RomTooLarge {
+            size: rom.len(),
+            max: MAX_ROM_SIZE,
+        });
+    }
+    let blocks = find_blocks(rom);
+    let mut w = Writer {
+        out: String::new(),
+        borrowed: false,
+    };
+    w.line(format!(
+        "// Translated from {} by the recompiler, do not edit.",
+        name
+    ));
+    w.line("use chip8_core::{Emu, EmuError};");
+    w.line("");
+    w.line("pub const ROM: &[u8] = &[");
+    for chunk in rom.chunks(16) {
+        let bytes: Vec<_> = chunk.iter().map(|b| format!("0x{:02X},", b)).collect();
+        w.line(format!("    {}", bytes.join(" ")));
+    }
+    w.line("];");
+    w.line("");
+    w.line("// Same as calling emu.tick() `ticks` times, stopping at the first error.");
+    w.line("pub fn run(emu: &mut Emu, ticks: usize) -> Result<(), EmuError> {");
+    w.line("    let mut left = ticks;");
+    w.line("    while left > 0 {");
+    w.line("        let done = match emu.pc() {");
+    for b in &blocks {
+        let len = b.ops.len();
+        w.line(format!(
+            "            0x{:03X} if fits(emu, left, 0x{:03X}, {}) => block_{:03x}(emu).map(|()| {}),",
+            b.start, b.start, len, b.start, len
+        ));
+    }
+    w.line("            _ => emu.tick().map(|()| 1),");
+    w.line("        }?;");
+    w.line("        left -= done;");
+    w.line("    }");
+    w.line("    Ok(())");
+    w.line("}");
+    if !blocks.is_empty() {
+        w.line("");
+        w.line("// A block runs if it fits in the ticks left and RAM still holds its code.");
+        w.line("fn fits(emu: &Emu, left: usize, addr: usize, len: usize) -> bool {");
+        w.line(format!(
+            "    let rom = addr - 0x{:03X}..addr - 0x{:03X} + 2 * len;",
+            START_ADDR, START_ADDR
+        ));
+        w.line("    left >= len && emu.ram()[addr..addr + 2 * len] == ROM[rom]");
+        w.line("}");
+    }
+    for b in &blocks {
+        w.line("");
+        emit_block(&mut w, b);
+    }
+    Ok(w.out)
+}
+
+fn emit_block(w: &mut Writer, b: &Block) {
+    use Instruction::*;
+    w.line(format!(
+        "fn block_{:03x}(emu: &mut Emu) -> Result<(), EmuError> {{",
+        b.start
+    ));
+    let uses_quirks = b.ops.iter().any(|&(_, _, ins)| {
+        matches!(
+            ins,
+            Or(..) | And(..) | Xor(..) | ShiftRight(..) | ShiftLeft(..)
+        )
+    });
+    if uses_quirks {
+        w.line("    let quirks = emu.quirks();");
+    }
+    w.borrowed = false;
+    // Inline code leaves pc alone, so it only has to be set for Emu::tick
+    // when the previous instruction wasn't interpreted too.
+    let mut pc = None;
+    for &(addr, op, ins) in &b.ops {
+        w.line(format!("    // {:03X}: {:04X}", addr, op));
+        if kind(ins) != Kind::Inline {
+            if pc != Some(addr) {
+                w.call(format!("    emu.set_pc(0x{:03X});", addr));
+            }
+            w.call("    emu.tick()?;");
+            pc = Some(after(addr, 2));
+            continue;
+        }
+        pc = None;
+        match ins {
+            LoadImm(x, nn) => {
+                w.regs();
+                w.line(format!("    v[0x{:X}] = 0x{:02X};", x, nn));
+            }
+            AddImm(x, nn) => {
+                w.regs();
+                w.line(format!(
+                    "    v[0x{:X}] = v[0x{:X}].wrapping_add(0x{:02X});",
+                    x, x, nn
+                ));
+            }
+            Move(x, y) => {
+                w.regs();
+                w.line(format!("    v[0x{:X}] = v[0x{:X}];", x, y));
+            }
+            Or(x, y) | And(x, y) | Xor(x, y) => {
+                let sym = match ins {
+                    Or(..) => '|',
+                    And(..) => '&',
+                    _ => '^',
+                };
+                w.regs();
+                w.line(format!("    v[0x{:X}] {}= v[0x{:X}];", x, sym, y));
+                w.line("    if quirks.vf_reset {");
+                w.line("        v[0xF] = 0;");
+                w.line("    }");
+            }
+            Add(x, y) | Sub(x, y) | SubN(x, y) => {
+                let (a, method, b, flag) = match ins {
+                    Add(..) => (x, "overflowing_add", y, "carry as u8"),
+                    Sub(..) => (x, "overflowing_sub", y, "!carry as u8"),
+                    _ => (y, "overflowing_sub", x, "!carry as u8"),
+                };
+                w.regs();
+                w.line(format!(
+                    "    let (vx, carry) = v[0x{:X}].{}(v[0x{:X}]);",
+                    a, method, b
+                ));
+                w.line(format!("    v[0x{:X}] = vx;", x));
+                w.line(format!("    v[0xF] = {};", flag));
+            }
+            ShiftRight(x, y) | ShiftLeft(x, y) => {
+                let (res, flag) = if let ShiftRight(..) = ins {
+                    ("src >> 1", "src & 1")
+                } else {
+                    ("src << 1", "src >> 7")
+                };
+                w.regs();
+                w.line(format!(
+                    "    let src = if quirks.shift_vx {{ v[0x{:X}] }} else {{ v[0x{:X}] }};",
+                    x, y
+                ));
+                w.line(format!("    v[0x{:X}] = {};", x, res));
+                w.line(format!("    v[0xF] = {};", flag));
+            }
+            LoadI(nnn) => w.call(format!("    emu.set_i_reg(0x{:03X});", nnn)),
+            AddI(x) => {
+                w.regs();
+                w.line(format!("    let vx = v[0x{:X}] as u32;", x));
+                w.call("    emu.set_i_reg((emu.i_reg() + vx) & 0xFFFF);");
+            }
+            Jump(nnn) => w.call(format!("    emu.set_pc(0x{:03X});", nnn)),
+            SkipEqImm(x, _) | SkipNeImm(x, _) | SkipEqReg(x, _) | SkipNeReg(x, _) => {
+                let cmp = match ins {
+                    SkipEqImm(..) | SkipEqReg(..) => "==",
+                    _ => "!=",
+                };
+                let rhs = match ins {
+                    SkipEqImm(_, nn) | SkipNeImm(_, nn) => format!("0x{:02X}", nn),
+                    SkipEqReg(_, y) | SkipNeReg(_, y) => format!("v[0x{:X}]", y),
+                    _ => unreachable!(),
+                };
+                w.regs();
+                w.line(format!("    let skip = v[0x{:X}] {} {};", x, cmp, rhs));
+                w.call(format!(
+                    "    emu.set_pc(if skip {{ 0x{:03X} }} else {{ 0x{:03X} }});",
+                    after(addr, 4),
+                    after(addr, 2)
+                ));
+            }
+            _ => unreachable!("{:?} is not translated inline", ins),
+        }
+    }
+    if let Some(next) = b.next.filter(|&next| pc != Some(next)) {
+        w.call(format!("    emu.set_pc(0x{:03X});", next));
+    }
+    w.line("    Ok(())");
+    w.line("}");
+}
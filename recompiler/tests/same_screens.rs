@@ -0,0 +1,70 @@
+// Every test ROM, translated by build.rs, must behave exactly like the
+// interpreter: same screen, pc and registers after every frame.
+use chip8_core::*;
+
+macro_rules! translated {
+    ($($rom:ident),*) => {
+        $(mod $rom {
+            include!(concat!(env!("OUT_DIR"), "/", stringify!($rom), ".rs"));
+        })*
+        const TRANSLATED: &[(&str, &[u8], Run)] = &[$((stringify!($rom), $rom::ROM, $rom::run)),*];
+    };
+}
+
+type Run = fn(&mut Emu, usize) -> Result<(), EmuError>;
+
+translated!(
+    font_grid,
+    bcd_memory,
+    subroutines,
+    keys_timers,
+    arith,
+    self_modify
+);
+
+const FRAMES: usize = 200;
+
+// `ticks` per frame, with a key held for a while so keys_timers gets going.
+fn compare(name: &str, rom: &[u8], run: Run, ticks: usize) {
+    let mut translated = Emu::new(|| 0xA5);
+    translated.load(rom).unwrap();
+    let mut interp = translated.clone();
+    for frame in 0..FRAMES {
+        let key = (20..40).contains(&frame);
+        translated.keypress(0x7, key);
+        interp.keypress(0x7, key);
+
+        let res = run(&mut translated, ticks);
+        let mut interp_res = Ok(());
+        for _ in 0..ticks {
+            interp_res = interp.tick();
+            if interp_res.is_err() {
+                break;
+            }
+        }
+        let at = format!("{} frame {} ({} ticks per frame)", name, frame, ticks);
+        assert_eq!(res, interp_res, "{}: result", at);
+        assert_eq!(translated.pc(), interp.pc(), "{}: pc", at);
+        assert_eq!(translated.i_reg(), interp.i_reg(), "{}: I", at);
+        assert_eq!(translated.v_reg(), interp.v_reg(), "{}: V registers", at);
+        assert_eq!(&translated.ram()[..], &interp.ram()[..], "{}: RAM", at);
+        assert_eq!(
+            translated.get_display(),
+            interp.get_display(),
+            "{}: screen",
+            at
+        );
+
+        translated.tick_timers();
+        interp.tick_timers();
+    }
+}
+
+#[test]
+fn translated_roms_match_interpreter() {
+    for &(name, rom, run) in TRANSLATED {
+        for ticks in [10, 7, 1] {
+            compare(name, rom, run, ticks);
+        }
+    }
+}
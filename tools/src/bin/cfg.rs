@@ -0,0 +1,55 @@
+// Recovers a ROM's control flow graph. Usage:
+//   cfg [--platform ID] [--format dot|json] ROM
+// DOT output renders with e.g. `dot -Tsvg`.
+use chip8_core::Platform;
+use std::{env, process};
+use tools::{Cfg, read_rom};
+
+fn main() {
+    let mut platform = None;
+    let mut json = false;
+    let mut path = None;
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--platform" => {
+                let id = args.next().unwrap_or_default();
+                match Platform::from_id(&id) {
+                    Some(p) => platform = Some(p),
+                    None => fail(&format!("Unknown platform {}", id)),
+                }
+            }
+            "--format" => match args.next().as_deref() {
+                Some("dot") => json = false,
+                Some("json") => json = true,
+                _ => fail("--format takes dot or json"),
+            },
+            _ if path.is_none() && !arg.starts_with("--") => path = Some(arg),
+            _ => usage(),
+        }
+    }
+    let Some(path) = path else { usage() };
+    let (rom, patch) = read_rom(path.as_ref())
+        .unwrap_or_else(|e| fail(&format!("Unable to open {}: {}", path, e)));
+    if let Some(patch) = patch {
+        eprintln!("Patched with {}", patch.display());
+    }
+    let platform = platform
+        .or_else(|| Platform::detect(&rom))
+        .unwrap_or(Platform::ModernChip8);
+    let cfg = Cfg::build(&rom, platform);
+    if json {
+        println!("{}", cfg.to_json());
+    } else {
+        print!("{}", cfg.to_dot());
+    }
+}
+
+fn usage() -> ! {
+    fail("Usage: cfg [--platform ID] [--format dot|json] ROM")
+}
+
+fn fail(msg: &str) -> ! {
+    eprintln!("{}", msg);
+    process::exit(1)
+}
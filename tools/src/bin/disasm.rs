@@ -0,0 +1,53 @@
+// Lists a ROM's instructions and data. Usage:
+//   disasm [--platform ID] [--coverage FILE]... ROM
+// Coverage maps from several sessions are merged before use.
+use chip8_core::Platform;
+use std::{env, fs, process};
+use tools::{CoverageMap, listing, read_rom};
+
+fn main() {
+    let mut platform = None;
+    let mut coverage: Option<CoverageMap> = None;
+    let mut path = None;
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--platform" => {
+                let id = args.next().unwrap_or_default();
+                match Platform::from_id(&id) {
+                    Some(p) => platform = Some(p),
+                    None => fail(&format!("Unknown platform {}", id)),
+                }
+            }
+            "--coverage" => {
+                let Some(file) = args.next() else { usage() };
+                let text = fs::read_to_string(&file)
+                    .unwrap_or_else(|e| fail(&format!("Unable to open {}: {}", file, e)));
+                let map =
+                    CoverageMap::parse(&text).unwrap_or_else(|e| fail(&format!("{}: {}", file, e)));
+                coverage.get_or_insert_default().merge(&map);
+            }
+            _ if path.is_none() && !arg.starts_with("--") => path = Some(arg),
+            _ => usage(),
+        }
+    }
+    let Some(path) = path else { usage() };
+    let (rom, patch) = read_rom(path.as_ref())
+        .unwrap_or_else(|e| fail(&format!("Unable to open {}: {}", path, e)));
+    if let Some(patch) = patch {
+        eprintln!("Patched with {}", patch.display());
+    }
+    let platform = platform
+        .or_else(|| Platform::detect(&rom))
+        .unwrap_or(Platform::ModernChip8);
+    print!("{}", listing(&rom, platform, coverage.as_ref()));
+}
+
+fn usage() -> ! {
+    fail("Usage: disasm [--platform ID] [--coverage FILE]... ROM")
+}
+
+fn fail(msg: &str) -> ! {
+    eprintln!("{}", msg);
+    process::exit(1)
+}
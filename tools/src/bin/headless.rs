@@ -0,0 +1,105 @@
+// Runs a ROM without a window for a number of frames, optionally profiling
+// it or recording what memory it touched. Usage:
+//   headless [--frames N] [--tickrate N] [--platform ID] [--profile text|json]
+//            [--coverage FILE] ROM
+// The coverage map is merged into FILE if it exists.
+use chip8_core::{Emu, Platform, os_random};
+use std::{env, path::PathBuf, process, sync::Arc};
+use tools::{Coverage, Profiler, read_rom};
+
+const FRAMES: u32 = 600;
+
+fn main() {
+    let mut frames = FRAMES;
+    let mut tickrate = None;
+    let mut platform = None;
+    let mut profile = None;
+    let mut coverage_file = None;
+    let mut path = None;
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--frames" => frames = number(args.next()),
+            "--tickrate" => tickrate = Some(number(args.next())),
+            "--platform" => {
+                let id = args.next().unwrap_or_default();
+                match Platform::from_id(&id) {
+                    Some(p) => platform = Some(p),
+                    None => fail(&format!("Unknown platform {}", id)),
+                }
+            }
+            "--profile" => match args.next().as_deref() {
+                Some(format @ ("text" | "json")) => profile = Some(format.to_string()),
+                _ => fail("--profile takes text or json"),
+            },
+            "--coverage" => match args.next() {
+                Some(file) => coverage_file = Some(PathBuf::from(file)),
+                None => usage(),
+            },
+            _ if path.is_none() && !arg.starts_with("--") => path = Some(arg),
+            _ => usage(),
+        }
+    }
+    let Some(path) = path else { usage() };
+    let (rom, patch) = read_rom(path.as_ref())
+        .unwrap_or_else(|e| fail(&format!("Unable to open {}: {}", path, e)));
+    if let Some(patch) = patch {
+        eprintln!("Patched with {}", patch.display());
+    }
+
+    let mut chip8 = Emu::new(os_random);
+    let platform = platform.or_else(|| Platform::detect(&rom));
+    if let Some(platform) = platform {
+        chip8.set_platform(platform);
+        chip8.set_quirks(platform.quirks());
+    }
+    let tickrate = tickrate.unwrap_or(chip8.platform().tickrate());
+    let profiler = Arc::new(Profiler::new());
+    if profile.is_some() {
+        chip8.add_observer(profiler.clone());
+    }
+    let coverage = Arc::new(Coverage::new());
+    if coverage_file.is_some() {
+        chip8.add_observer(coverage.clone());
+    }
+    if let Err(e) = chip8.load(&rom) {
+        fail(&format!("Unable to load {}: {}", path, e));
+    }
+
+    'frames: for _ in 0..frames {
+        for _ in 0..tickrate {
+            if let Err(e) = chip8.tick() {
+                eprintln!("Emulation stopped: {}", e);
+                break 'frames;
+            }
+        }
+        chip8.tick_timers();
+    }
+
+    match profile.as_deref() {
+        Some("json") => println!("{}", profiler.report().to_json()),
+        Some(_) => print!("{}", profiler.report()),
+        None => (),
+    }
+    if let Some(file) = coverage_file
+        && let Err(e) = coverage.map().save_merged(&file)
+    {
+        fail(&format!("Unable to save {}: {}", file.display(), e));
+    }
+}
+
+fn number(arg: Option<String>) -> u32 {
+    arg.and_then(|n| n.parse().ok()).unwrap_or_else(|| usage())
+}
+
+fn usage() -> ! {
+    fail(
+        "Usage: headless [--frames N] [--tickrate N] [--platform ID] \
+         [--profile text|json] [--coverage FILE] ROM",
+    )
+}
+
+fn fail(msg: &str) -> ! {
+    eprintln!("{}", msg);
+    process::exit(1)
+}
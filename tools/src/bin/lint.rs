@@ -0,0 +1,55 @@
+// Checks a ROM for likely bugs without running it. Usage:
+//   lint [--platform ID]... ROM
+// With several platforms, the ROM is checked against each. Exits with 1 if
+// anything is an error.
+use chip8_core::Platform;
+use std::{env, process};
+use tools::{Severity, lint, read_rom};
+
+fn main() {
+    let mut platforms = Vec::new();
+    let mut path = None;
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--platform" => {
+                let id = args.next().unwrap_or_default();
+                match Platform::from_id(&id) {
+                    Some(p) => platforms.push(p),
+                    None => fail(&format!("Unknown platform {}", id)),
+                }
+            }
+            _ if path.is_none() && !arg.starts_with("--") => path = Some(arg),
+            _ => usage(),
+        }
+    }
+    let Some(path) = path else { usage() };
+    let (rom, patch) = read_rom(path.as_ref())
+        .unwrap_or_else(|e| fail(&format!("Unable to open {}: {}", path, e)));
+    if let Some(patch) = patch {
+        eprintln!("Patched with {}", patch.display());
+    }
+    if platforms.is_empty() {
+        platforms.push(Platform::detect(&rom).unwrap_or(Platform::ModernChip8));
+    }
+
+    let mut errors = false;
+    for platform in platforms {
+        for finding in lint(&rom, platform) {
+            errors |= finding.severity == Severity::Error;
+            println!("{}: {}", path, finding);
+        }
+    }
+    if errors {
+        process::exit(1);
+    }
+}
+
+fn usage() -> ! {
+    fail("Usage: lint [--platform ID]... ROM")
+}
+
+fn fail(msg: &str) -> ! {
+    eprintln!("{}", msg);
+    process::exit(1)
+}
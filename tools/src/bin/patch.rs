@@ -0,0 +1,25 @@
+// Applies an IPS or BPS patch to a ROM and writes the result. Usage:
+//   patch ROM PATCH OUT
+use chip8_core::apply_patch;
+use std::{env, fs, process};
+
+fn main() {
+    let args: Vec<_> = env::args().skip(1).collect();
+    let [rom, patch, out] = &args[..] else {
+        fail("Usage: patch ROM PATCH OUT");
+    };
+    let read = |path: &str| {
+        fs::read(path).unwrap_or_else(|e| fail(&format!("Unable to open {}: {}", path, e)))
+    };
+    let patched = apply_patch(&read(rom), &read(patch))
+        .unwrap_or_else(|e| fail(&format!("Unable to apply {}: {}", patch, e)));
+    if let Err(e) = fs::write(out, &patched) {
+        fail(&format!("Unable to write {}: {}", out, e));
+    }
+    println!("Wrote {} ({} bytes)", out, patched.len());
+}
+
+fn fail(msg: &str) -> ! {
+    eprintln!("{}", msg);
+    process::exit(1)
+}
@@ -0,0 +1,330 @@
+// Static control flow recovery. Walks a ROM from its entry point through
+// jumps, calls and skips, using chip8_core's decoder so it sees opcodes the
+// way Emu does, and splits what it reaches into basic blocks and functions.
+// Computed jumps (BNNN) and opcodes that don't decode end the walk where
+// they are, and are listed so they can be looked at by hand. Like Emu's pc,
+// the walk wraps around at 4K, so the part of a big MegaChip8 ROM past that
+// is only ever data.
+use chip8_core::{Instruction, Platform, RAM_SIZE};
+use serde::Serialize;
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt::Write,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum EdgeKind {
+    // On to the next instruction, or the one after a skip not taken.
+    Fallthrough,
+    Jump,
+    // Past the next instruction, when a skip is taken.
+    Skip,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct Edge {
+    pub target: u16,
+    pub kind: EdgeKind,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Line {
+    pub addr: u16,
+    pub op: u16,
+    pub text: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Block {
+    pub start: u16,
+    pub instructions: Vec<Line>,
+    // Empty after a return, a computed jump or an invalid opcode.
+    pub successors: Vec<Edge>,
+    // Subroutines called from the block, which return into it.
+    pub calls: Vec<u16>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Function {
+    pub entry: u16,
+    // Start addresses of the blocks reached from the entry without calls.
+    pub blocks: Vec<u16>,
+    pub calls: Vec<u16>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Cfg {
+    pub entry: u16,
+    // In address order.
+    pub blocks: Vec<Block>,
+    // The program itself first, then the subroutines in address order.
+    pub functions: Vec<Function>,
+    // Addresses of BNNN jumps, whose targets depend on a register.
+    pub computed_jumps: Vec<u16>,
+    // Addresses of reachable opcodes the platform doesn't know.
+    pub invalid: Vec<u16>,
+    // First and last address of each run of ROM bytes below 4K nothing
+    // reaches.
+    pub unreachable: Vec<(u16, u16)>,
+}
+
+struct Decoded {
+    op: u16,
+    ins: Instruction,
+    len: u16,
+    successors: Vec<Edge>,
+}
+
+impl Decoded {
+    // Whether control only ever goes on to the next instruction.
+    fn falls_through(&self) -> bool {
+        matches!(
+            self.successors[..],
+            [Edge {
+                kind: EdgeKind::Fallthrough,
+                ..
+            }]
+        )
+    }
+}
+
+// Reads the opcode at `addr` if all of it is in the ROM.
+fn fetch(rom: &[u8], base: u16, addr: u16, len: u16) -> Option<&[u8]> {
+    let start = addr.checked_sub(base)? as usize;
+    rom.get(start..start + len as usize)
+}
+
+fn decode(rom: &[u8], base: u16, platform: Platform, addr: u16) -> Option<Decoded> {
+    let bytes = fetch(rom, base, addr, 2)?;
+    let op = u16::from_be_bytes([bytes[0], bytes[1]]);
+    let ins = Instruction::decode_for(platform, op);
+    // MegaChip's long I load takes the next word too.
+    let len = match ins {
+        Instruction::LoadLongI(_) => 4,
+        _ => 2,
+    };
+    let next = (addr + len) % RAM_SIZE as u16;
+    let fallthrough = Edge {
+        target: next,
+        kind: EdgeKind::Fallthrough,
+    };
+    use Instruction::*;
+    let successors = match ins {
+        Jump(nnn) => vec![Edge {
+            target: nnn,
+            kind: EdgeKind::Jump,
+        }],
+        Ret | JumpOffset(..) | Invalid(_) => vec![],
+        SkipEqImm(..) | SkipNeImm(..) | SkipEqReg(..) | SkipNeReg(..) | SkipKey(_)
+        | SkipNotKey(_) | SkipKey2(_) | SkipNotKey2(_) => vec![
+            fallthrough,
+            Edge {
+                target: (next + 2) % RAM_SIZE as u16,
+                kind: EdgeKind::Skip,
+            },
+        ],
+        _ => vec![fallthrough],
+    };
+    Some(Decoded {
+        op,
+        ins,
+        len,
+        successors,
+    })
+}
+
+impl Cfg {
+    // Analyses `rom` as loaded on `platform`, from the platform's entry point.
+    pub fn build(rom: &[u8], platform: Platform) -> Cfg {
+        let base = platform.load_addr();
+        let entry = platform.entry();
+
+        // Every instruction reachable from the entry point.
+        let mut reached = BTreeMap::new();
+        let mut work = vec![entry];
+        while let Some(addr) = work.pop() {
+            if reached.contains_key(&addr) {
+                continue;
+            }
+            let Some(decoded) = decode(rom, base, platform, addr) else {
+                continue;
+            };
+            work.extend(decoded.successors.iter().map(|edge| edge.target));
+            if let Instruction::Call(nnn) = decoded.ins {
+                work.push(nnn);
+            }
+            reached.insert(addr, decoded);
+        }
+
+        // Blocks start at the entry point, at anything jumped, skipped or
+        // called to, and after anything that doesn't just fall through.
+        let mut leaders = BTreeSet::from([entry]);
+        for decoded in reached.values() {
+            if !decoded.falls_through() {
+                leaders.extend(decoded.successors.iter().map(|edge| edge.target));
+            }
+            if let Instruction::Call(nnn) = decoded.ins {
+                leaders.insert(nnn);
+            }
+        }
+
+        let mut blocks = Vec::new();
+        for &start in leaders.iter().filter(|addr| reached.contains_key(addr)) {
+            let mut block = Block {
+                start,
+                instructions: Vec::new(),
+                successors: Vec::new(),
+                calls: Vec::new(),
+            };
+            let mut addr = start;
+            loop {
+                let decoded = &reached[&addr];
+                block.instructions.push(Line {
+                    addr,
+                    op: decoded.op,
+                    text: decoded.ins.to_string(),
+                });
+                if let Instruction::Call(nnn) = decoded.ins
+                    && !block.calls.contains(&nnn)
+                {
+                    block.calls.push(nnn);
+                }
+                let next = (addr + decoded.len) % RAM_SIZE as u16;
+                if !decoded.falls_through()
+                    || leaders.contains(&next)
+                    || !reached.contains_key(&next)
+                {
+                    block.successors = decoded.successors.clone();
+                    break;
+                }
+                addr = next;
+            }
+            blocks.push(block);
+        }
+
+        let by_start: BTreeMap<_, _> = blocks.iter().map(|b| (b.start, b)).collect();
+        let mut entries = BTreeSet::new();
+        for block in &blocks {
+            entries.extend(block.calls.iter().filter(|c| by_start.contains_key(c)));
+        }
+        entries.remove(&entry);
+        let functions = [entry]
+            .into_iter()
+            .chain(entries)
+            .filter(|start| by_start.contains_key(start))
+            .map(|start| {
+                let mut seen = BTreeSet::new();
+                let mut calls = BTreeSet::new();
+                let mut work = vec![start];
+                while let Some(addr) = work.pop() {
+                    let Some(block) = by_start.get(&addr) else {
+                        continue;
+                    };
+                    if !seen.insert(addr) {
+                        continue;
+                    }
+                    calls.extend(&block.calls);
+                    work.extend(block.successors.iter().map(|edge| edge.target));
+                }
+                Function {
+                    entry: start,
+                    blocks: seen.into_iter().collect(),
+                    calls: calls.into_iter().collect(),
+                }
+            })
+            .collect();
+
+        let find = |pred: fn(&Instruction) -> bool| {
+            reached
+                .iter()
+                .filter(|(_, decoded)| pred(&decoded.ins))
+                .map(|(&addr, _)| addr)
+                .collect()
+        };
+        let computed_jumps = find(|ins| matches!(ins, Instruction::JumpOffset(..)));
+        let invalid = find(|ins| matches!(ins, Instruction::Invalid(_)));
+
+        let code_len = rom.len().min(RAM_SIZE.saturating_sub(base as usize));
+        let mut covered = vec![false; code_len];
+        for (&addr, decoded) in &reached {
+            let start = (addr - base) as usize;
+            let end = (start + decoded.len as usize).min(code_len);
+            covered[start..end].fill(true);
+        }
+        let mut unreachable: Vec<(u16, u16)> = Vec::new();
+        for (idx, _) in covered.iter().enumerate().filter(|(_, c)| !**c) {
+            let addr = base + idx as u16;
+            match unreachable.last_mut() {
+                Some((_, end)) if *end + 1 == addr => *end = addr,
+                _ => unreachable.push((addr, addr)),
+            }
+        }
+
+        Cfg {
+            entry,
+            blocks,
+            functions,
+            computed_jumps,
+            invalid,
+            unreachable,
+        }
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).unwrap()
+    }
+
+    // Graphviz source: a box per block, solid edges for control flow and
+    // dashed ones for calls. Blocks that end in a computed jump or an
+    // invalid opcode are drawn in red.
+    pub fn to_dot(&self) -> String {
+        let mut out = String::from("digraph rom {\n");
+        out.push_str("  node [shape=box, fontname=\"monospace\"];\n");
+        for block in &self.blocks {
+            let mut label = String::new();
+            for line in &block.instructions {
+                write!(label, "0x{:03X}  {}\\l", line.addr, line.text).unwrap();
+            }
+            let last = block.instructions.last().map(|line| line.addr);
+            let flagged = last.is_some_and(|addr| {
+                self.computed_jumps.contains(&addr) || self.invalid.contains(&addr)
+            });
+            let color = if flagged { ", color=red" } else { "" };
+            writeln!(
+                out,
+                "  b{:03X} [label=\"{}\"{}];",
+                block.start, label, color
+            )
+            .unwrap();
+        }
+        for block in &self.blocks {
+            for edge in &block.successors {
+                let style = match edge.kind {
+                    EdgeKind::Fallthrough => "",
+                    EdgeKind::Jump => " [label=\"jump\"]",
+                    EdgeKind::Skip => " [label=\"skip\"]",
+                };
+                writeln!(
+                    out,
+                    "  b{:03X} -> b{:03X}{};",
+                    block.start, edge.target, style
+                )
+                .unwrap();
+            }
+            for call in &block.calls {
+                writeln!(
+                    out,
+                    "  b{:03X} -> b{:03X} [style=dashed, label=\"call\"];",
+                    block.start, call
+                )
+                .unwrap();
+            }
+        }
+        for (start, end) in &self.unreachable {
+            writeln!(out, "  // unreachable: 0x{:03X}-0x{:03X}", start, end).unwrap();
+        }
+        out.push_str("}\n");
+        out
+    }
+}
@@ -0,0 +1,299 @@
+// Cheat finding the classic way: snapshot RAM, then keep narrowing the
+// candidate addresses down by how their values moved between frames until
+// the one holding lives or energy is left. Found addresses become cheats,
+// kept in a text file next to the ROM:
+//
+//   # chip8 cheats v1
+//   freeze 0x3A0 0x09 infinite lives
+//   patch 0x2F4 0x12 skip the intro
+//
+// A patch is written once after loading; a frozen value is written back
+// every frame the game changes it.
+use chip8_core::{Emu, RAM_SIZE};
+use std::{
+    fmt, fs, io,
+    path::{Path, PathBuf},
+};
+
+const HEADER: &str = "# chip8 cheats v1";
+// Candidates the console lists at most.
+const SHOWN: usize = 20;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Filter {
+    // Same value as at the last search.
+    Equal,
+    Changed,
+    Increased,
+    Decreased,
+    // Holding exactly this value now.
+    Value(u8),
+}
+
+impl Filter {
+    fn keeps(self, old: u8, new: u8) -> bool {
+        match self {
+            Filter::Equal => new == old,
+            Filter::Changed => new != old,
+            Filter::Increased => new > old,
+            Filter::Decreased => new < old,
+            Filter::Value(val) => new == val,
+        }
+    }
+}
+
+// Addresses still in the running, with their values at the last search.
+#[derive(Debug, Clone)]
+pub struct CheatSearch {
+    candidates: Vec<(u16, u8)>,
+}
+
+impl CheatSearch {
+    // Starts with every address in RAM.
+    pub fn new(emu: &Emu) -> Self {
+        CheatSearch {
+            candidates: (0..RAM_SIZE as u16).map(|a| (a, emu.peek(a))).collect(),
+        }
+    }
+
+    // Drops the candidates `filter` rejects and remembers the values of the
+    // rest for the next search.
+    pub fn filter(&mut self, emu: &Emu, filter: Filter) {
+        self.candidates.retain_mut(|(addr, old)| {
+            let new = emu.peek(*addr);
+            let keep = filter.keeps(*old, new);
+            *old = new;
+            keep
+        });
+    }
+
+    pub fn candidates(&self) -> &[(u16, u8)] {
+        &self.candidates
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CheatKind {
+    Freeze,
+    Patch,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Cheat {
+    pub kind: CheatKind,
+    pub addr: u16,
+    pub value: u8,
+    pub name: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CheatError {
+    pub line: usize,
+    pub msg: String,
+}
+
+impl fmt::Display for CheatError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.msg)
+    }
+}
+
+impl std::error::Error for CheatError {}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CheatList {
+    pub cheats: Vec<Cheat>,
+}
+
+impl CheatList {
+    pub fn parse(text: &str) -> Result<CheatList, CheatError> {
+        let mut cheats = Vec::new();
+        for (idx, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let cheat = parse_cheat(line).map_err(|msg| CheatError {
+                line: idx + 1,
+                msg: msg.to_string(),
+            })?;
+            cheats.push(cheat);
+        }
+        Ok(CheatList { cheats })
+    }
+
+    // The cheats saved at `path`, or none if there is no such file.
+    pub fn load(path: &Path) -> io::Result<CheatList> {
+        match fs::read_to_string(path) {
+            Ok(text) => {
+                CheatList::parse(&text).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+            }
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(CheatList::default()),
+            Err(e) => Err(e),
+        }
+    }
+
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        fs::write(path, self.to_string())
+    }
+
+    // Writes every cheat, for right after loading the ROM.
+    pub fn patch(&self, emu: &mut Emu) {
+        for cheat in &self.cheats {
+            poke_changed(emu, cheat.addr, cheat.value);
+        }
+    }
+
+    // Writes back frozen values the game has changed, once a frame.
+    pub fn freeze(&self, emu: &mut Emu) {
+        for cheat in &self.cheats {
+            if cheat.kind == CheatKind::Freeze {
+                poke_changed(emu, cheat.addr, cheat.value);
+            }
+        }
+    }
+}
+
+// Writing the same value again would still throw away decoded and compiled
+// code around it.
+fn poke_changed(emu: &mut Emu, addr: u16, value: u8) {
+    if emu.peek(addr) != value {
+        emu.poke(addr, value);
+    }
+}
+
+fn parse_cheat(line: &str) -> Result<Cheat, &'static str> {
+    let mut words = line.split_whitespace();
+    let kind = match words.next() {
+        Some("freeze") => CheatKind::Freeze,
+        Some("patch") => CheatKind::Patch,
+        _ => return Err("cheats start with freeze or patch"),
+    };
+    let addr = words.next().and_then(parse_hex).ok_or("bad address")?;
+    let value = words.next().and_then(parse_hex).ok_or("bad value")?;
+    if addr as usize >= RAM_SIZE || value > 0xFF {
+        return Err("address or value out of range");
+    }
+    Ok(Cheat {
+        kind,
+        addr: addr as u16,
+        value: value as u8,
+        name: words.collect::<Vec<_>>().join(" "),
+    })
+}
+
+fn parse_hex(text: &str) -> Option<u32> {
+    u32::from_str_radix(text.strip_prefix("0x")?, 16).ok()
+}
+
+impl fmt::Display for CheatList {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{}", HEADER)?;
+        for cheat in &self.cheats {
+            let kind = match cheat.kind {
+                CheatKind::Freeze => "freeze",
+                CheatKind::Patch => "patch",
+            };
+            let line = format!(
+                "{} 0x{:03X} 0x{:02X} {}",
+                kind, cheat.addr, cheat.value, cheat.name
+            );
+            writeln!(f, "{}", line.trim_end())?;
+        }
+        Ok(())
+    }
+}
+
+// Where the cheats for the ROM at `rom` are kept.
+pub fn cheat_file(rom: &Path) -> PathBuf {
+    rom.with_extension("cht")
+}
+
+// Text commands driving a search and the cheat list, for a console next to
+// the game window. The list is saved whenever it changes.
+pub struct CheatConsole {
+    path: PathBuf,
+    pub cheats: CheatList,
+    search: Option<CheatSearch>,
+}
+
+impl CheatConsole {
+    pub fn new(path: PathBuf) -> io::Result<Self> {
+        let cheats = CheatList::load(&path)?;
+        Ok(CheatConsole {
+            path,
+            cheats,
+            search: None,
+        })
+    }
+
+    // Runs one command and returns what to show.
+    pub fn command(&mut self, line: &str, emu: &mut Emu) -> String {
+        let words: Vec<_> = line.split_whitespace().collect();
+        let filter = match words[..] {
+            ["eq"] => Some(Filter::Equal),
+            ["ne"] => Some(Filter::Changed),
+            ["inc"] => Some(Filter::Increased),
+            ["dec"] => Some(Filter::Decreased),
+            ["is", value] => match parse_hex(value).or_else(|| value.parse().ok()) {
+                Some(value @ 0..=0xFF) => Some(Filter::Value(value as u8)),
+                _ => return "is takes a byte".to_string(),
+            },
+            _ => None,
+        };
+        if let Some(filter) = filter {
+            let Some(search) = &mut self.search else {
+                return "No search yet; start one with new".to_string();
+            };
+            search.filter(emu, filter);
+            return self.list();
+        }
+        match words[..] {
+            ["new"] => {
+                self.search = Some(CheatSearch::new(emu));
+                self.list()
+            }
+            ["list"] => self.list(),
+            ["cheats"] => self.cheats.to_string(),
+            ["freeze" | "patch", ..] => {
+                let cheat = match parse_cheat(line) {
+                    Ok(cheat) => cheat,
+                    Err(msg) => return msg.to_string(),
+                };
+                poke_changed(emu, cheat.addr, cheat.value);
+                self.cheats.cheats.retain(|c| c.addr != cheat.addr);
+                self.cheats.cheats.push(cheat);
+                self.save()
+            }
+            ["remove", addr] => {
+                let Some(addr) = parse_hex(addr) else {
+                    return "bad address".to_string();
+                };
+                self.cheats.cheats.retain(|c| c.addr as u32 != addr);
+                self.save()
+            }
+            _ => "Commands: new, eq, ne, inc, dec, is VALUE, list, \
+                  freeze ADDR VALUE [NAME], patch ADDR VALUE [NAME], remove ADDR, cheats"
+                .to_string(),
+        }
+    }
+
+    fn list(&self) -> String {
+        let Some(search) = &self.search else {
+            return "No search yet; start one with new".to_string();
+        };
+        let found = search.candidates();
+        let mut out = format!("{} candidates", found.len());
+        for (addr, value) in found.iter().take(SHOWN) {
+            out.push_str(&format!("\n  0x{:03X} = 0x{:02X}", addr, value));
+        }
+        out
+    }
+
+    fn save(&self) -> String {
+        match self.cheats.save(&self.path) {
+            Ok(()) => format!("Saved {}", self.path.display()),
+            Err(e) => format!("Unable to save {}: {}", self.path.display(), e),
+        }
+    }
+}
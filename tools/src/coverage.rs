@@ -0,0 +1,206 @@
+// Which memory a program touched while it ran: bytes fetched as opcodes,
+// read as data through I (sprites, FX65) and written (FX33, FX55). Maps from
+// several sessions merge, and the listing disassembler uses them to tell
+// code from data.
+//
+// The file format is text, one line per run of addresses with the same
+// accesses, in address order:
+//
+//   # chip8 coverage v1
+//   0x200-0x22B c
+//   0x22C-0x23F r
+//   0x300 rw
+//
+// where c, r and w stand for code, read and written. The header comes first;
+// other lines starting with # are comments.
+use chip8_core::{Instruction, Observer, RAM_SIZE};
+use std::{collections::BTreeMap, fmt, fs, io, path::Path, sync::Mutex};
+
+const HEADER: &str = "# chip8 coverage v1";
+// MegaChip8's 16M, more memory than any other platform has.
+const MAX_ADDR: u32 = (1 << 24) - 1;
+
+pub const CODE: u8 = 1;
+pub const READ: u8 = 2;
+pub const WRITTEN: u8 = 4;
+
+const FLAGS: [(u8, char); 3] = [(CODE, 'c'), (READ, 'r'), (WRITTEN, 'w')];
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CoverageMap {
+    // Accesses per address; untouched addresses are left out.
+    bytes: BTreeMap<u32, u8>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CoverageError {
+    pub line: usize,
+    pub msg: String,
+}
+
+impl fmt::Display for CoverageError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.msg)
+    }
+}
+
+impl std::error::Error for CoverageError {}
+
+impl CoverageMap {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // CODE, READ and WRITTEN bits for `addr`, 0 if it was never touched.
+    pub fn get(&self, addr: u32) -> u8 {
+        self.bytes.get(&addr).copied().unwrap_or(0)
+    }
+
+    pub fn mark(&mut self, addr: u32, flags: u8) {
+        *self.bytes.entry(addr).or_default() |= flags;
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.bytes.is_empty()
+    }
+
+    pub fn merge(&mut self, other: &CoverageMap) {
+        for (&addr, &flags) in &other.bytes {
+            self.mark(addr, flags);
+        }
+    }
+
+    pub fn parse(text: &str) -> Result<CoverageMap, CoverageError> {
+        let mut map = CoverageMap::new();
+        let mut header = false;
+        for (idx, line) in text.lines().enumerate() {
+            let line = line.trim();
+            let err = |msg: &str| CoverageError {
+                line: idx + 1,
+                msg: msg.to_string(),
+            };
+            if line.is_empty() {
+                continue;
+            }
+            if !header {
+                if line != HEADER {
+                    return Err(err("not a coverage file"));
+                }
+                header = true;
+                continue;
+            }
+            if line.starts_with('#') {
+                continue;
+            }
+            let (range, flags) = line
+                .split_once(' ')
+                .ok_or_else(|| err("expected an address range and flags"))?;
+            let (start, end) = range.split_once('-').unwrap_or((range, range));
+            let (start, end) = (
+                parse_addr(start).ok_or_else(|| err("bad address"))?,
+                parse_addr(end).ok_or_else(|| err("bad address"))?,
+            );
+            if end < start {
+                return Err(err("range ends before it starts"));
+            }
+            if end > MAX_ADDR {
+                return Err(err("address past the end of memory"));
+            }
+            let mut bits = 0;
+            for c in flags.trim().chars() {
+                let (bit, _) = FLAGS
+                    .iter()
+                    .find(|(_, name)| *name == c)
+                    .ok_or_else(|| err("flags are c, r and w"))?;
+                bits |= bit;
+            }
+            for addr in start..=end {
+                map.mark(addr, bits);
+            }
+        }
+        Ok(map)
+    }
+
+    // Adds this map to the one saved at `path`, if any, and saves the result.
+    pub fn save_merged(&self, path: &Path) -> io::Result<()> {
+        let mut map = match fs::read_to_string(path) {
+            Ok(text) => CoverageMap::parse(&text)
+                .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => CoverageMap::new(),
+            Err(e) => return Err(e),
+        };
+        map.merge(self);
+        fs::write(path, map.to_string())
+    }
+}
+
+fn parse_addr(text: &str) -> Option<u32> {
+    u32::from_str_radix(text.strip_prefix("0x")?, 16).ok()
+}
+
+impl fmt::Display for CoverageMap {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{}", HEADER)?;
+        let mut bytes = self.bytes.iter().peekable();
+        while let Some((&start, &flags)) = bytes.next() {
+            let mut end = start;
+            while let Some(&(&next, &next_flags)) = bytes.peek()
+                && next == end + 1
+                && next_flags == flags
+            {
+                end = next;
+                bytes.next();
+            }
+            if end == start {
+                write!(f, "0x{:03X} ", start)?;
+            } else {
+                write!(f, "0x{:03X}-0x{:03X} ", start, end)?;
+            }
+            for (bit, name) in FLAGS {
+                if flags & bit != 0 {
+                    write!(f, "{}", name)?;
+                }
+            }
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+}
+
+// Records a CoverageMap while attached to an Emu.
+#[derive(Default)]
+pub struct Coverage {
+    map: Mutex<CoverageMap>,
+}
+
+impl Coverage {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn map(&self) -> CoverageMap {
+        self.map.lock().unwrap().clone()
+    }
+}
+
+impl Observer for Coverage {
+    fn fetch(&self, addr: u16, ins: Instruction) {
+        // MegaChip's long I load takes the next word too.
+        let len = match ins {
+            Instruction::LoadLongI(_) => 4,
+            _ => 2,
+        };
+        let mut map = self.map.lock().unwrap();
+        for offset in 0..len {
+            map.mark((addr as u32 + offset) % RAM_SIZE as u32, CODE);
+        }
+    }
+
+    fn read(&self, addr: u32, _val: u8) {
+        self.map.lock().unwrap().mark(addr, READ);
+    }
+
+    fn write(&self, addr: u32, _val: u8) {
+        self.map.lock().unwrap().mark(addr, WRITTEN);
+    }
+}
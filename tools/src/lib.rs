@@ -0,0 +1,20 @@
+// Tools around chip8_core: profiling and coverage through the observer
+// hooks, static analysis of ROMs, cheats and patched ROM loading. Shared by
+// the command line tools in src/bin and the desktop binary.
+mod cfg;
+mod cheats;
+mod coverage;
+mod lint;
+mod listing;
+mod profiler;
+mod rom;
+
+pub use cfg::{Block, Cfg, Edge, EdgeKind, Function, Line};
+pub use cheats::{
+    Cheat, CheatConsole, CheatError, CheatKind, CheatList, CheatSearch, Filter, cheat_file,
+};
+pub use coverage::{CODE, Coverage, CoverageError, CoverageMap, READ, WRITTEN};
+pub use lint::{Finding, Severity, lint};
+pub use listing::listing;
+pub use profiler::{FrameStats, Hotspot, InstructionCount, Profile, Profiler, RoutineStats};
+pub use rom::read_rom;
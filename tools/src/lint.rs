@@ -0,0 +1,234 @@
+// Static checks for bugs that would otherwise only show up at runtime. The
+// linter works over the blocks Cfg recovers, so it only looks at code that
+// can run, and follows I through each block to check the memory that
+// sprites and FX33/FX55/FX65 touch when I is set by a constant.
+use crate::cfg::Cfg;
+use chip8_core::{Instruction, Platform};
+use std::{collections::BTreeSet, fmt};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    // Will go wrong when the code runs.
+    Error,
+    // Likely a mistake, or only works by accident.
+    Warning,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Severity::Error => write!(f, "error"),
+            Severity::Warning => write!(f, "warning"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Finding {
+    pub addr: u16,
+    pub severity: Severity,
+    pub platform: Platform,
+    pub message: String,
+}
+
+impl fmt::Display for Finding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "0x{:03X}: {} ({}): {}",
+            self.addr,
+            self.severity,
+            self.platform.id(),
+            self.message
+        )
+    }
+}
+
+// Everything found in `rom` when run on `platform`, in address order.
+pub fn lint(rom: &[u8], platform: Platform) -> Vec<Finding> {
+    let cfg = Cfg::build(rom, platform);
+    let base = platform.load_addr() as u32;
+    let rom_end = base + rom.len() as u32;
+    let memory = platform.memory_size() as u32;
+    let mut findings = Vec::new();
+    let mut report = |addr: u16, severity, message: String| {
+        findings.push(Finding {
+            addr,
+            severity,
+            platform,
+            message,
+        });
+    };
+
+    let in_subroutine: BTreeSet<u16> = cfg.functions[1..]
+        .iter()
+        .flat_map(|func| func.blocks.iter().copied())
+        .collect();
+    for block in &cfg.blocks {
+        // I, while it holds a known address.
+        let mut i_reg: Option<u32> = None;
+        for line in &block.instructions {
+            let addr = line.addr;
+            if addr % 2 != 0 {
+                report(
+                    addr,
+                    Severity::Warning,
+                    "instruction at an odd address".to_string(),
+                );
+            }
+            let ins = Instruction::decode_for(platform, line.op);
+            match ins {
+                Instruction::Jump(nnn) | Instruction::Call(nnn) => {
+                    let what = if let Instruction::Jump(_) = ins {
+                        "jumps"
+                    } else {
+                        "calls"
+                    };
+                    if (nnn as u32) < base {
+                        report(
+                            addr,
+                            Severity::Error,
+                            format!("{} to 0x{:03X}, below the program", what, nnn),
+                        );
+                    } else if nnn as u32 >= rom_end {
+                        report(
+                            addr,
+                            Severity::Error,
+                            format!("{} to 0x{:03X}, past the end of the ROM", what, nnn),
+                        );
+                    }
+                }
+                Instruction::JumpOffset(_, nnn) if nnn as u32 + 0xFF >= memory => {
+                    report(
+                        addr,
+                        Severity::Warning,
+                        format!("0x{:03X} plus the offset can jump past the end of RAM", nnn),
+                    );
+                }
+                Instruction::Ret if !in_subroutine.contains(&block.start) => {
+                    report(
+                        addr,
+                        Severity::Error,
+                        "RET outside any subroutine".to_string(),
+                    );
+                }
+                // Defined for the platform, but Emu has no way to run it.
+                Instruction::Invalid(op) if defined(platform, op) => {
+                    report(
+                        addr,
+                        Severity::Warning,
+                        format!("0x{:04X} is not emulated", op),
+                    );
+                }
+                Instruction::Invalid(op) => {
+                    let others: Vec<_> = Platform::ALL
+                        .into_iter()
+                        .filter(|&other| {
+                            defined(other, op)
+                                || !matches!(
+                                    Instruction::decode_for(other, op),
+                                    Instruction::Invalid(_)
+                                )
+                        })
+                        .map(|other| other.id())
+                        .collect();
+                    let message = if others.is_empty() {
+                        format!("0x{:04X} is not an opcode", op)
+                    } else {
+                        format!(
+                            "0x{:04X} is not supported here, only on {}",
+                            op,
+                            others.join(", ")
+                        )
+                    };
+                    report(addr, Severity::Error, message);
+                }
+                Instruction::Draw(_, _, n) => {
+                    if let Some(i) = i_reg
+                        && i >= base
+                        && i + n as u32 > rom_end
+                    {
+                        report(
+                            addr,
+                            Severity::Warning,
+                            format!("sprite at 0x{:03X} runs past the end of the ROM", i),
+                        );
+                    }
+                }
+                Instruction::Bcd(_) | Instruction::Store(_) | Instruction::Load(_) => {
+                    let (len, name) = match ins {
+                        Instruction::Bcd(_) => (3, "FX33"),
+                        Instruction::Store(x) => (x as u32 + 1, "FX55"),
+                        Instruction::Load(x) => (x as u32 + 1, "FX65"),
+                        _ => unreachable!(),
+                    };
+                    if let Some(i) = i_reg
+                        && i + len > memory
+                    {
+                        report(
+                            addr,
+                            Severity::Error,
+                            format!("{} at 0x{:03X} runs past the end of RAM", name, i),
+                        );
+                    }
+                }
+                _ => (),
+            }
+            i_reg = match ins {
+                Instruction::LoadI(nnn) => Some(nnn as u32),
+                Instruction::Store(x) | Instruction::Load(x) => {
+                    let step = platform.quirks().memory_increment as u32 * (x as u32 + 1);
+                    i_reg.map(|i| i + step)
+                }
+                // Anything else that moves I, or a subroutine that might.
+                Instruction::AddI(_)
+                | Instruction::Font(_)
+                | Instruction::LoadLongI(_)
+                | Instruction::Call(_)
+                | Instruction::MachineCall(_) => None,
+                _ => i_reg,
+            };
+        }
+    }
+
+    for func in &cfg.functions[1..] {
+        let returns = cfg
+            .blocks
+            .iter()
+            .filter(|block| func.blocks.contains(&block.start))
+            .flat_map(|block| &block.instructions)
+            .any(|line| Instruction::decode_for(platform, line.op) == Instruction::Ret);
+        if !returns {
+            report(
+                func.entry,
+                Severity::Warning,
+                "subroutine never returns".to_string(),
+            );
+        }
+    }
+
+    findings.sort_by_key(|f| (f.addr, f.severity));
+    findings
+}
+
+// Whether the platform's own documentation defines `op`, for the SUPER-CHIP
+// and XO-CHIP opcodes that Instruction::decode_for doesn't know.
+fn defined(platform: Platform, op: u16) -> bool {
+    // 00FD-00FF (exit, lores, hires), FX30 (big font), FX75 and FX85 (flags).
+    let schip1 = matches!(op, 0x00FD..=0x00FF) || matches!(op & 0xF0FF, 0xF030 | 0xF075 | 0xF085);
+    // Scrolling: 00CN down, 00FB right, 00FC left.
+    let schip = schip1 || op & 0xFFF0 == 0x00C0 || matches!(op, 0x00FB | 0x00FC);
+    // 00DN up, 5XY2/5XY3 ranges, F000 long I, FN01 planes, F002 audio and
+    // FX3A pitch.
+    let xo = schip
+        || op & 0xFFF0 == 0x00D0
+        || matches!(op & 0xF00F, 0x5002 | 0x5003)
+        || matches!(op, 0xF000 | 0xF002)
+        || matches!(op & 0xF0FF, 0xF001 | 0xF03A);
+    match platform {
+        Platform::SuperChip1 => schip1,
+        Platform::SuperChip | Platform::MegaChip8 => schip,
+        Platform::XoChip => xo,
+        _ => false,
+    }
+}
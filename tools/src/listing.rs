@@ -0,0 +1,93 @@
+// Disassembles a whole ROM. With a coverage map, bytes that only ever ran
+// are listed as instructions and bytes that were only read or written as
+// data; bytes the map has nothing on, or all of them without a map, are
+// disassembled as they come and marked as not reached.
+use crate::coverage::{CODE, CoverageMap};
+use chip8_core::{Instruction, Platform};
+use std::fmt::Write;
+
+// Data bytes per DB line.
+const DB_WIDTH: usize = 8;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Region {
+    Code,
+    Data,
+    Unknown,
+}
+
+impl Region {
+    fn at(coverage: Option<&CoverageMap>, addr: u32) -> Region {
+        match coverage.map_or(0, |map| map.get(addr)) {
+            0 => Region::Unknown,
+            flags if flags & CODE != 0 => Region::Code,
+            _ => Region::Data,
+        }
+    }
+
+    fn comment(self) -> &'static str {
+        match self {
+            Region::Code => "; code",
+            Region::Data => "; data",
+            Region::Unknown => "; not reached",
+        }
+    }
+}
+
+// The listing of `rom` as loaded on `platform`, one line per instruction or
+// run of data bytes: address, bytes and mnemonic.
+pub fn listing(rom: &[u8], platform: Platform, coverage: Option<&CoverageMap>) -> String {
+    let base = platform.load_addr() as u32;
+    let region = |idx: usize| Region::at(coverage, base + idx as u32);
+    let mut out = String::new();
+    let mut current = None;
+    let mut idx = 0;
+    while idx < rom.len() {
+        let here = region(idx);
+        if current != Some(here) {
+            if current.is_some() {
+                out.push('\n');
+            }
+            out.push_str(here.comment());
+            out.push('\n');
+            current = Some(here);
+        }
+        let addr = base + idx as u32;
+        // Data, and a lone byte before a change of region or the end.
+        let len = if here == Region::Data || idx + 1 == rom.len() || region(idx + 1) != here {
+            (idx..rom.len())
+                .take(DB_WIDTH)
+                .take_while(|&next| region(next) == here)
+                .count()
+                .max(1)
+        } else {
+            0
+        };
+        if len > 0 {
+            let bytes = &rom[idx..idx + len];
+            let hex: Vec<_> = bytes.iter().map(|b| format!("0x{:02X}", b)).collect();
+            writeln!(out, "0x{:03X}  {:<9}  DB {}", addr, "", hex.join(", ")).unwrap();
+            idx += len;
+            continue;
+        }
+        let op = u16::from_be_bytes([rom[idx], rom[idx + 1]]);
+        let ins = Instruction::decode_for(platform, op);
+        // MegaChip's long I load carries the address in the next word.
+        if let Instruction::LoadLongI(nn) = ins
+            && let Some(low) = rom.get(idx + 2..idx + 4)
+        {
+            let i = u32::from_be_bytes([0, nn, low[0], low[1]]);
+            writeln!(
+                out,
+                "0x{:03X}  {:04X} {:02X}{:02X}  LDHI I, 0x{:06X}",
+                addr, op, low[0], low[1], i
+            )
+            .unwrap();
+            idx += 4;
+            continue;
+        }
+        writeln!(out, "0x{:03X}  {:04X}       {}", addr, op, ins).unwrap();
+        idx += 2;
+    }
+    out
+}
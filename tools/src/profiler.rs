@@ -0,0 +1,291 @@
+// Counts what a program spends its time on: how often each address and kind
+// of instruction runs, how long subroutines take, following CALL and RET,
+// and how much each frame draws. Time is measured in instructions.
+use chip8_core::{Instruction, Observer, STACK_SIZE};
+use serde::Serialize;
+use std::{collections::HashMap, fmt, sync::Mutex};
+
+// Entries shown per table in the text report.
+const TOP: usize = 20;
+
+#[derive(Default)]
+pub struct Profiler {
+    state: Mutex<State>,
+}
+
+#[derive(Default)]
+struct State {
+    total: u64,
+    // Executions and the instruction last seen at each address.
+    addrs: HashMap<u16, (u64, Instruction)>,
+    classes: HashMap<String, u64>,
+    routines: HashMap<u16, Routine>,
+    // Caller and callee entry points.
+    calls: HashMap<(u16, u16), u64>,
+    stack: Vec<Call>,
+    frames: Vec<FrameStats>,
+    current: FrameStats,
+}
+
+#[derive(Default)]
+struct Routine {
+    calls: u64,
+    self_time: u64,
+    total_time: u64,
+}
+
+struct Call {
+    entry: u16,
+    // Instructions run before the call, to work out its inclusive time.
+    start: u64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Profile {
+    pub instructions: u64,
+    // Most executed first.
+    pub hotspots: Vec<Hotspot>,
+    pub mix: Vec<InstructionCount>,
+    // Most time spent first, including what the program did outside of any
+    // subroutine, under its entry point.
+    pub routines: Vec<RoutineStats>,
+    pub frames: Vec<FrameStats>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Hotspot {
+    pub addr: u16,
+    pub count: u64,
+    pub instruction: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct InstructionCount {
+    pub class: String,
+    pub count: u64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct RoutineStats {
+    pub entry: u16,
+    pub calls: u64,
+    // Instructions run in the routine itself, and including its callees.
+    pub self_time: u64,
+    pub total_time: u64,
+    // Routines it called, and how often.
+    pub callees: Vec<(u16, u64)>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct FrameStats {
+    pub draws: u32,
+    pub collisions: u32,
+}
+
+impl Profiler {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn report(&self) -> Profile {
+        let state = self.state.lock().unwrap();
+        let mut hotspots: Vec<_> = state
+            .addrs
+            .iter()
+            .map(|(&addr, &(count, ins))| Hotspot {
+                addr,
+                count,
+                instruction: ins.to_string(),
+            })
+            .collect();
+        hotspots.sort_by_key(|h| (std::cmp::Reverse(h.count), h.addr));
+
+        let mut mix: Vec<_> = state
+            .classes
+            .iter()
+            .map(|(class, &count)| InstructionCount {
+                class: class.clone(),
+                count,
+            })
+            .collect();
+        mix.sort_by(|a, b| b.count.cmp(&a.count).then(a.class.cmp(&b.class)));
+
+        // Routines still running count up to now.
+        let mut open = HashMap::new();
+        for call in &state.stack {
+            open.entry(call.entry).or_insert(call.start);
+        }
+        let mut routines: Vec<_> = state
+            .routines
+            .iter()
+            .map(|(&entry, routine)| {
+                let running = open.get(&entry).map_or(0, |start| state.total - start);
+                let mut callees: Vec<_> = state
+                    .calls
+                    .iter()
+                    .filter(|((caller, _), _)| *caller == entry)
+                    .map(|(&(_, callee), &count)| (callee, count))
+                    .collect();
+                callees.sort();
+                RoutineStats {
+                    entry,
+                    calls: routine.calls,
+                    self_time: routine.self_time,
+                    total_time: routine.total_time + running,
+                    callees,
+                }
+            })
+            .collect();
+        routines.sort_by_key(|r| (std::cmp::Reverse(r.total_time), r.entry));
+
+        let mut frames = state.frames.clone();
+        if state.current != FrameStats::default() {
+            frames.push(state.current);
+        }
+        Profile {
+            instructions: state.total,
+            hotspots,
+            mix,
+            routines,
+            frames,
+        }
+    }
+}
+
+impl Observer for Profiler {
+    fn fetch(&self, addr: u16, ins: Instruction) {
+        let mut state = self.state.lock().unwrap();
+        let state = &mut *state;
+        if state.stack.is_empty() {
+            // The first instruction is the program's entry point, which
+            // stands for the code outside any subroutine.
+            state.stack.push(Call {
+                entry: addr,
+                start: 0,
+            });
+            state.routines.entry(addr).or_default().calls = 1;
+        }
+        state.total += 1;
+        let spot = state.addrs.entry(addr).or_insert((0, ins));
+        *spot = (spot.0 + 1, ins);
+        *state.classes.entry(class(ins)).or_default() += 1;
+
+        let caller = state.stack.last().unwrap().entry;
+        state.routines.entry(caller).or_default().self_time += 1;
+        match ins {
+            // A CALL with the stack full fails in Emu::execute and goes
+            // nowhere. The first entry here is the entry point, not a slot.
+            Instruction::Call(nnn) if state.stack.len() <= STACK_SIZE => {
+                *state.calls.entry((caller, nnn)).or_default() += 1;
+                state.routines.entry(nnn).or_default().calls += 1;
+                state.stack.push(Call {
+                    entry: nnn,
+                    // The CALL itself belongs to the caller.
+                    start: state.total,
+                });
+            }
+            // A RET with nothing to return from is left to the interpreter.
+            Instruction::Ret if state.stack.len() > 1 => {
+                let call = state.stack.pop().unwrap();
+                state.routines.entry(call.entry).or_default().total_time +=
+                    state.total - call.start;
+            }
+            _ => (),
+        }
+    }
+
+    fn draw(&self, _x: u8, _y: u8, _height: u8, collision: bool) {
+        let mut state = self.state.lock().unwrap();
+        state.current.draws += 1;
+        state.current.collisions += collision as u32;
+    }
+
+    fn timers(&self, _delay: u8, _sound: u8) {
+        let mut state = self.state.lock().unwrap();
+        let frame = std::mem::take(&mut state.current);
+        state.frames.push(frame);
+    }
+}
+
+// The instruction's kind, without its operands.
+fn class(ins: Instruction) -> String {
+    let mut name = format!("{:?}", ins);
+    name.truncate(name.find('(').unwrap_or(name.len()));
+    name
+}
+
+impl Profile {
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).unwrap()
+    }
+}
+
+impl fmt::Display for Profile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let percent = |count: u64| 100.0 * count as f64 / self.instructions.max(1) as f64;
+        writeln!(f, "{} instructions", self.instructions)?;
+
+        writeln!(f, "\nHotspots")?;
+        for spot in self.hotspots.iter().take(TOP) {
+            writeln!(
+                f,
+                "  0x{:03X}  {:>10}  {:5.1}%  {}",
+                spot.addr,
+                spot.count,
+                percent(spot.count),
+                spot.instruction
+            )?;
+        }
+
+        writeln!(f, "\nInstruction mix")?;
+        for class in &self.mix {
+            writeln!(
+                f,
+                "  {:<16} {:>10}  {:5.1}%",
+                class.class,
+                class.count,
+                percent(class.count)
+            )?;
+        }
+
+        writeln!(f, "\nSubroutines")?;
+        writeln!(
+            f,
+            "  {:<5}  {:>10}  {:>10}  {:>10}",
+            "entry", "calls", "self", "total"
+        )?;
+        for routine in self.routines.iter().take(TOP) {
+            writeln!(
+                f,
+                "  0x{:03X}  {:>10}  {:>10}  {:>10}  {:5.1}%",
+                routine.entry,
+                routine.calls,
+                routine.self_time,
+                routine.total_time,
+                percent(routine.total_time)
+            )?;
+            for (callee, count) in &routine.callees {
+                writeln!(f, "    -> 0x{:03X} x{}", callee, count)?;
+            }
+        }
+
+        let frames = self.frames.len();
+        let draws: u64 = self.frames.iter().map(|fr| fr.draws as u64).sum();
+        let collisions: u64 = self.frames.iter().map(|fr| fr.collisions as u64).sum();
+        let busiest = self.frames.iter().map(|fr| fr.draws).max().unwrap_or(0);
+        writeln!(f, "\n{} frames", frames)?;
+        writeln!(
+            f,
+            "  draws: {} ({:.1} per frame, at most {})",
+            draws,
+            draws as f64 / frames.max(1) as f64,
+            busiest
+        )?;
+        writeln!(
+            f,
+            "  collisions: {} ({:.1} per frame)",
+            collisions,
+            collisions as f64 / frames.max(1) as f64
+        )
+    }
+}
@@ -0,0 +1,29 @@
+// Reading ROM files, with a patch next to the ROM applied automatically:
+// game.ch8 is loaded patched by game.bps or, failing that, game.ips.
+use chip8_core::apply_patch;
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+const SIDECARS: [&str; 2] = ["bps", "ips"];
+
+// The ROM at `path`, patched, and the patch that was applied if any.
+pub fn read_rom(path: &Path) -> io::Result<(Vec<u8>, Option<PathBuf>)> {
+    let rom = fs::read(path)?;
+    for ext in SIDECARS {
+        let sidecar = path.with_extension(ext);
+        if sidecar == path || !sidecar.is_file() {
+            continue;
+        }
+        let patch = fs::read(&sidecar)?;
+        let patched = apply_patch(&rom, &patch).map_err(|e| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("{}: {}", sidecar.display(), e),
+            )
+        })?;
+        return Ok((patched, Some(sidecar)));
+    }
+    Ok((rom, None))
+}
@@ -0,0 +1,113 @@
+// Control flow recovery.
+use chip8_core::Platform;
+use tools::{Cfg, Edge, EdgeKind};
+
+const ROM: [u8; 12] = [
+    0x22, 0x08, // call 208
+    0x30, 0x00, // skip if V0 == 0
+    0x12, 0x00, // back to the start
+    0xB3, 0x00, // jump to 300 + V0
+    0x00, 0xEE, // 208: return
+    0xFF, 0xFF, // never reached
+];
+
+fn edge(target: u16, kind: EdgeKind) -> Edge {
+    Edge { target, kind }
+}
+
+#[test]
+fn finds_blocks_and_functions() {
+    let cfg = Cfg::build(&ROM, Platform::ModernChip8);
+    assert_eq!(cfg.entry, 0x200);
+
+    let starts: Vec<_> = cfg.blocks.iter().map(|b| b.start).collect();
+    assert_eq!(starts, [0x200, 0x204, 0x206, 0x208]);
+    let first = &cfg.blocks[0];
+    let addrs: Vec<_> = first.instructions.iter().map(|l| l.addr).collect();
+    assert_eq!(addrs, [0x200, 0x202]);
+    assert_eq!(first.instructions[1].text, "SE V0, 0x00");
+    assert_eq!(
+        first.successors,
+        [
+            edge(0x204, EdgeKind::Fallthrough),
+            edge(0x206, EdgeKind::Skip)
+        ]
+    );
+    assert_eq!(first.calls, [0x208]);
+    assert_eq!(cfg.blocks[1].successors, [edge(0x200, EdgeKind::Jump)]);
+    assert!(cfg.blocks[2].successors.is_empty());
+    assert!(cfg.blocks[3].successors.is_empty());
+
+    assert_eq!(cfg.functions.len(), 2);
+    assert_eq!(cfg.functions[0].entry, 0x200);
+    assert_eq!(cfg.functions[0].blocks, [0x200, 0x204, 0x206]);
+    assert_eq!(cfg.functions[0].calls, [0x208]);
+    assert_eq!(cfg.functions[1].entry, 0x208);
+    assert_eq!(cfg.functions[1].blocks, [0x208]);
+
+    assert_eq!(cfg.computed_jumps, [0x206]);
+    assert!(cfg.invalid.is_empty());
+    assert_eq!(cfg.unreachable, [(0x20A, 0x20B)]);
+}
+
+#[test]
+fn stops_at_invalid_opcodes_and_the_end_of_the_rom() {
+    let cfg = Cfg::build(
+        &[
+            0x12, 0x04, // jump over
+            0x00, 0x00, //
+            0xFF, 0xFF, // invalid
+            0x13, 0x00, // never reached
+            0x60, // half an instruction
+        ],
+        Platform::ModernChip8,
+    );
+    assert_eq!(cfg.invalid, [0x204]);
+    assert_eq!(cfg.unreachable, [(0x202, 0x203), (0x206, 0x208)]);
+
+    // Running off the end leaves an edge to nowhere.
+    let cfg = Cfg::build(&[0x60, 0x00], Platform::ModernChip8);
+    assert_eq!(
+        cfg.blocks[0].successors,
+        [edge(0x202, EdgeKind::Fallthrough)]
+    );
+}
+
+#[test]
+fn exports_dot_and_json() {
+    let cfg = Cfg::build(&ROM, Platform::ModernChip8);
+    let dot = cfg.to_dot();
+    assert!(dot.starts_with("digraph rom {\n"));
+    assert!(dot.contains("  b200 [label=\"0x200  CALL 0x208\\l0x202  SE V0, 0x00\\l\"];\n"));
+    assert!(dot.contains("  b206 [label=\"0x206  JP V0, 0x300\\l\", color=red];\n"));
+    assert!(dot.contains("  b200 -> b204;\n"));
+    assert!(dot.contains("  b200 -> b206 [label=\"skip\"];\n"));
+    assert!(dot.contains("  b204 -> b200 [label=\"jump\"];\n"));
+    assert!(dot.contains("  b200 -> b208 [style=dashed, label=\"call\"];\n"));
+    assert!(dot.contains("  // unreachable: 0x20A-0x20B\n"));
+    assert!(dot.ends_with("}\n"));
+
+    let json: serde_json::Value = serde_json::from_str(&cfg.to_json()).unwrap();
+    assert_eq!(json["entry"], 0x200);
+    assert_eq!(json["blocks"][0]["successors"][1]["kind"], "skip");
+    assert_eq!(json["functions"][1]["entry"], 0x208);
+    assert_eq!(json["computed_jumps"][0], 0x206);
+    assert_eq!(json["unreachable"][0][0], 0x20A);
+}
+
+#[test]
+fn big_megachip_roms_stop_at_4k() {
+    // Falls through every word of 4K, wrapping below the program at the end.
+    let mut rom = vec![0x60, 0x00];
+    rom.resize(70000, 0x60);
+    let cfg = Cfg::build(&rom, Platform::MegaChip8);
+    assert_eq!(cfg.blocks.len(), 1);
+    assert_eq!(cfg.blocks[0].instructions.last().unwrap().addr, 0xFFE);
+    assert_eq!(cfg.blocks[0].successors, [edge(0, EdgeKind::Fallthrough)]);
+    assert!(cfg.unreachable.is_empty());
+
+    let mut rom = vec![0x12, 0x00];
+    rom.resize(70000, 0);
+    let cfg = Cfg::build(&rom, Platform::MegaChip8);
+    assert_eq!(cfg.unreachable, [(0x202, 0xFFF)]);
+}
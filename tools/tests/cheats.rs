@@ -0,0 +1,132 @@
+// RAM search and cheats.
+use chip8_core::Emu;
+use std::{fs, path::PathBuf};
+use tools::{Cheat, CheatConsole, CheatKind, CheatList, CheatSearch, Filter, cheat_file};
+
+fn emu() -> Emu {
+    let mut emu = Emu::new(|| 0);
+    emu.load(&[0x12, 0x00]).unwrap();
+    emu
+}
+
+fn temp_file(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("cheats-{}-{}", name, std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    dir.join("game.cht")
+}
+
+#[test]
+fn narrows_down_to_the_changing_address() {
+    let mut emu = emu();
+    emu.poke(0x300, 3);
+    let mut search = CheatSearch::new(&emu);
+    assert_eq!(search.candidates().len(), 4096);
+
+    // A life lost.
+    emu.poke(0x300, 2);
+    search.filter(&emu, Filter::Decreased);
+    assert_eq!(search.candidates(), [(0x300, 2)]);
+
+    // Nothing happened, then an extra life.
+    search.filter(&emu, Filter::Equal);
+    assert_eq!(search.candidates(), [(0x300, 2)]);
+    emu.poke(0x300, 3);
+    search.filter(&emu, Filter::Increased);
+    assert_eq!(search.candidates(), [(0x300, 3)]);
+    search.filter(&emu, Filter::Changed);
+    assert!(search.candidates().is_empty());
+
+    let mut search = CheatSearch::new(&emu);
+    search.filter(&emu, Filter::Value(3));
+    assert!(search.candidates().contains(&(0x300, 3)));
+    assert!(search.candidates().iter().all(|(_, v)| *v == 3));
+}
+
+#[test]
+fn patches_once_and_freezes_every_frame() {
+    let list = CheatList::parse(
+        "# chip8 cheats v1\n\
+         freeze 0x300 0x09 infinite lives\n\
+         patch 0x202 0x12\n",
+    )
+    .unwrap();
+    assert_eq!(
+        list.cheats[0],
+        Cheat {
+            kind: CheatKind::Freeze,
+            addr: 0x300,
+            value: 9,
+            name: "infinite lives".to_string(),
+        }
+    );
+    assert_eq!(
+        list.to_string(),
+        "# chip8 cheats v1\nfreeze 0x300 0x09 infinite lives\npatch 0x202 0x12\n"
+    );
+
+    let mut emu = emu();
+    list.patch(&mut emu);
+    assert_eq!((emu.peek(0x300), emu.peek(0x202)), (9, 0x12));
+    emu.poke(0x300, 1);
+    emu.poke(0x202, 0);
+    list.freeze(&mut emu);
+    assert_eq!((emu.peek(0x300), emu.peek(0x202)), (9, 0));
+}
+
+#[test]
+fn rejects_bad_cheat_files() {
+    let err = CheatList::parse("freeze 0x300 0x09\nthaw 0x300 0x09\n").unwrap_err();
+    assert_eq!(err.line, 2);
+    assert!(CheatList::parse("freeze 0x300").is_err());
+    assert!(CheatList::parse("freeze 0x1000 0x00").is_err());
+    assert!(CheatList::parse("patch 0x300 0x100").is_err());
+}
+
+#[test]
+fn console_searches_and_saves_cheats() {
+    assert_eq!(
+        cheat_file("roms/game.ch8".as_ref()),
+        PathBuf::from("roms/game.cht")
+    );
+    let file = temp_file("console");
+    let _ = fs::remove_file(&file);
+    let mut emu = emu();
+    let mut console = CheatConsole::new(file.clone()).unwrap();
+
+    assert!(console.command("dec", &mut emu).starts_with("No search"));
+    emu.poke(0x300, 3);
+    assert!(
+        console
+            .command("new", &mut emu)
+            .starts_with("4096 candidates")
+    );
+    emu.poke(0x300, 2);
+    assert_eq!(
+        console.command("dec", &mut emu),
+        "1 candidates\n  0x300 = 0x02"
+    );
+    assert!(
+        console
+            .command("is 2", &mut emu)
+            .starts_with("1 candidates")
+    );
+
+    console.command("freeze 0x300 0x09 infinite  lives", &mut emu);
+    assert_eq!(emu.peek(0x300), 9);
+    let saved = CheatList::load(&file).unwrap();
+    assert_eq!(saved.cheats.len(), 1);
+    assert_eq!(saved.cheats[0].name, "infinite lives");
+
+    // Replacing and removing.
+    console.command("patch 0x300 0x05", &mut emu);
+    assert_eq!(
+        CheatList::load(&file).unwrap().cheats[0].kind,
+        CheatKind::Patch
+    );
+    console.command("remove 0x300", &mut emu);
+    assert!(CheatList::load(&file).unwrap().cheats.is_empty());
+
+    assert!(console.command("help", &mut emu).starts_with("Commands:"));
+    assert_eq!(console.command("freeze 0x300", &mut emu), "bad value");
+    fs::remove_dir_all(file.parent().unwrap()).unwrap();
+}
@@ -0,0 +1,132 @@
+// Coverage maps and the listing disassembler.
+use chip8_core::{Emu, Platform};
+use std::sync::Arc;
+use tools::{CODE, Coverage, CoverageMap, READ, WRITTEN, listing};
+
+const ROM: [u8; 16] = [
+    0xA2, 0x0A, // I = sprite
+    0xD0, 0x15, // draw it
+    0x12, 0x04, // loop
+    0x00, 0x00, // never run
+    0x00, 0x00, //
+    0xF0, 0x90, 0x90, 0x90, 0xF0, // sprite
+    0x00,
+];
+
+fn record(rom: &[u8], ticks: usize) -> CoverageMap {
+    let mut emu = Emu::new(|| 0);
+    emu.load(rom).unwrap();
+    let coverage = Arc::new(Coverage::new());
+    emu.add_observer(coverage.clone());
+    for _ in 0..ticks {
+        emu.tick().unwrap();
+    }
+    coverage.map()
+}
+
+#[test]
+fn records_code_reads_and_writes() {
+    let map = record(&ROM, 3);
+    assert_eq!(map.get(0x200), CODE);
+    assert_eq!(map.get(0x205), CODE);
+    assert_eq!(map.get(0x206), 0);
+    assert_eq!(map.get(0x20A), READ);
+    assert_eq!(map.get(0x20E), READ);
+    assert_eq!(map.get(0x20F), 0);
+
+    // A loop written over the next instruction, which then runs.
+    let map = record(
+        &[
+            0x60, 0x12, // V0 = 12
+            0x61, 0x08, // V1 = 08
+            0xA2, 0x08, // I = 208
+            0xF1, 0x55, // store V0-V1: jump to 208
+            0x00, 0x00, //
+        ],
+        5,
+    );
+    assert_eq!(map.get(0x208), CODE | WRITTEN);
+    assert_eq!(map.get(0x209), CODE | WRITTEN);
+    assert_eq!(map.get(0x20A), 0);
+}
+
+#[test]
+fn saves_and_parses_ranges() {
+    let map = record(&ROM, 3);
+    let text = map.to_string();
+    assert_eq!(text, "# chip8 coverage v1\n0x200-0x205 c\n0x20A-0x20E r\n");
+    assert_eq!(CoverageMap::parse(&text).unwrap(), map);
+
+    let parse = |lines: &str| CoverageMap::parse(&format!("# chip8 coverage v1\n{}", lines));
+    let parsed = parse("0x300 rw\n\n# note\n0x301-0x302 w\n").unwrap();
+    assert_eq!(parsed.get(0x300), READ | WRITTEN);
+    assert_eq!(parsed.get(0x302), WRITTEN);
+
+    let err = parse("0x200 c\n0x200 x\n").unwrap_err();
+    assert_eq!(err.line, 3);
+    assert!(parse("200 c").is_err());
+    assert!(parse("0x202-0x200 c").is_err());
+    assert_eq!(CoverageMap::parse("").unwrap(), CoverageMap::new());
+}
+
+#[test]
+fn rejects_other_files() {
+    let err = CoverageMap::parse("\n0x200 c\n").unwrap_err();
+    assert_eq!(err.line, 2);
+    assert!(CoverageMap::parse("# some notes\n# chip8 coverage v1\n").is_err());
+
+    // Ranges stop at the end of MegaChip8's 16M.
+    let err = CoverageMap::parse("# chip8 coverage v1\n0x0-0xFFFFFFFF r\n").unwrap_err();
+    assert_eq!(err.line, 2);
+    assert!(CoverageMap::parse("# chip8 coverage v1\n0x1000000 w\n").is_err());
+    let last = CoverageMap::parse("# chip8 coverage v1\n0xFFFFFF w\n").unwrap();
+    assert_eq!(last.get(0xFF_FFFF), WRITTEN);
+}
+
+#[test]
+fn sessions_merge() {
+    let dir = std::env::temp_dir().join(format!("coverage-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let file = dir.join("rom.cov");
+    let _ = std::fs::remove_file(&file);
+
+    let mut first = CoverageMap::new();
+    first.mark(0x200, CODE);
+    first.save_merged(&file).unwrap();
+    let mut second = CoverageMap::new();
+    second.mark(0x200, READ);
+    second.mark(0x300, WRITTEN);
+    second.save_merged(&file).unwrap();
+
+    let saved = CoverageMap::parse(&std::fs::read_to_string(&file).unwrap()).unwrap();
+    assert_eq!(saved.get(0x200), CODE | READ);
+    assert_eq!(saved.get(0x300), WRITTEN);
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn listing_labels_code_and_data() {
+    let map = record(&ROM, 3);
+    assert_eq!(
+        listing(&ROM, Platform::ModernChip8, Some(&map)),
+        "; code\n\
+         0x200  A20A       LD I, 0x20A\n\
+         0x202  D015       DRW V0, V1, 5\n\
+         0x204  1204       JP 0x204\n\
+         \n\
+         ; not reached\n\
+         0x206  0000       DW 0x0000\n\
+         0x208  0000       DW 0x0000\n\
+         \n\
+         ; data\n\
+         0x20A             DB 0xF0, 0x90, 0x90, 0x90, 0xF0\n\
+         \n\
+         ; not reached\n\
+         0x20F             DB 0x00\n"
+    );
+
+    // Without a map everything is decoded as it comes.
+    let plain = listing(&ROM, Platform::ModernChip8, None);
+    assert!(plain.starts_with("; not reached\n0x200  A20A       LD I, 0x20A\n"));
+    assert!(plain.contains("0x20A  F090       "));
+}
@@ -0,0 +1,149 @@
+// ROM linter.
+use chip8_core::Platform;
+use tools::{Finding, Severity, lint};
+
+fn findings(rom: &[u8], platform: Platform) -> Vec<(u16, Severity, String)> {
+    lint(rom, platform)
+        .into_iter()
+        .map(|f| (f.addr, f.severity, f.message))
+        .collect()
+}
+
+#[test]
+fn flags_memory_opcode_and_call_bugs() {
+    let rom = [
+        0x22, 0x14, // call 214
+        0xAF, 0xFE, // I = FFE
+        0xF2, 0x55, // store V0-V2 at FFE-1000
+        0xA2, 0x1A, // I = sprite
+        0xD0, 0x16, // draw 6 rows of a 2 row sprite
+        0x22, 0x18, // call 218
+        0x30, 0x00, // skip if V0 == 0
+        0x50, 0x11, // CHIP-8X only
+        0x13, 0x00, // jump past the ROM
+        0x00, 0x00, //
+        0x00, 0xEE, // 214: return
+        0x00, 0x00, //
+        0x12, 0x18, // 218: loop forever
+        0xF0, 0x90, // sprite
+    ];
+    let found = findings(&rom, Platform::ModernChip8);
+    let expected = [
+        (
+            0x204,
+            Severity::Error,
+            "FX55 at 0xFFE runs past the end of RAM",
+        ),
+        (
+            0x208,
+            Severity::Warning,
+            "sprite at 0x21A runs past the end of the ROM",
+        ),
+        (
+            0x20E,
+            Severity::Error,
+            "0x5011 is not supported here, only on chip8x",
+        ),
+        (
+            0x210,
+            Severity::Error,
+            "jumps to 0x300, past the end of the ROM",
+        ),
+        (0x218, Severity::Warning, "subroutine never returns"),
+    ];
+    assert_eq!(
+        found,
+        expected.map(|(addr, severity, msg)| (addr, severity, msg.to_string()))
+    );
+
+    // The same opcode is fine where it exists.
+    assert!(
+        findings(&rom, Platform::Chip8X)
+            .iter()
+            .all(|(_, _, msg)| !msg.contains("0x5011"))
+    );
+}
+
+#[test]
+fn defined_opcodes_that_arent_emulated_are_warnings() {
+    let rom = [0xF0, 0x75, 0x12, 0x02]; // save flags, then loop
+    assert_eq!(
+        findings(&rom, Platform::XoChip),
+        [(
+            0x200,
+            Severity::Warning,
+            "0xF075 is not emulated".to_string()
+        )]
+    );
+    assert_eq!(
+        findings(&rom, Platform::ModernChip8),
+        [(
+            0x200,
+            Severity::Error,
+            "0xF075 is not supported here, only on superchip1, superchip, megachip8, xochip"
+                .to_string()
+        )]
+    );
+}
+
+#[test]
+fn flags_control_flow_bugs() {
+    assert_eq!(
+        findings(&[0x00, 0xEE], Platform::ModernChip8),
+        [(
+            0x200,
+            Severity::Error,
+            "RET outside any subroutine".to_string()
+        )]
+    );
+    assert_eq!(
+        findings(&[0x21, 0x00], Platform::ModernChip8),
+        [(
+            0x200,
+            Severity::Error,
+            "calls to 0x100, below the program".to_string()
+        )]
+    );
+    assert_eq!(
+        findings(&[0x12, 0x03, 0x00, 0x12, 0x03], Platform::ModernChip8),
+        [(
+            0x203,
+            Severity::Warning,
+            "instruction at an odd address".to_string()
+        )]
+    );
+    assert_eq!(
+        findings(&[0xBF, 0x80], Platform::ModernChip8),
+        [(
+            0x200,
+            Severity::Warning,
+            "0xF80 plus the offset can jump past the end of RAM".to_string()
+        )]
+    );
+}
+
+#[test]
+fn findings_name_the_platform() {
+    let found = lint(&[0x00, 0xEE], Platform::Chip8X);
+    assert_eq!(
+        found,
+        [Finding {
+            addr: 0x300,
+            severity: Severity::Error,
+            platform: Platform::Chip8X,
+            message: "RET outside any subroutine".to_string(),
+        }]
+    );
+    assert_eq!(
+        found[0].to_string(),
+        "0x300: error (chip8x): RET outside any subroutine"
+    );
+}
+
+#[test]
+fn lints_big_megachip_roms() {
+    // Sprite data past 64K, read through I, after a program that loops.
+    let mut rom = vec![0x12, 0x00];
+    rom.resize(70000, 0xFF);
+    assert_eq!(findings(&rom, Platform::MegaChip8), []);
+}
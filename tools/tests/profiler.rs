@@ -0,0 +1,121 @@
+// Profiler reports.
+use chip8_core::{Emu, STACK_SIZE};
+use std::sync::Arc;
+use tools::{FrameStats, Profiler};
+
+fn run(rom: &[u8], frames: &[usize]) -> Arc<Profiler> {
+    let mut emu = Emu::new(|| 0);
+    emu.load(rom).unwrap();
+    let profiler = Arc::new(Profiler::new());
+    emu.add_observer(profiler.clone());
+    for &ticks in frames {
+        for _ in 0..ticks {
+            emu.tick().unwrap();
+        }
+        emu.tick_timers();
+    }
+    profiler
+}
+
+#[test]
+fn counts_addresses_classes_calls_and_frames() {
+    let profiler = run(
+        &[
+            0x22, 0x08, // call 208
+            0xD0, 0x15, // draw
+            0xD0, 0x15, // draw again, colliding
+            0x12, 0x06, // loop
+            0x70, 0x01, // 208: V0 += 1
+            0x00, 0xEE, // return
+        ],
+        &[6, 4],
+    );
+    let profile = profiler.report();
+    assert_eq!(profile.instructions, 10);
+
+    let top = &profile.hotspots[0];
+    assert_eq!((top.addr, top.count), (0x206, 5));
+    assert_eq!(top.instruction, "JP 0x206");
+    assert_eq!(profile.hotspots.len(), 6);
+
+    let mix: Vec<_> = profile
+        .mix
+        .iter()
+        .map(|c| (c.class.as_str(), c.count))
+        .collect();
+    assert_eq!(
+        mix,
+        [
+            ("Jump", 5),
+            ("Draw", 2),
+            ("AddImm", 1),
+            ("Call", 1),
+            ("Ret", 1)
+        ]
+    );
+
+    let main = &profile.routines[0];
+    assert_eq!(main.entry, 0x200);
+    assert_eq!((main.calls, main.self_time, main.total_time), (1, 8, 10));
+    assert_eq!(main.callees, [(0x208, 1)]);
+    let sub = &profile.routines[1];
+    assert_eq!(sub.entry, 0x208);
+    assert_eq!((sub.calls, sub.self_time, sub.total_time), (1, 2, 2));
+    assert!(sub.callees.is_empty());
+
+    assert_eq!(
+        profile.frames,
+        [
+            FrameStats {
+                draws: 2,
+                collisions: 1
+            },
+            FrameStats::default()
+        ]
+    );
+}
+
+#[test]
+fn routines_still_running_count_up_to_now() {
+    let profiler = run(
+        &[
+            0x22, 0x02, // call 202
+            0x12, 0x02, // 202: loop forever
+        ],
+        &[5],
+    );
+    let profile = profiler.report();
+    let sub = profile.routines.iter().find(|r| r.entry == 0x202).unwrap();
+    assert_eq!((sub.calls, sub.self_time, sub.total_time), (1, 4, 4));
+}
+
+#[test]
+fn calls_that_overflow_the_stack_are_not_counted() {
+    let mut emu = Emu::new(|| 0);
+    emu.load(&[0x22, 0x00]).unwrap(); // call itself
+    let profiler = Arc::new(Profiler::new());
+    emu.add_observer(profiler.clone());
+    let results: Vec<_> = (0..STACK_SIZE + 2).map(|_| emu.tick()).collect();
+    assert!(results[..STACK_SIZE].iter().all(Result::is_ok));
+    assert!(results[STACK_SIZE..].iter().all(Result::is_err));
+    let profile = profiler.report();
+    let main = &profile.routines[0];
+    assert_eq!(main.calls, STACK_SIZE as u64 + 1);
+    assert_eq!(main.callees, [(0x200, STACK_SIZE as u64)]);
+}
+
+#[test]
+fn reports_as_text_and_json() {
+    let profile = run(&[0x12, 0x00], &[3]).report();
+    let text = profile.to_string();
+    assert!(text.starts_with("3 instructions\n"));
+    assert!(text.contains("  0x200           3  100.0%  JP 0x200\n"));
+    assert!(text.contains("\n1 frames\n"));
+
+    let json: serde_json::Value = serde_json::from_str(&profile.to_json()).unwrap();
+    assert_eq!(json["instructions"], 3);
+    assert_eq!(json["hotspots"][0]["addr"], 0x200);
+    assert_eq!(json["mix"][0]["class"], "Jump");
+    assert_eq!(json["routines"][0]["total_time"], 3);
+    assert_eq!(json["frames"][0]["draws"], 0);
+}
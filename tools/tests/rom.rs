@@ -0,0 +1,28 @@
+// ROM files with sidecar patches.
+use std::fs;
+use tools::read_rom;
+
+#[test]
+fn applies_a_patch_next_to_the_rom() {
+    let dir = std::env::temp_dir().join(format!("sidecar-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let rom = dir.join("game.ch8");
+    fs::write(&rom, [0x60, 0x01, 0x12, 0x02]).unwrap();
+
+    assert_eq!(
+        read_rom(&rom).unwrap(),
+        (vec![0x60, 0x01, 0x12, 0x02], None)
+    );
+
+    let ips = dir.join("game.ips");
+    fs::write(&ips, b"PATCH\x00\x00\x01\x00\x01\x09EOF").unwrap();
+    assert_eq!(
+        read_rom(&rom).unwrap(),
+        (vec![0x60, 0x09, 0x12, 0x02], Some(ips.clone()))
+    );
+
+    fs::write(&ips, b"PATCH\x00\x00").unwrap();
+    let err = read_rom(&rom).unwrap_err();
+    assert!(err.to_string().ends_with("game.ips: patch is truncated"));
+    fs::remove_dir_all(&dir).unwrap();
+}
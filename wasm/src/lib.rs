@@ -5,13 +5,22 @@ use chip8_core::*;
 use console_log::init_with_level;
 use js_sys::Uint8Array;
 use log::{info, warn, Level};
-use wasm_bindgen::prelude::*;
-use web_sys::{CanvasRenderingContext2d, HtmlCanvasElement, KeyboardEvent};
+use wasm_bindgen::{prelude::*, Clamped};
+use web_sys::{CanvasRenderingContext2d, HtmlCanvasElement, ImageData, KeyboardEvent};
+
+// Used when neither the ROM database nor the platform sets a speed.
+const TICK_PERFRAME: u32 = 10;
 
 #[wasm_bindgen]
 pub struct EmuWasm {
-    chip8: Emu,
+    // Rebuilt for each game's platform.
+    chip8: Box<dyn Machine>,
     ctx: CanvasRenderingContext2d,
+    db: Option<RomDb>,
+    // Database entry or cartridge settings for the loaded game.
+    info: Option<RomInfo>,
+    // Instructions per frame, from `info` or the platform it was run as.
+    ticks: u32,
 }
 #[wasm_bindgen]
 impl EmuWasm {
@@ -22,7 +31,7 @@ impl EmuWasm {
 
         info!("be works!");
 
-        let chip8 = Emu::default();
+        let chip8 = Platform::ModernChip8.machine(os_random);
 
         let document = web_sys::window().unwrap().document().unwrap();
         let canvas = document.get_element_by_id("canvas").unwrap();
@@ -38,7 +47,13 @@ impl EmuWasm {
             .dyn_into::<CanvasRenderingContext2d>()
             .unwrap();
 
-        Ok(EmuWasm { chip8, ctx })
+        Ok(EmuWasm {
+            chip8,
+            ctx,
+            db: None,
+            info: None,
+            ticks: TICK_PERFRAME,
+        })
     }
 }
 impl Default for EmuWasm {
@@ -50,9 +65,11 @@ impl Default for EmuWasm {
 impl EmuWasm {
     // Wrappers to call corresponding functions in the chip8_core.
     #[wasm_bindgen]
-    pub fn tick(&mut self) {
+    pub fn tick(&mut self) -> Result<(), JsValue> {
         info!("tick!");
-        self.chip8.tick();
+        self.chip8
+            .step()
+            .map_err(|e| JsValue::from_str(&e.to_string()))
     }
 
     #[wasm_bindgen]
@@ -73,28 +90,134 @@ impl EmuWasm {
         info!("keypress!");
 
         let key = evt.key();
-        if let Some(k) = key2btn(&key) {
+        if let Some(k) = key2btn(&key, self.chip8.platform().keypad()) {
             self.chip8.keypress(k, pressed);
         }
     }
+    // `name` is the file's, whose extension may name the platform. A zip
+    // pack holding a single ROM loads that ROM.
     #[wasm_bindgen]
-    pub fn load_game(&mut self, data: Uint8Array) {
+    pub fn load_game(&mut self, data: Uint8Array, name: Option<String>) -> Result<(), JsValue> {
         info!("load game!");
 
         if data.is_null() {
             warn!("Game data is empty!");
         }
-        self.chip8.load(&data.to_vec());
+        let rom = data.to_vec();
+        if RomPack::detect(&rom) {
+            let pack = open_pack(&rom)?;
+            return match &pack.roms[..] {
+                [rom] => self.load_rom(rom.data.clone(), Some(&rom.name)),
+                _ => Err(JsValue::from_str(&format!(
+                    "Pick one of the ROMs in the pack:\n{}",
+                    pack
+                ))),
+            };
+        }
+        self.load_rom(rom, name.as_deref())
+    }
+    // The ROMs in a zip pack, a line each with its index, name, size and
+    // SHA-1.
+    #[wasm_bindgen]
+    pub fn pack_contents(&self, data: Uint8Array) -> Result<String, JsValue> {
+        Ok(open_pack(&data.to_vec())?.to_string())
+    }
+    // Loads the ROM of a zip pack with the index or name `which`.
+    #[wasm_bindgen]
+    pub fn load_from_pack(&mut self, data: Uint8Array, which: &str) -> Result<(), JsValue> {
+        let pack = open_pack(&data.to_vec())?;
+        let rom = pack
+            .find(which)
+            .ok_or_else(|| JsValue::from_str(&format!("No ROM {} in the pack", which)))?;
+        self.load_rom(rom.data.clone(), Some(&rom.name))
+    }
+    // Takes the text of the chip-8-database's programs.json; games loaded
+    // afterwards get their quirks, tickrate and colours from it.
+    #[wasm_bindgen]
+    pub fn load_database(&mut self, json: &str) -> Result<(), JsValue> {
+        let db = RomDb::from_json(json).map_err(|e| JsValue::from_str(&e.to_string()))?;
+        info!("ROM database: {} ROMs", db.len());
+        self.db = Some(db);
+        Ok(())
+    }
+    #[wasm_bindgen]
+    pub fn title(&self) -> Option<String> {
+        self.info.as_ref().map(|info| info.title.clone())
+    }
+    // Instructions to run per frame.
+    #[wasm_bindgen]
+    pub fn tickrate(&self) -> u32 {
+        self.ticks
+    }
+    // CSS colours for the canvas, "#rrggbb".
+    #[wasm_bindgen]
+    pub fn background(&self) -> String {
+        match self.chip8.frame() {
+            Frame::Mono {
+                colors: Some(layer),
+                ..
+            } => css(layer.background()),
+            _ => self.color(0, [0, 0, 0]),
+        }
     }
     #[wasm_bindgen]
-    pub fn draw_screen(&mut self, scale: usize) {
+    pub fn foreground(&self) -> String {
+        self.color(1, [255, 255, 255])
+    }
+    // Button hints such as "up: 5", one per line.
+    #[wasm_bindgen]
+    pub fn key_hints(&self) -> String {
+        let keys = self.info.iter().flat_map(|info| &info.keys);
+        keys.map(|(button, key)| format!("{}: {:X}\n", button, key))
+            .collect()
+    }
+    // Size of the picture in CHIP-8 pixels. It depends on the game, and
+    // changes when a MegaChip8 game turns on its colour screen.
+    #[wasm_bindgen]
+    pub fn screen_width(&self) -> usize {
+        self.chip8.frame().size().0
+    }
+    #[wasm_bindgen]
+    pub fn screen_height(&self) -> usize {
+        self.chip8.frame().size().1
+    }
+    #[wasm_bindgen]
+    pub fn draw_screen(&mut self, scale: usize) -> Result<(), JsValue> {
         info!("draw screen!");
 
-        let disp = self.chip8.get_display();
-        for i in 0..(SCREEN_H * SCREEN_W) {
-            if disp[i] {
-                let x = i % SCREEN_W;
-                let y = i / SCREEN_W;
+        let (disp, width, layer) = match self.chip8.frame() {
+            Frame::Mono {
+                pixels,
+                width,
+                colors,
+                ..
+            } => (pixels, width, colors),
+            Frame::Color(picture) => {
+                let (width, height) = (picture.width() * scale, picture.height() * scale);
+                let mut rgba = Vec::with_capacity(width * height * 4);
+                for y in 0..height {
+                    for x in 0..width {
+                        let [r, g, b] = picture.pixel(x / scale, y / scale);
+                        rgba.extend_from_slice(&[r, g, b, 0xFF]);
+                    }
+                }
+                let image = ImageData::new_with_u8_clamped_array_and_sh(
+                    Clamped(&rgba),
+                    width as u32,
+                    height as u32,
+                )?;
+                return self.ctx.put_image_data(&image, 0.0, 0.0);
+            }
+        };
+
+        // CHIP-8X pixels each take their zone's colour.
+        for (i, pixel) in disp.iter().enumerate() {
+            if *pixel {
+                let x = i % width;
+                let y = i / width;
+                if let Some(layer) = layer {
+                    self.ctx.set_fill_style_str(&css(layer.foreground(x, y)));
+                }
                 self.ctx.fill_rect(
                     (x * scale) as f64,
                     (y * scale) as f64,
@@ -103,29 +226,96 @@ impl EmuWasm {
                 );
             }
         }
+        Ok(())
     }
 }
 
-fn key2btn(key: &str) -> Option<usize> {
-    info!("bey2btn...!");
+impl EmuWasm {
+    fn load_rom(&mut self, mut rom: Vec<u8>, name: Option<&str>) -> Result<(), JsValue> {
+        // Octo cartridges carry their source, to assemble, and how to run it.
+        if Cartridge::detect(&rom) {
+            let cartridge =
+                Cartridge::decode(&rom).map_err(|e| JsValue::from_str(&e.to_string()))?;
+            rom = cartridge
+                .rom()
+                .map_err(|e| JsValue::from_str(&e.to_string()))?;
+            self.info = Some(cartridge.info);
+        } else {
+            self.info = self.db.as_ref().and_then(|db| db.lookup(&rom)).cloned();
+        }
+        match &self.info {
+            Some(info) => {
+                info!("{} ({})", info.title, info.platform.id());
+                self.chip8 = info.platform.machine(os_random);
+                self.chip8.set_quirks(info.quirks);
+                self.ticks = info.tickrate;
+            }
+            None => match name.and_then(Platform::from_file_name) {
+                Some(platform) => {
+                    info!("{} by its extension", platform.id());
+                    self.chip8 = platform.machine(os_random);
+                    self.ticks = platform.tickrate();
+                }
+                None => match Platform::detect(&rom) {
+                    Some(platform) => {
+                        info!("Looks like {}", platform.id());
+                        self.chip8 = platform.machine(os_random);
+                        self.ticks = platform.tickrate();
+                    }
+                    // Nothing known about the game: a plain Emu's quirks.
+                    None => {
+                        self.chip8 = Platform::ModernChip8.machine(os_random);
+                        self.chip8.set_quirks(Quirks::default());
+                        self.ticks = TICK_PERFRAME;
+                    }
+                },
+            },
+        }
+        self.chip8
+            .load(&rom)
+            .map_err(|e| JsValue::from_str(&e.to_string()))
+    }
 
-    match key {
-        "1" => Some(0x1),
-        "2" => Some(0x2),
-        "3" => Some(0x3),
-        "4" => Some(0xC),
-        "q" => Some(0x4),
-        "w" => Some(0x5),
-        "e" => Some(0x6),
-        "r" => Some(0xD),
-        "a" => Some(0x7),
-        "s" => Some(0x8),
-        "d" => Some(0x9),
-        "f" => Some(0xE),
-        "z" => Some(0xA),
-        "x" => Some(0x0),
-        "c" => Some(0xB),
-        "v" => Some(0xF),
-        _ => None,
+    fn color(&self, idx: usize, default: [u8; 3]) -> String {
+        let palette = self.info.as_ref().map_or(&[][..], |info| &info.palette[..]);
+        css(if palette.len() >= 2 {
+            palette[idx]
+        } else {
+            default
+        })
     }
 }
+
+fn open_pack(zip: &[u8]) -> Result<RomPack, JsValue> {
+    RomPack::open(zip).map_err(|e| JsValue::from_str(&e.to_string()))
+}
+
+fn css([r, g, b]: [u8; 3]) -> String {
+    format!("#{:02x}{:02x}{:02x}", r, g, b)
+}
+
+// Keys by position on the keypad, see Platform::keypad.
+fn key2btn(key: &str, keypad: [[u8; 4]; 4]) -> Option<usize> {
+    info!("bey2btn...!");
+
+    let (row, col) = match key {
+        "1" => (0, 0),
+        "2" => (0, 1),
+        "3" => (0, 2),
+        "4" => (0, 3),
+        "q" => (1, 0),
+        "w" => (1, 1),
+        "e" => (1, 2),
+        "r" => (1, 3),
+        "a" => (2, 0),
+        "s" => (2, 1),
+        "d" => (2, 2),
+        "f" => (2, 3),
+        "z" => (3, 0),
+        "x" => (3, 1),
+        "c" => (3, 2),
+        "v" => (3, 3),
+        _ => return None,
+    };
+    Some(keypad[row][col] as usize)
+}
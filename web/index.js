@@ -3,7 +3,6 @@ import init, * as wasm from "../wasm.js";
 const WIDTH = 64;
 const HEIGHT = 32;
 const SCALE = 15;
-const TICKS_PER_FRAME = 10;
 let anim_frame = 0;
 
 const canvas = document.getElementById("canvas");
@@ -25,6 +24,16 @@ async function run() {
     return;
   }
 
+  // Optional: programs.json from the chip-8-database, next to index.html.
+  try {
+    const resp = await fetch("programs.json");
+    if (resp.ok) {
+      chip8.load_database(await resp.text());
+    }
+  } catch (e) {
+    console.warn("No ROM database:", e);
+  }
+
   document.addEventListener("keydown", function (evt) {
     chip8.keypress(evt, true);
   });
@@ -59,13 +68,26 @@ async function run() {
 
         // 检查 ROM 前几个字节的内容，确保数据读取正常
         console.log("First few bytes of ROM: ", rom.slice(0, 10));
-        chip8.reset();
 
         try {
-          chip8.load_game(rom);
+          if (file.name.toLowerCase().endsWith(".zip")) {
+            // A pack of ROMs: ask which one to play.
+            const which = prompt(
+              "ROMs in " + file.name + ":\n" + chip8.pack_contents(rom) +
+                "Play which one (index or name)?",
+              "0",
+            );
+            chip8.load_from_pack(rom, which || "0");
+          } else {
+            chip8.load_game(rom, file.name);
+          }
         } catch (e) {
           console.error("Error calling load_game:", e);
         }
+        const title = chip8.title();
+        if (title) {
+          console.log("Recognised " + title + "\n" + chip8.key_hints());
+        }
         mainloop(chip8);
       };
       // 错误处理：如果文件读取失败
@@ -77,15 +99,26 @@ async function run() {
     false,
   );
   function mainloop(chip8) {
-    for (let i = 0; i < TICKS_PER_FRAME; i++) {
+    const ticks = chip8.tickrate();
+    for (let i = 0; i < ticks; i++) {
       chip8.tick();
     }
     chip8.tick_timers();
 
-    ctx.fillStyle = "black";
-    ctx.fillRect(0, 0, WIDTH * SCALE, HEIGHT * SCALE);
-    ctx.fillStyle = "white";
-    chip8.draw_screen(SCALE);
+    // Keep the canvas about as wide as the 64 pixel screen, whatever size
+    // the game's screen is.
+    const scale = Math.floor((WIDTH * SCALE) / chip8.screen_width());
+    const width = chip8.screen_width() * scale;
+    const height = chip8.screen_height() * scale;
+    if (canvas.width !== width || canvas.height !== height) {
+      canvas.width = width;
+      canvas.height = height;
+    }
+
+    ctx.fillStyle = chip8.background();
+    ctx.fillRect(0, 0, canvas.width, canvas.height);
+    ctx.fillStyle = chip8.foreground();
+    chip8.draw_screen(scale);
 
     anim_frame = window.requestAnimationFrame(() => {
       mainloop(chip8);